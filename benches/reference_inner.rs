@@ -0,0 +1,26 @@
+use bencher::{benchmark_group, benchmark_main, black_box, Bencher};
+use reflect::runtime::RuntimeType;
+use reflect::*;
+
+fn nested_reference() -> Type {
+    let mut param_map = SynParamMap::new();
+    Path::path_from_str(
+        "::std::vec::Vec<::std::collections::HashMap<::std::string::String, ::std::vec::Vec<u8>>>",
+        &mut param_map,
+    )
+    .SELF()
+    .reference()
+}
+
+fn as_reference_inner(b: &mut Bencher) {
+    let ty = nested_reference();
+    b.iter(|| black_box(&ty).as_reference_inner().is_some());
+}
+
+fn dereference(b: &mut Bencher) {
+    let ty = nested_reference();
+    b.iter(|| black_box(&ty).dereference());
+}
+
+benchmark_group!(benches, as_reference_inner, dereference);
+benchmark_main!(benches);
@@ -0,0 +1,93 @@
+use bencher::{benchmark_group, benchmark_main, black_box, Bencher};
+use quote::quote;
+use reflect::*;
+
+library! {
+    use memo {
+        trait Bound {
+            fn bound(&self);
+        }
+
+        trait First {
+            fn first(&self);
+        }
+
+        trait Second {
+            fn second(&self);
+        }
+
+        trait Third {
+            fn third(&self);
+        }
+
+        trait Fourth {
+            fn fourth(&self);
+        }
+
+        trait Fifth {
+            fn fifth(&self);
+        }
+    }
+}
+
+fn input() -> proc_macro2::TokenStream {
+    quote! {
+        struct Record<A, B, C, D> {
+            a: Vec<Option<A>>,
+            b: Option<Vec<B>>,
+            c: (C, Vec<D>),
+            d: Vec<Vec<(A, B)>>,
+        }
+    }
+}
+
+// Five impls over one struct, each bounding every field
+macro_rules! make_five_impls {
+    ($batch:expr) => {
+        make_five_impls!($batch, First first, Second second, Third third, Fourth fourth, Fifth fifth)
+    };
+    ($batch:expr, $($trait:ident $method:ident),*) => {
+        $(
+            $batch.make_trait_impl(RUNTIME::memo::$trait, $batch.target_type(), |block| {
+                block.make_function(RUNTIME::memo::$trait::$method, |make_function| {
+                    let receiver = make_function.arg(0);
+                    match receiver.data() {
+                        Data::Struct(Struct::Struct(receiver)) => {
+                            for field in receiver.fields() {
+                                RUNTIME::memo::Bound::bound.INVOKE(field.get_value());
+                            }
+                            make_function.unit()
+                        }
+                        _ => unimplemented!(),
+                    }
+                });
+            });
+        )*
+    };
+}
+
+fn derive_shared(ex: Execution) {
+    ex.make_impl_batch(|batch| {
+        make_five_impls!(batch);
+    });
+}
+
+fn derive_per_impl(ex: Execution) {
+    ex.make_impl_batch(|batch| {
+        batch.set_per_impl_memo(true);
+        make_five_impls!(batch);
+    });
+}
+
+fn shared_memo(b: &mut Bencher) {
+    let input = input();
+    b.iter(|| reflect::derive(black_box(input.clone()), derive_shared));
+}
+
+fn per_impl_memo(b: &mut Bencher) {
+    let input = input();
+    b.iter(|| reflect::derive(black_box(input.clone()), derive_per_impl));
+}
+
+benchmark_group!(benches, shared_memo, per_impl_memo);
+benchmark_main!(benches);
@@ -7,9 +7,9 @@ use quote::{quote, ToTokens};
 use syn::parse::{Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
 use syn::{
-    braced, parenthesized, parse_macro_input, token, GenericArgument, GenericParam, Generics,
-    Ident, Lifetime, Path, PathArguments, PathSegment, ReturnType, Token, TypeImplTrait,
-    TypeParamBound, TypeTraitObject, WherePredicate,
+    braced, bracketed, parenthesized, parse_macro_input, token, Expr, GenericArgument,
+    GenericParam, Generics, Ident, Lifetime, Path, PathArguments, PathSegment, ReturnType, Token,
+    TypeImplTrait, TypeParamBound, TypeTraitObject, WherePredicate,
 };
 
 use self::proc_macro::TokenStream;
@@ -42,17 +42,26 @@ struct ItemImpl {
 }
 
 struct ItemTrait {
+    unsafety: bool,
     ident: Ident,
     generics: Generics,
+    /// The names of the associated types, like `Item` of `type Item;`
+    assoc_types: Vec<Ident>,
     functions: Vec<Function>,
 }
 
 struct Function {
+    constness: bool,
+    asyncness: bool,
+    unsafety: bool,
     name: Ident,
     generics: Generics,
     receiver: Receiver,
     args: Vec<Type>,
     ret: Option<Type>,
+    /// Whether a trait method is declared with a default body, which is
+    /// skipped
+    has_default: bool,
 }
 
 struct ItemMacro {
@@ -66,6 +75,7 @@ enum Receiver {
         is_mut: bool,
         lifetime: Option<Lifetime>,
     },
+    SelfByWrapper(Type),
 }
 
 enum Type {
@@ -78,6 +88,16 @@ enum Type {
         lifetime: Option<Lifetime>,
         inner: Box<Type>,
     },
+    /// `<Type as Trait>::Assoc`
+    Projection {
+        self_ty: Box<Type>,
+        trait_path: Path,
+        assoc: Ident,
+    },
+    /// `[T]`
+    Slice(Box<Type>),
+    /// `[T; N]`, with the length kept as it is written
+    Array(Box<Type>, Box<Expr>),
 }
 
 enum ParentKind {
@@ -122,7 +142,7 @@ impl Item {
             input.parse().map(Item::Type)
         } else if lookahead.peek(Token![impl]) {
             input.parse().map(Item::Impl)
-        } else if lookahead.peek(Token![trait]) {
+        } else if lookahead.peek(Token![trait]) || lookahead.peek(Token![unsafe]) {
             input.parse().map(Item::Trait)
         } else if lookahead.peek(Token![macro]) {
             input.parse().map(Item::Macro)
@@ -191,7 +211,14 @@ impl Parse for ItemImpl {
         braced!(content in input);
         let mut functions = Vec::new();
         while !content.is_empty() {
-            functions.push(content.parse()?);
+            let function: Function = content.parse()?;
+            if function.has_default {
+                return Err(syn::Error::new(
+                    function.name.span(),
+                    "only trait methods can have a default body",
+                ));
+            }
+            functions.push(function);
         }
 
         Ok(ItemImpl {
@@ -204,6 +231,7 @@ impl Parse for ItemImpl {
 
 impl Parse for ItemTrait {
     fn parse(input: ParseStream) -> Result<Self> {
+        let unsafety = input.parse::<Option<Token![unsafe]>>()?.is_some();
         input.parse::<Token![trait]>()?;
 
         let ident = input.parse()?;
@@ -217,14 +245,27 @@ impl Parse for ItemTrait {
 
         let content;
         braced!(content in input);
+        let mut assoc_types = Vec::new();
         let mut functions = Vec::new();
         while !content.is_empty() {
-            functions.push(content.parse()?);
+            if content.peek(Token![type]) {
+                content.parse::<Token![type]>()?;
+                assoc_types.push(content.parse()?);
+                if content.peek(Token![:]) {
+                    content.parse::<Token![:]>()?;
+                    Punctuated::<TypeParamBound, Token![+]>::parse_separated_nonempty(&content)?;
+                }
+                content.parse::<Token![;]>()?;
+            } else {
+                functions.push(content.parse()?);
+            }
         }
 
         Ok(ItemTrait {
+            unsafety,
             ident,
             generics,
+            assoc_types,
             functions,
         })
     }
@@ -232,6 +273,9 @@ impl Parse for ItemTrait {
 
 impl Parse for Function {
     fn parse(input: ParseStream) -> Result<Self> {
+        let constness = input.parse::<Option<Token![const]>>()?.is_some();
+        let asyncness = input.parse::<Option<Token![async]>>()?.is_some();
+        let unsafety = input.parse::<Option<Token![unsafe]>>()?.is_some();
         input.parse::<Token![fn]>()?;
         let name: Ident = input.parse()?;
 
@@ -267,14 +311,26 @@ impl Parse for Function {
 
         generics.where_clause = input.parse()?;
 
-        input.parse::<Token![;]>()?;
+        let has_default = if input.peek(token::Brace) {
+            let body;
+            braced!(body in input);
+            body.parse::<TokenStream2>()?;
+            true
+        } else {
+            input.parse::<Token![;]>()?;
+            false
+        };
 
         Ok(Function {
+            constness,
+            asyncness,
+            unsafety,
             name,
             generics,
             receiver,
             args,
             ret,
+            has_default,
         })
     }
 }
@@ -284,7 +340,12 @@ impl Parse for Receiver {
         let fork = input.fork();
         if input.peek(Token![self]) {
             input.parse::<Token![self]>()?;
-            Ok(Receiver::SelfByValue)
+            if input.peek(Token![:]) {
+                input.parse::<Token![:]>()?;
+                Ok(Receiver::from_self_type(input.parse()?))
+            } else {
+                Ok(Receiver::SelfByValue)
+            }
         } else if fork.parse::<Token![&]>().is_ok()
             && fork.parse::<Option<Lifetime>>().is_ok()
             && fork.parse::<Option<Token![mut]>>().is_ok()
@@ -326,11 +387,39 @@ impl Parse for Type {
                 lifetime,
                 inner: Box::new(inner),
             })
+        } else if lookahead.peek(token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            let elem: Type = content.parse()?;
+            if content.is_empty() {
+                return Ok(Type::Slice(Box::new(elem)));
+            }
+            content.parse::<Token![;]>()?;
+            let len: Expr = content.parse()?;
+
+            Ok(Type::Array(Box::new(elem), Box::new(len)))
         } else if lookahead.peek(Token![dyn]) {
             Ok(Type::TraitObject(input.parse()?))
         } else if lookahead.peek(Token![impl]) {
             Ok(Type::ImplTrait(input.parse()?))
-        } else if lookahead.peek(Ident) || lookahead.peek(Token![::]) {
+        } else if lookahead.peek(Token![<]) {
+            input.parse::<Token![<]>()?;
+            let self_ty: Type = input.parse()?;
+            input.parse::<Token![as]>()?;
+            let trait_path: Path = input.parse()?;
+            input.parse::<Token![>]>()?;
+            input.parse::<Token![::]>()?;
+            let assoc: Ident = input.parse()?;
+
+            Ok(Type::Projection {
+                self_ty: Box::new(self_ty),
+                trait_path,
+                assoc,
+            })
+        } else if lookahead.peek(Ident)
+            || lookahead.peek(Token![::])
+            || lookahead.peek(Token![Self])
+        {
             input.parse().map(Type::Path)
         } else {
             Err(lookahead.error())
@@ -350,6 +439,20 @@ impl Parse for ItemMacro {
 }
 
 impl Receiver {
+    /// `self: Self` and `self: &Self` are the same as `self` and `&self`, any
+    /// other self type is kept as a wrapper around `Self`
+    fn from_self_type(ty: Type) -> Self {
+        match ty {
+            ref ty if ty.is_self() => Receiver::SelfByValue,
+            Type::Reference {
+                is_mut,
+                lifetime,
+                ref inner,
+            } if inner.is_self() => Receiver::SelfByReference { is_mut, lifetime },
+            ty => Receiver::SelfByWrapper(ty),
+        }
+    }
+
     fn is_none(&self) -> bool {
         match self {
             Receiver::NoSelf => true,
@@ -358,6 +461,15 @@ impl Receiver {
     }
 }
 
+impl Type {
+    fn is_self(&self) -> bool {
+        match self {
+            Type::Path(path) => path.is_ident("Self"),
+            _ => false,
+        }
+    }
+}
+
 #[proc_macro]
 pub fn library(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as Input);
@@ -435,6 +547,8 @@ fn declare_parent(
     mod_path: &Path,
     params: &[&GenericParam],
     parent_kind: ParentKind,
+    unsafety: bool,
+    assoc_types: &[Ident],
 ) -> TokenStream2 {
     let set_parent_params = if !generics.params.is_empty() {
         let param_strings = generics.params.iter().cloned().map(|mut param| {
@@ -462,6 +576,23 @@ fn declare_parent(
         None
     };
 
+    let set_unsafe = if unsafety {
+        Some(quote! {
+            parent_builder.set_unsafe();
+        })
+    } else {
+        None
+    };
+
+    let set_assoc_types = if !assoc_types.is_empty() {
+        let assoc_strings = assoc_types.iter().map(Ident::to_string);
+        Some(quote! {
+            parent_builder.set_assoc_types(&[#(#assoc_strings),*]);
+        })
+    } else {
+        None
+    };
+
     let parent = &parent_type.ident;
     let parent_kind = match parent_kind {
         ParentKind::Trait => quote! {
@@ -491,6 +622,8 @@ fn declare_parent(
                         let mut parent_builder = _reflect::ParentBuilder::new(#parent_kind);
                         #set_parent_params
                         #set_parent_constraints
+                        #set_unsafe
+                        #set_assoc_types
                         parent_builder.set_path(|param_map: &mut _reflect::SynParamMap| #get_runtime_path);
                         ::std::rc::Rc::new(parent_builder.into_parent())
                     };
@@ -511,6 +644,8 @@ fn declare_impl(item: &ItemImpl, mod_path: &Path) -> TokenStream2 {
         mod_path,
         params,
         ParentKind::Impl,
+        false,
+        &[],
     );
 
     let functions = item
@@ -541,6 +676,8 @@ fn declare_trait(item: &ItemTrait, mod_path: &Path) -> TokenStream2 {
         mod_path,
         params,
         ParentKind::Trait,
+        item.unsafety,
+        &item.assoc_types,
     );
 
     let functions = item
@@ -572,6 +709,12 @@ fn declare_function(
         Receiver::SelfByValue => Some(quote! {
             sig.set_self_by_value();
         }),
+        Receiver::SelfByWrapper(ref ty) => {
+            let ty = to_runtime_type(ty, mod_path, params);
+            Some(quote! {
+                sig.set_self_by_wrapper(|param_map: &mut _reflect::SynParamMap| {#ty});
+            })
+        }
         Receiver::SelfByReference {
             is_mut,
             ref lifetime,
@@ -599,6 +742,34 @@ fn declare_function(
             }
         },
     };
+    let set_const = if function.constness {
+        Some(quote! {
+            sig.set_const();
+        })
+    } else {
+        None
+    };
+    let set_has_default = if function.has_default {
+        Some(quote! {
+            fun.set_has_default();
+        })
+    } else {
+        None
+    };
+    let set_async = if function.asyncness {
+        Some(quote! {
+            sig.set_async();
+        })
+    } else {
+        None
+    };
+    let set_unsafe = if function.unsafety {
+        Some(quote! {
+            sig.set_unsafe();
+        })
+    } else {
+        None
+    };
     let params: &Vec<_> = &function
         .generics
         .params
@@ -671,6 +842,9 @@ fn declare_function(
                                 #set_sig_params
                                 #add_parent_params
                                 #set_sig_constraints
+                                #set_const
+                                #set_async
+                                #set_unsafe
                                 #setup_receiver
                                 #(
                                     #setup_inputs
@@ -678,6 +852,7 @@ fn declare_function(
                                 #set_output
                                 let mut fun = _reflect::Function::get_function(#name_str, sig);
                                 fun.set_parent(parent);
+                                #set_has_default
                                 ::std::rc::Rc::new(fun)
                             };
                         };
@@ -807,9 +982,46 @@ fn to_runtime_type(ty: &Type, mod_path: &Path, params: &[&GenericParam]) -> Toke
                 }
             }
         }
+
+        Type::Projection {
+            self_ty,
+            trait_path,
+            assoc,
+        } => {
+            let self_ty = to_runtime_type(self_ty, mod_path, params);
+            let trait_path = to_runtime_path(trait_path, mod_path, params);
+            let assoc_str = assoc.to_string();
+            quote! {
+                _reflect::Type::projection(&#self_ty, #trait_path, #assoc_str)
+            }
+        }
+
+        Type::Slice(elem) => {
+            let elem = to_runtime_type(elem, mod_path, params);
+            quote! {
+                #elem.slice()
+            }
+        }
+
+        Type::Array(elem, len) => {
+            let elem = to_runtime_type(elem, mod_path, params);
+            let len_str = len.to_token_stream().to_string();
+            quote! {
+                #elem.array(#len_str)
+            }
+        }
     }
 }
 
+/// Primitive types are in scope everywhere, and are never module defined
+fn ident_is_primitive(ident: &Ident) -> bool {
+    const PRIMITIVE_TYPES: &[&str] = &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ];
+    PRIMITIVE_TYPES.iter().any(|primitive| ident == primitive)
+}
+
 fn ident_is_param(ident: &Ident, params: &[&GenericParam]) -> bool {
     params.iter().any(|param| {
         if let GenericParam::Type(type_param) = param {
@@ -836,7 +1048,16 @@ fn to_runtime_path(path: &Path, mod_path: &Path, params: &[&GenericParam]) -> To
     expand_path_arguments(arguments, mod_path, params);
 
     // Check if path is defined in current module
-    if path.segments.len() == 1 && path.leading_colon.is_none() {
+    if path.is_ident("Self") {
+        quote! {
+            _reflect::Path::path_from_str("Self", param_map)
+        }
+    } else if path.get_ident().is_some_and(ident_is_primitive) {
+        let path_str = path.to_token_stream().to_string();
+        quote! {
+            _reflect::Path::path_from_str(#path_str, param_map)
+        }
+    } else if path.segments.len() == 1 && path.leading_colon.is_none() {
         let segment = path.segments[0].to_token_stream().to_string();
         quote! {
             MODULE().get_path(#segment, param_map)
@@ -916,7 +1137,7 @@ fn expand_path(path: &mut syn::Path, mod_path: &Path, params: &[&GenericParam])
         let segment = &path.segments[0];
         let ident = &segment.ident;
 
-        if !ident_is_param(ident, params) {
+        if !ident_is_param(ident, params) && !ident_is_primitive(ident) && ident != "Self" {
             let mut segments = Punctuated::new();
             segments.extend(mod_path.segments.iter().cloned());
             segments.push(segment.clone());
@@ -1,15 +1,20 @@
 use crate::ident::Ident;
+#[cfg(feature = "pretty")]
+use crate::{pretty::pretty_print, ReflectError};
 use crate::{
-    Function, GlobalBorrow, InvokeRef, MacroInvokeRef, Parent, Print, Receiver, SimplePath,
-    TraitInferenceResult, Type, TypeNode, ValueNode, ValueRef, INVOKES, MACROS, VALUES,
+    Data, Function, GenericConstraint, GenericParam, GlobalBorrow, InvokeRef, MacroInvokeRef,
+    Parent, Path, Print, Receiver, SimplePath, Struct, TraitInferenceResult, Type, TypeNode,
+    ValueNode, ValueRef, Variant, Verbatim, WhereClauseStyle, CODEGEN_OPTIONS, INVOKES, MACROS,
+    VALUES,
 };
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use ref_cast::RefCast;
+use std::cell::Cell;
 use std::collections::BTreeSet as Set;
 use std::ops::Range;
 use std::rc::Rc;
-use syn::Token;
+use syn::{Attribute, Meta, Token};
 
 #[derive(Debug)]
 pub(crate) struct Program {
@@ -17,12 +22,20 @@ pub(crate) struct Program {
     pub impls: Vec<CompleteImpl>,
 }
 
+/// An impl whose trait bounds have been inferred, ready to be emitted. Lent
+/// out by [`derive_with_impls`](crate::derive_with_impls) for inspection.
 #[derive(Debug)]
-pub(crate) struct CompleteImpl {
-    pub trait_ty: Option<Rc<Parent>>,
-    pub ty: Type,
-    pub functions: Vec<CompleteFunction>,
-    pub result: Option<TraitInferenceResult>,
+pub struct CompleteImpl {
+    pub(crate) trait_ty: Option<Rc<Parent>>,
+    pub(crate) ty: Type,
+    /// Whether to emit an `unsafe impl`, as needed for an `unsafe trait`
+    pub(crate) unsafety: bool,
+    pub(crate) functions: Vec<CompleteFunction>,
+    /// The `type Name = Type;` items of the implemented trait
+    pub(crate) assoc_types: Vec<(Ident, Type)>,
+    /// The predicates of the `#[cfg(..)]` attributes on the impl
+    pub(crate) cfgs: Vec<Meta>,
+    pub(crate) result: Option<TraitInferenceResult>,
 }
 
 #[derive(Debug)]
@@ -33,6 +46,12 @@ pub(crate) struct CompleteFunction {
     pub invokes: Range<InvokeRef>,
     pub macros: Range<MacroInvokeRef>,
     pub ret: Option<ValueRef>,
+    /// The predicates of the `#[cfg(..)]` attributes on the function
+    pub cfgs: Vec<Meta>,
+    /// Other attributes on the function, like `#[inline]`
+    pub attrs: Vec<Attribute>,
+    /// The parameters declared `mut`, by the name of their binding
+    pub mut_args: Vec<Ident>,
 }
 
 impl Program {
@@ -46,31 +65,118 @@ impl Program {
 }
 
 impl CompleteImpl {
-    fn compile(&self) -> TokenStream {
-        let functions = self.functions.iter().map(CompleteFunction::compile);
+    /// The type the impl is for.
+    ///
+    /// ```
+    /// use quote::quote;
+    /// use reflect::*;
+    ///
+    /// library! {
+    ///     use greet {
+    ///         trait Greet {
+    ///             fn greet(&self);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn derive(ex: Execution) {
+    ///     ex.make_trait_impl(RUNTIME::greet::Greet, ex.target_type(), |block| {
+    ///         block.make_function(RUNTIME::greet::Greet::greet, |make_function| {
+    ///             make_function.unit()
+    ///         });
+    ///     });
+    /// }
+    ///
+    /// reflect::derive_with_impls(quote!(struct World;), derive, |impls| {
+    ///     let mut param_map = SynParamMap::new();
+    ///     assert_eq!(impls[0].self_type().display(&param_map), "World");
+    ///     let greet = Path::path_from_str("::greet::Greet", &mut param_map);
+    ///     assert_eq!(impls[0].trait_path(), Some(&greet));
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn self_type(&self) -> &Type {
+        &self.ty
+    }
 
-        let name = if let TypeNode::DataStructure(data) = &self.ty.0 {
-            &data.name
-        } else {
-            panic!()
+    /// The path of the implemented trait, or `None` for an inherent impl
+    pub fn trait_path(&self) -> Option<&Path> {
+        self.trait_ty.as_ref().map(|parent| &parent.path)
+    }
+
+    /// The impl formatted for reading. The generated code is parsed first,
+    /// so that invalid code is reported instead of printed. Needs the
+    /// "pretty" feature.
+    #[cfg(feature = "pretty")]
+    pub fn to_pretty_string(&self) -> Result<String, ReflectError> {
+        let tokens = self.compile();
+        syn::parse2::<syn::ItemImpl>(tokens.clone())
+            .map_err(|error| ReflectError::InvalidOutput { error })?;
+        Ok(pretty_print(tokens))
+    }
+
+    fn compile(&self) -> TokenStream {
+        let in_trait_impl = self.trait_ty.is_some();
+        let functions = self
+            .functions
+            .iter()
+            .map(|function| function.compile(in_trait_impl));
+        let assoc_types = self.assoc_types.iter().map(|(name, ty)| {
+            // The target type is written as `Self`, like in the where clause
+            let ty = match &ty.0 {
+                TypeNode::DataStructure(_) => quote!(Self),
+                _ => Print::ref_cast(ty).to_token_stream(),
+            };
+            quote!(type #name = #ty;)
+        });
+
+        let name = match &self.ty.0 {
+            TypeNode::DataStructure(data) => data.name.to_token_stream(),
+            // The self type of a blanket impl, or a wrapper of the data
+            // structure like `&Foo<T>`, which gets its arguments below
+            _ => Print::ref_cast(&self.ty).to_token_stream(),
         };
+        let inline = CODEGEN_OPTIONS.with(Cell::get).where_clause_style == WhereClauseStyle::Inline;
         let (params, self_ty_args, where_clause, trait_ty) = if let Some(result) = &self.result {
-            let params = result.generic_params.iter().map(Print::ref_cast);
-            let params = if result.generic_params.is_empty() {
+            let mut constraints: Vec<_> = result.constraints.set.iter().collect();
+            constraints.sort_by_cached_key(|constraint| constraint.sort_key());
+            let params: Vec<_> = result
+                .generic_params
+                .iter()
+                .map(|param| {
+                    let param_tokens = Print::ref_cast(param);
+                    // The bounds of the self param of a blanket impl are
+                    // always written inline, `impl<T: Bound> Trait for T`
+                    let is_self = match (param, &self.ty.0) {
+                        (GenericParam::Type(param), TypeNode::TypeParam(self_param)) => {
+                            param == self_param
+                        }
+                        _ => false,
+                    };
+                    if inline || is_self {
+                        let bounds = take_inline_bounds(param, &mut constraints);
+                        if !bounds.is_empty() {
+                            return quote!(#param_tokens: #(#bounds)+*);
+                        }
+                    }
+                    param_tokens.to_token_stream()
+                })
+                .collect();
+            let params = if params.is_empty() {
                 None
             } else {
                 Some(quote!(<#(#params),*>))
             };
-            let constraints = result.constraints.set.iter().map(Print::ref_cast);
             let self_ty_args = result.data_struct_args.args.iter().map(Print::ref_cast);
             let self_ty_args = if result.data_struct_args.args.is_empty() {
                 None
             } else {
                 Some(quote!(<#(#self_ty_args),*>))
             };
-            let where_clause = if result.constraints.set.is_empty() {
+            let where_clause = if constraints.is_empty() {
                 None
             } else {
+                let constraints = constraints.into_iter().map(Print::ref_cast);
                 Some(quote!(where #(#constraints,)*))
             };
             let trait_ty = self.trait_ty.as_ref().map(|parent| {
@@ -94,15 +200,39 @@ impl CompleteImpl {
             (None, None, None, trait_ty)
         };
 
+        // The arguments of a wrapped data structure go inside the wrapper
+        let (name, self_ty_args) = match &self.ty.0 {
+            TypeNode::DataStructure(_) | TypeNode::TypeParam(_) => (name, self_ty_args),
+            wrapper => {
+                let mut wrapper = wrapper.clone();
+                if let Some(node) = wrapper.wrapped_data_structure_mut() {
+                    if let TypeNode::DataStructure(data) = node {
+                        let data_name = &data.name;
+                        *node = TypeNode::Verbatim(Verbatim(quote!(#data_name #self_ty_args)));
+                    }
+                }
+                (Print::ref_cast(&wrapper).to_token_stream(), None)
+            }
+        };
+
+        let unsafety = if self.unsafety {
+            Some(quote!(unsafe))
+        } else {
+            None
+        };
+        let cfgs = &self.cfgs;
+
         if let Some(trait_ty) = trait_ty {
             quote! {
-                // FIXME: assosiated types
-                impl #params #trait_ty for #name #self_ty_args #where_clause {
+                #(#[cfg(#cfgs)])*
+                #unsafety impl #params #trait_ty for #name #self_ty_args #where_clause {
+                    #(#assoc_types)*
                     #(#functions)*
                 }
             }
         } else {
             quote! {
+                #(#[cfg(#cfgs)])*
                 impl #params #name #self_ty_args #where_clause {
                     #(#functions)*
                 }
@@ -111,14 +241,61 @@ impl CompleteImpl {
     }
 }
 
+/// Removes the predicates that bound `param` itself from `constraints` and
+/// returns their bounds, to be written next to the param
+fn take_inline_bounds(
+    param: &GenericParam,
+    constraints: &mut Vec<&GenericConstraint>,
+) -> Vec<TokenStream> {
+    let mut bounds = Vec::new();
+    constraints.retain(|constraint| match (param, constraint) {
+        (GenericParam::Type(param), GenericConstraint::Type(pred_ty))
+            if pred_ty.lifetimes.is_empty()
+                && pred_ty.bounded_ty.0 == TypeNode::TypeParam(*param) =>
+        {
+            bounds.extend(
+                pred_ty
+                    .bounds
+                    .iter()
+                    .map(|bound| Print::ref_cast(bound).to_token_stream()),
+            );
+            false
+        }
+        (GenericParam::Lifetime(param), GenericConstraint::Lifetime(lifetime_def))
+            if lifetime_def.lifetime == *param =>
+        {
+            bounds.extend(
+                lifetime_def
+                    .bounds
+                    .iter()
+                    .map(|bound| Print::ref_cast(bound).to_token_stream()),
+            );
+            false
+        }
+        _ => true,
+    });
+    bounds
+}
+
 impl CompleteFunction {
-    fn compile(&self) -> TokenStream {
+    fn compile(&self, in_trait_impl: bool) -> TokenStream {
         let name = Ident::new(&self.f.name);
 
-        let generics = &self.f.sig.generics;
-        let (params, where_clause) = if !generics.params.is_empty() {
-            let params = generics.params.iter().map(Print::ref_cast);
-            let params = Some(quote!(<#(#params),*>));
+        let mut sig = self.f.sig.clone();
+        if CODEGEN_OPTIONS.with(Cell::get).preserve_elided_lifetimes {
+            sig.restore_elided_lifetimes();
+        }
+
+        let generics = &sig.generics;
+        let (params, where_clause) = if generics.is_empty() {
+            (None, None)
+        } else {
+            let params = if generics.params.is_empty() {
+                None
+            } else {
+                let params = generics.params.iter().map(Print::ref_cast);
+                Some(quote!(<#(#params),*>))
+            };
             let where_clause = if generics.constraints.is_empty() {
                 None
             } else {
@@ -126,21 +303,31 @@ impl CompleteFunction {
                 Some(quote!(where #(#constraints,)*))
             };
             (params, where_clause)
-        } else {
-            (None, None)
+        };
+
+        let mut_token = |binding: &Ident| {
+            if self.mut_args.contains(binding) {
+                Some(quote!(mut))
+            } else {
+                None
+            }
         };
 
         let mut inputs = Vec::new();
-        inputs.extend(receiver_tokens(self.f.sig.receiver));
-        for (i, input) in self.f.sig.inputs.iter().enumerate() {
+        inputs.extend(receiver_tokens(&sig.receiver).map(|receiver| {
+            let mut_self = mut_token(&Ident::new("self"));
+            quote!(#mut_self #receiver)
+        }));
+        for (i, input) in sig.inputs.iter().enumerate() {
             let binding = Ident::new(format!("__arg{}", i));
+            let mut_arg = mut_token(&binding);
             let ty = Print::ref_cast(input);
             inputs.push(quote! {
-                #binding : #ty
+                #mut_arg #binding : #ty
             });
         }
 
-        let output = match &self.f.sig.output {
+        let output = match &sig.output {
             Type(TypeNode::Tuple(types)) if types.is_empty() => None,
             other => {
                 let ty = Print::ref_cast(other);
@@ -148,46 +335,107 @@ impl CompleteFunction {
             }
         };
 
+        // Functions in a trait impl cannot be declared const (E0379)
+        let constness = if sig.constness && !in_trait_impl {
+            Some(quote!(const))
+        } else {
+            None
+        };
+        let asyncness = if sig.asyncness {
+            Some(quote!(async))
+        } else {
+            None
+        };
+        let unsafety = if sig.unsafety {
+            Some(quote!(unsafe))
+        } else {
+            None
+        };
+
+        let values = self.compile_block(self.values.clone());
+
+        let ret = self.ret.map(|v| self.compile_operand(v));
+        let cfgs = &self.cfgs;
+        let attrs = &self.attrs;
+
+        quote! {
+            #(#[cfg(#cfgs)])*
+            #(#attrs)*
+            #constness #asyncness #unsafety fn #name #params (#(#inputs),*) #output #where_clause {
+                #(#values)*
+                #ret
+            }
+        }
+    }
+
+    /// Compiles the values in `range` into statements, leaving out the
+    /// values of the match arms nested in it, which are compiled in their arm
+    fn compile_block(&self, range: Range<ValueRef>) -> Vec<TokenStream> {
         let reachable = self.compute_reachability();
         let mutable = self.compute_mutability();
+        let nested_arms = self.arm_ranges();
+        let in_nested_arm = |v: ValueRef| {
+            nested_arms.iter().any(|arm| {
+                arm.contains(&v)
+                    && *arm != range
+                    && range.start <= arm.start
+                    && arm.end <= range.end
+            })
+        };
         VALUES.with_borrow(|value_nodes| {
-            let values = self.refs().flat_map(|v| {
-                // Don't create let bindings for inlineable values
-                if value_nodes[v.0].inlineable() {
-                    return None;
-                }
+            (range.start.0..range.end.0)
+                .map(ValueRef)
+                .filter(|&v| !in_nested_arm(v))
+                .flat_map(|v| {
+                    // Don't create let bindings for inlineable values
+                    if self.is_inlined(&value_nodes[v.0]) {
+                        return None;
+                    }
 
-                let expr = self.compile_value(v);
-                if reachable.contains(&v) {
-                    let let_mut = if mutable.contains(&v) {
-                        quote!(let mut)
+                    let expr = self.compile_value(v);
+                    if reachable.contains(&v) {
+                        let let_mut = if mutable.contains(&v) {
+                            quote!(let mut)
+                        } else {
+                            quote!(let)
+                        };
+                        let binding = v.binding();
+                        Some(quote! {
+                            #let_mut #binding = #expr;
+                        })
+                    } else if self.is_important(v) {
+                        Some(quote! {
+                            let _ = #expr;
+                        })
                     } else {
-                        quote!(let)
-                    };
-                    let binding = v.binding();
-                    Some(quote! {
-                        #let_mut #binding = #expr;
-                    })
-                } else if self.is_important(v) {
-                    Some(quote! {
-                        let _ = #expr;
-                    })
-                } else {
-                    None
-                }
-            });
+                        None
+                    }
+                })
+                .collect()
+        })
+    }
 
-            let ret = self.ret.map(|v| match &value_nodes[v.0] {
-                value if value.inlineable() => self.compile_value(v),
-                _ => v.binding().to_token_stream(),
-            });
+    /// The ranges of the values of every match arm and closure body in the
+    /// function
+    fn arm_ranges(&self) -> Vec<Range<ValueRef>> {
+        VALUES.with_borrow(|values| {
+            self.refs()
+                .flat_map(|v| match &values[v.0] {
+                    ValueNode::Match { arms, .. } => {
+                        arms.iter().map(|arm| arm.values.clone()).collect()
+                    }
+                    ValueNode::Closure { values, .. } => vec![values.clone()],
+                    _ => Vec::new(),
+                })
+                .collect()
+        })
+    }
 
-            quote! {
-                fn #name #params (#(#inputs),*) #output #where_clause {
-                    #(#values)*
-                    #ret
-                }
-            }
+    /// A value used by another value, either inlined or by its binding
+    fn compile_operand(&self, v: ValueRef) -> TokenStream {
+        VALUES.with_borrow(|values| match &values[v.0] {
+            value if self.is_inlined(value) => self.compile_value(v),
+            _ => v.binding().to_token_stream(),
         })
     }
 
@@ -208,7 +456,7 @@ impl CompleteFunction {
 
         while let Some(v) = stack.pop() {
             VALUES.with_borrow(|values| match &values[v.0] {
-                Tuple(values) => {
+                Tuple(values) | Array(values) => {
                     for &v in values.iter() {
                         if reachable.insert(v) {
                             stack.push(v);
@@ -216,7 +464,7 @@ impl CompleteFunction {
                     }
                 }
                 Str(s) => {}
-                Reference { value: v, .. } | Dereference(v) => {
+                Reference { value: v, .. } | Dereference(v) | Try(v) => {
                     if reachable.insert(*v) {
                         stack.push(*v);
                     }
@@ -241,7 +489,33 @@ impl CompleteFunction {
                         stack.push(*parent);
                     }
                 }
-                DataStructure { .. } => unimplemented!(),
+                Match { value, arms, .. } => {
+                    for &v in std::iter::once(value).chain(arms.iter().map(|arm| &arm.ret)) {
+                        if reachable.insert(v) {
+                            stack.push(v);
+                        }
+                    }
+                }
+                DataStructure { data, .. } => {
+                    for (_, field) in data.fields() {
+                        if reachable.insert(field.element) {
+                            stack.push(field.element);
+                        }
+                    }
+                }
+                PatternBinding { .. } => {}
+                Closure { ret, .. } => {
+                    if reachable.insert(*ret) {
+                        stack.push(*ret);
+                    }
+                }
+                Call { closure, args } => {
+                    for &v in std::iter::once(closure).chain(args) {
+                        if reachable.insert(v) {
+                            stack.push(v);
+                        }
+                    }
+                }
             })
         }
 
@@ -266,7 +540,14 @@ impl CompleteFunction {
 
     fn is_important(&self, v: ValueRef) -> bool {
         VALUES.with_borrow(|values| {
-            if let ValueNode::Invoke(_) | ValueNode::MacroInvocation(_) = values[v.0] {
+            // A `?` may return early, and the arms of a match may have
+            // effects, so they are kept even if their value is unused
+            if let ValueNode::Invoke(_)
+            | ValueNode::MacroInvocation(_)
+            | ValueNode::Try(_)
+            | ValueNode::Match { .. }
+            | ValueNode::Call { .. } = values[v.0]
+            {
                 return true;
             }
             false
@@ -282,9 +563,16 @@ impl CompleteFunction {
                     ( #values )
                 }
             }
+            ValueNode::Array(values) => {
+                let values = self.make_values_list(values);
+
+                quote! {
+                    [ #values ]
+                }
+            }
             ValueNode::Str(s) => quote! { #s },
             ValueNode::Reference { is_mut, value } if !is_mut => {
-                if values[value.0].inlineable() {
+                if self.is_inlined(&values[value.0]) {
                     let v = self.compile_value(*value);
                     quote! { &#v }
                 } else {
@@ -293,7 +581,7 @@ impl CompleteFunction {
                 }
             }
             ValueNode::Reference { is_mut, value } => {
-                if values[value.0].inlineable() {
+                if self.is_inlined(&values[value.0]) {
                     let v = self.compile_value(*value);
                     quote! { &mut #v }
                 } else {
@@ -302,7 +590,7 @@ impl CompleteFunction {
                 }
             }
             ValueNode::Dereference(v) => {
-                if values[v.0].inlineable() {
+                if self.is_inlined(&values[v.0]) {
                     let v = self.compile_value(*v);
                     quote! { *#v }
                 } else {
@@ -310,30 +598,56 @@ impl CompleteFunction {
                     quote! { *#v }
                 }
             }
+            ValueNode::Try(v) => {
+                let v = self.compile_operand(*v);
+                quote! { #v? }
+            }
             ValueNode::Binding { name, .. } => quote! { #name },
             ValueNode::Invoke(invoke) => INVOKES.with_borrow(|invokes| {
                 let invoke = &invokes[invoke.0];
-                let parent_type = match invoke.function.parent {
-                    Some(ref parent) => {
+                let parent_type = match (&invoke.function.parent, &invoke.qualified_self) {
+                    (Some(parent), Some(self_ty)) => {
+                        let print = Print::ref_cast(SimplePath::ref_cast(&parent.path));
+                        // The target type is `Self` inside its own impl
+                        let self_ty = match &self_ty.0 {
+                            TypeNode::DataStructure(_) => quote!(Self),
+                            _ => Print::ref_cast(self_ty).to_token_stream(),
+                        };
+                        Some(quote!(<#self_ty as #print>::))
+                    }
+                    (Some(parent), None) => {
                         let print = Print::ref_cast(SimplePath::ref_cast(&parent.path));
                         Some(quote!(#print ::))
                     }
-                    None => None,
+                    (None, _) => None,
                 };
                 let name = Ident::new(&invoke.function.name);
+                let turbofish = if invoke.type_args.is_empty() {
+                    None
+                } else {
+                    let type_args = invoke.type_args.iter().map(Print::ref_cast);
+                    Some(quote!(::<#(#type_args),*>))
+                };
                 let args = self.make_values_list(&invoke.args);
 
-                quote! {
-                    #parent_type #name ( #args )
+                if invoke.function.sig.unsafety {
+                    quote! {
+                        unsafe { #parent_type #name #turbofish ( #args ) }
+                    }
+                } else {
+                    quote! {
+                        #parent_type #name #turbofish ( #args )
+                    }
                 }
             }),
             ValueNode::Destructure {
                 parent,
                 accessor,
                 ty,
+                ..
             } => {
                 let mut node = &parent.node().get_type().0;
-                let parent = parent.binding();
+                let parent = self.compile_operand(*parent);
                 let accessor = Print::ref_cast(accessor);
                 let mut references = TokenStream::new();
 
@@ -346,7 +660,80 @@ impl CompleteFunction {
                 }
                 quote!(#references #parent.#accessor)
             }
-            ValueNode::DataStructure { .. } => unimplemented!(),
+            ValueNode::Match {
+                value,
+                arms,
+                wildcard,
+            } => {
+                let value = self.compile_operand(*value);
+                let arms = arms.iter().map(|arm| {
+                    let pattern = compile_pattern(&arm.variant);
+                    let values = self.compile_block(arm.values.clone());
+                    let ret = self.compile_operand(arm.ret);
+                    quote! {
+                        #pattern => {
+                            #(#values)*
+                            #ret
+                        }
+                    }
+                });
+                // The match is in the crate of the enum, where the variants
+                // are all known and the wildcard arm is unreachable
+                let wildcard = if *wildcard {
+                    Some(quote! {
+                        #[allow(unreachable_patterns)]
+                        _ => ::core::unreachable!(),
+                    })
+                } else {
+                    None
+                };
+                quote! {
+                    match #value {
+                        #(#arms)*
+                        #wildcard
+                    }
+                }
+            }
+            ValueNode::PatternBinding { .. } => v.binding().to_token_stream(),
+            ValueNode::Closure {
+                params,
+                values,
+                ret,
+            } => {
+                let params = params.iter().map(|param| param.binding());
+                let values = self.compile_block(values.clone());
+                let ret = self.compile_operand(*ret);
+                quote! {
+                    |#(#params),*| {
+                        #(#values)*
+                        #ret
+                    }
+                }
+            }
+            ValueNode::Call { closure, args } => {
+                let closure = closure.binding();
+                let args = self.make_values_list(args);
+                quote!(#closure(#args))
+            }
+            ValueNode::DataStructure { data, .. } => match data {
+                Data::Struct(Struct::Struct(data)) => {
+                    let fields = data.fields.iter().map(|field| {
+                        let accessor = Print::ref_cast(&field.accessor);
+                        let value = self.compile_operand(field.element);
+                        quote!(#accessor: #value)
+                    });
+                    quote!(Self { #(#fields),* })
+                }
+                Data::Struct(Struct::Tuple(data)) => {
+                    let fields = data
+                        .fields
+                        .iter()
+                        .map(|field| self.compile_operand(field.element));
+                    quote!(Self(#(#fields),*))
+                }
+                Data::Struct(Struct::Unit(_)) => quote!(Self),
+                Data::Enum(_) => unreachable!(),
+            },
             ValueNode::MacroInvocation(invoke) => MACROS.with_borrow(|macros| {
                 let invoke = &macros[invoke.0];
                 let path = Print::ref_cast(&invoke.macro_path);
@@ -361,11 +748,20 @@ impl CompleteFunction {
         })
     }
 
+    /// Whether the value is used in place instead of being bound by a `let`.
+    /// A `mut` parameter is used directly, as it is already mutable.
+    fn is_inlined(&self, node: &ValueNode) -> bool {
+        match node {
+            ValueNode::Binding { name, .. } => self.mut_args.contains(name),
+            node => node.inlineable(),
+        }
+    }
+
     /// Makes a list of comma-separated values with string literals inlined
     fn make_values_list(&self, values_refs: &[ValueRef]) -> TokenStream {
         VALUES.with_borrow(|values| {
             let values = values_refs.iter().map(|value| match &values[value.0] {
-                node if node.inlineable() => self.compile_value(*value),
+                node if self.is_inlined(node) => self.compile_value(*value),
                 _ => value.binding().to_token_stream(),
             });
 
@@ -374,8 +770,33 @@ impl CompleteFunction {
     }
 }
 
-fn receiver_tokens(receiver: Receiver) -> Option<TokenStream> {
-    match receiver {
+/// The pattern of a match arm on a variant of the target enum, binding each
+/// field to the binding of its value
+fn compile_pattern(variant: &Variant<ValueRef>) -> TokenStream {
+    match variant {
+        Variant::Unit(variant) => {
+            let name = &variant.name;
+            quote!(Self::#name)
+        }
+        Variant::Tuple(variant) => {
+            let name = &variant.name;
+            let fields = variant.fields.iter().map(|field| field.element.binding());
+            quote!(Self::#name(#(#fields),*))
+        }
+        Variant::Struct(variant) => {
+            let name = &variant.name;
+            let fields = variant.fields.iter().map(|field| {
+                let accessor = Print::ref_cast(&field.accessor);
+                let binding = field.element.binding();
+                quote!(#accessor: #binding)
+            });
+            quote!(Self::#name { #(#fields),* })
+        }
+    }
+}
+
+fn receiver_tokens(receiver: &Receiver) -> Option<TokenStream> {
+    match *receiver {
         Receiver::NoSelf => None,
         Receiver::SelfByValue => Some(quote!(self)),
         Receiver::SelfByReference { is_mut, lifetime } if !is_mut => {
@@ -386,6 +807,10 @@ fn receiver_tokens(receiver: Receiver) -> Option<TokenStream> {
             let lifetime = lifetime.as_ref().map(|lifetime| Print::ref_cast(lifetime));
             Some(quote!(&#lifetime mut self))
         }
+        Receiver::SelfByWrapper(ref ty) => {
+            let ty = Print::ref_cast(ty);
+            Some(quote!(self: #ty))
+        }
     }
 }
 
@@ -398,7 +823,7 @@ impl ValueRef {
 impl ValueNode {
     fn inlineable(&self) -> bool {
         match self {
-            ValueNode::Str(_) => true,
+            ValueNode::Str(_) | ValueNode::PatternBinding { .. } => true,
             ValueNode::Tuple(values) => values.is_empty(),
             _ => false,
         }
@@ -1,7 +1,8 @@
-use crate::{attr, Field, Fields, Value};
+use crate::{
+    attr, Field, Fields, GlobalPush, Ident, MatchArm, Type, Value, ValueNode, ValueRef, VALUES,
+};
 use std::fmt;
 use std::fmt::Debug;
-use std::marker::PhantomData;
 use syn::Attribute;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -17,6 +18,60 @@ impl<T> Data<T> {
             Data::Enum(e) => &e.attrs,
         }
     }
+
+    /// The fields of a struct, or the fields of every variant of an enum in
+    /// the order the variants are declared. Each field is paired with the
+    /// variant it belongs to, which is `None` for the fields of a struct.
+    pub fn fields(&self) -> impl Iterator<Item = (Option<&Variant<T>>, &Field<T>)> {
+        let (struct_fields, variants): (&[Field<T>], &[Variant<T>]) = match self {
+            Data::Struct(s) => (s.field_slice(), &[]),
+            Data::Enum(e) => (&[], &e.variants),
+        };
+        struct_fields
+            .iter()
+            .map(|field| (None, field))
+            .chain(variants.iter().flat_map(|variant| {
+                variant
+                    .field_slice()
+                    .iter()
+                    .map(move |field| (Some(variant), field))
+            }))
+    }
+
+    /// The variants of an enum, or no variants for a struct
+    pub fn variants(&self) -> &[Variant<T>] {
+        match self {
+            Data::Struct(_) => &[],
+            Data::Enum(e) => &e.variants,
+        }
+    }
+
+    /// The number of variants of an enum. A struct counts as a single
+    /// variant, since a value of it always has the same shape.
+    pub fn variant_count(&self) -> usize {
+        match self {
+            Data::Struct(_) => 1,
+            Data::Enum(e) => e.variants.len(),
+        }
+    }
+}
+
+impl Data<Type> {
+    /// The distinct type params of the field types across every variant, in
+    /// the order they first appear. A derive bounds exactly these params, so
+    /// that e.g. `enum Either<L, R, X> { Left(L), Right(Vec<R>) }` gets
+    /// `L: Clone, R: Clone` for `Clone`, while `X` stays unbounded.
+    pub fn collect_field_params(&self) -> Vec<Type> {
+        let mut params = Vec::new();
+        for (_, field) in self.fields() {
+            for param in field.element.type_params() {
+                if !params.contains(&param) {
+                    params.push(param);
+                }
+            }
+        }
+        params
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -34,6 +89,14 @@ impl<T> Struct<T> {
             Struct::Struct(ss) => &ss.attrs,
         }
     }
+
+    fn field_slice(&self) -> &[Field<T>] {
+        match self {
+            Struct::Unit(_) => &[],
+            Struct::Tuple(ts) => &ts.fields,
+            Struct::Struct(ss) => &ss.fields,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
@@ -129,6 +192,11 @@ impl<T> StructStruct<T> {
 pub struct Enum<T> {
     pub(crate) variants: Vec<Variant<T>>,
     pub(crate) attrs: Vec<Attribute>,
+    /// The value the variants are matched on, for the data of a value
+    pub(crate) scrutinee: Option<ValueRef>,
+    /// Whether the enum is `#[non_exhaustive]`, so that a match on it needs a
+    /// wildcard arm
+    pub(crate) non_exhaustive: bool,
 }
 
 impl<T: Debug> Debug for Enum<T> {
@@ -136,21 +204,51 @@ impl<T: Debug> Debug for Enum<T> {
         f.debug_struct("Enum")
             .field("variants", &self.variants)
             .field("attrs", attr::debug(&self.attrs))
+            .field("non_exhaustive", &self.non_exhaustive)
             .finish()
     }
 }
 
 impl Enum<Value> {
+    /// Generates a `match` with one arm per variant. `run` is called with
+    /// each variant, whose fields are bound by the pattern of the arm, and
+    /// returns the value of the arm. The values of all arms should have the
+    /// same type, which is the type of the match. A `#[non_exhaustive]` enum
+    /// also gets a wildcard arm, as its variants may not all be known.
     pub fn match_variant<Run>(&self, run: Run) -> Value
     where
         Run: Fn(Variant<Value>) -> Value,
     {
+        let value = self
+            .scrutinee
+            .expect("Enum::match_variant: Not the data of a value");
         let mut arms = Vec::new();
         for variant in self.variants.clone() {
-            arms.push(run(variant));
+            let start = ValueRef(VALUES.with_borrow(Vec::len));
+            let variant = variant.map(|field| {
+                let node = ValueNode::PatternBinding {
+                    ty: field.element.get_type(),
+                };
+                Value {
+                    index: VALUES.index_push(node),
+                }
+            });
+            let ret = run(variant.clone()).index;
+            let end = ValueRef(VALUES.with_borrow(Vec::len));
+            arms.push(MatchArm {
+                variant: variant.map(|field| field.element.index),
+                values: start..end,
+                ret,
+            });
+        }
+        let node = ValueNode::Match {
+            value,
+            arms,
+            wildcard: self.non_exhaustive,
+        };
+        Value {
+            index: VALUES.index_push(node),
         }
-        // FIXME introduce a match node
-        unimplemented!()
     }
 
     pub fn attrs(&self) -> &[Attribute] {
@@ -173,16 +271,44 @@ impl<T> Variant<T> {
             Variant::Struct(sv) => &sv.attrs,
         }
     }
+
+    /// The name of the variant
+    pub fn get_name(&self) -> String {
+        match self {
+            Variant::Unit(uv) => uv.name.to_string(),
+            Variant::Tuple(tv) => tv.name.to_string(),
+            Variant::Struct(sv) => sv.name.to_string(),
+        }
+    }
+
+    pub fn fields(&self) -> Fields<T>
+    where
+        T: Clone,
+    {
+        Fields {
+            fields: self.field_slice().to_vec().into_iter(),
+        }
+    }
+
+    fn field_slice(&self) -> &[Field<T>] {
+        match self {
+            Variant::Unit(_) => &[],
+            Variant::Tuple(tv) => &tv.fields,
+            Variant::Struct(sv) => &sv.fields,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct UnitVariant {
+    pub(crate) name: Ident,
     pub(crate) attrs: Vec<Attribute>,
 }
 
 impl Debug for UnitVariant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("UnitVariant")
+            .field("name", &self.name)
             .field("attrs", attr::debug(&self.attrs))
             .finish()
     }
@@ -190,13 +316,16 @@ impl Debug for UnitVariant {
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct TupleVariant<T> {
-    pub(crate) phantom: PhantomData<T>,
+    pub(crate) name: Ident,
+    pub(crate) fields: Vec<Field<T>>,
     pub(crate) attrs: Vec<Attribute>,
 }
 
 impl<T: Debug> Debug for TupleVariant<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("TupleVariant")
+            .field("name", &self.name)
+            .field("fields", &self.fields)
             .field("attrs", attr::debug(&self.attrs))
             .finish()
     }
@@ -204,13 +333,16 @@ impl<T: Debug> Debug for TupleVariant<T> {
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct StructVariant<T> {
-    pub(crate) phantom: PhantomData<T>,
+    pub(crate) name: Ident,
+    pub(crate) fields: Vec<Field<T>>,
     pub(crate) attrs: Vec<Attribute>,
 }
 
 impl<T: Debug> Debug for StructVariant<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("StructVariant")
+            .field("name", &self.name)
+            .field("fields", &self.fields)
             .field("attrs", attr::debug(&self.attrs))
             .finish()
     }
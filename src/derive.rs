@@ -1,9 +1,13 @@
+use crate::generics::reject_const_params;
+use crate::ty::check_projections;
 use crate::{
     global_data, Accessor, CompleteFunction, CompleteImpl, Data, DataStructure, Enum, Execution,
-    Field, Generics, Ident, Program, Struct, StructStruct, Tracker, TupleStruct, Type, TypeNode,
-    UnitStruct, WipFunction, WipImpl,
+    Field, Generics, Ident, Program, ReflectError, Struct, StructStruct, StructVariant, Tracker,
+    TraitImplTable, TupleStruct, TupleVariant, Type, TypeNode, UnitStruct, UnitVariant, Variant,
+    WipBatch, WipFunction, WipImpl,
 };
 use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
 use syn::DeriveInput;
 
 pub fn derive<TokenStream>(input: TokenStream, run: fn(Execution)) -> TokenStream
@@ -16,8 +20,57 @@ where
     output.into()
 }
 
+/// Like `derive`, but returns the generated impls formatted for reading
+/// instead of as tokens, to inspect the output of a macro while debugging it.
+/// Needs the "pretty" feature.
+#[cfg(feature = "pretty")]
+pub fn derive_to_pretty_string<TokenStream>(
+    input: TokenStream,
+    run: fn(Execution),
+) -> Result<String, ReflectError>
+where
+    TokenStream: Into<proc_macro2::TokenStream>,
+{
+    let output = run_to_program(input.into(), run).and_then(|program| {
+        let impls = program
+            .impls
+            .iter()
+            .map(CompleteImpl::to_pretty_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(impls.concat())
+    });
+    global_data::clear();
+    output
+}
+
+/// Like `derive`, but passes the generated impls to `inspect` instead of
+/// emitting them, so that what a macro produces can be looked at. The impls
+/// refer to the values of the derive, which are cleared once `inspect`
+/// returns, so they are only lent to it.
+pub fn derive_with_impls<TokenStream, R>(
+    input: TokenStream,
+    run: fn(Execution),
+    inspect: impl FnOnce(&[CompleteImpl]) -> R,
+) -> Result<R, ReflectError>
+where
+    TokenStream: Into<proc_macro2::TokenStream>,
+{
+    let output = run_to_program(input.into(), run).map(|program| inspect(&program.impls));
+    global_data::clear();
+    output
+}
+
 fn derive2(input: TokenStream, run: fn(Execution)) -> TokenStream {
-    let input = syn::parse2(input).unwrap();
+    match run_to_program(input, run) {
+        Ok(program) => program.compile(),
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn run_to_program(input: TokenStream, run: fn(Execution)) -> Result<Program, ReflectError> {
+    let input: DeriveInput = syn::parse2(input).unwrap();
+    reject_const_params(&input.generics)?;
+    check_projections(&input)?;
     let ty = syn_to_type(input);
 
     let tracker = Tracker::new();
@@ -26,11 +79,14 @@ fn derive2(input: TokenStream, run: fn(Execution)) -> TokenStream {
         tracker: &tracker,
     });
 
-    let program = tracker_to_program(tracker);
-    program.compile()
+    tracker_to_program(tracker)
 }
 
 fn syn_to_type(input: DeriveInput) -> Type {
+    let non_exhaustive = input
+        .attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("non_exhaustive"));
     let attrs: Vec<_> = input
         .attrs
         .into_iter()
@@ -41,39 +97,41 @@ fn syn_to_type(input: DeriveInput) -> Type {
     let data = match input.data {
         syn::Data::Struct(data) => match data.fields {
             syn::Fields::Named(fields) => Data::Struct(Struct::Struct(StructStruct {
-                fields: fields
-                    .named
-                    .into_iter()
-                    .map(|field| Field {
-                        attrs: field.attrs,
-                        accessor: Accessor::Name(Ident::from(field.ident.unwrap())),
-                        element: Type::syn_to_type(field.ty, &mut generics.param_map),
-                    })
-                    .collect(),
+                fields: syn_to_fields(fields.named, &mut generics),
                 attrs,
             })),
             syn::Fields::Unnamed(fields) => Data::Struct(Struct::Tuple(TupleStruct {
-                fields: fields
-                    .unnamed
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, field)| Field {
-                        attrs: field.attrs,
-                        accessor: Accessor::Index(i),
-                        element: Type::syn_to_type(field.ty, &mut generics.param_map),
-                    })
-                    .collect(),
+                fields: syn_to_fields(fields.unnamed, &mut generics),
                 attrs,
             })),
             syn::Fields::Unit => Data::Struct(Struct::Unit(UnitStruct { attrs })),
         },
-        syn::Data::Enum(data) => {
-            // FIXME convert enum variants
-            Data::Enum(Enum {
-                variants: Vec::new(),
-                attrs,
-            })
-        }
+        syn::Data::Enum(data) => Data::Enum(Enum {
+            variants: data
+                .variants
+                .into_iter()
+                .map(|variant| {
+                    let name = Ident::from(variant.ident);
+                    let attrs = variant.attrs;
+                    match variant.fields {
+                        syn::Fields::Named(fields) => Variant::Struct(StructVariant {
+                            name,
+                            fields: syn_to_fields(fields.named, &mut generics),
+                            attrs,
+                        }),
+                        syn::Fields::Unnamed(fields) => Variant::Tuple(TupleVariant {
+                            name,
+                            fields: syn_to_fields(fields.unnamed, &mut generics),
+                            attrs,
+                        }),
+                        syn::Fields::Unit => Variant::Unit(UnitVariant { name, attrs }),
+                    }
+                })
+                .collect(),
+            attrs,
+            scrutinee: None,
+            non_exhaustive,
+        }),
         syn::Data::Union(_) => unimplemented!("union"),
     };
 
@@ -81,26 +139,69 @@ fn syn_to_type(input: DeriveInput) -> Type {
         name: Ident::from(input.ident),
         generics,
         data,
+        non_exhaustive,
     })))
 }
 
-fn tracker_to_program(tracker: Tracker) -> Program {
-    Program {
+/// Named fields are accessed by name, and unnamed fields by their position
+fn syn_to_fields<I>(fields: I, generics: &mut Generics) -> Vec<Field<Type>>
+where
+    I: IntoIterator<Item = syn::Field>,
+{
+    fields
+        .into_iter()
+        .enumerate()
+        .map(|(i, field)| Field {
+            span: field.ty.span(),
+            attrs: field.attrs,
+            accessor: match field.ident {
+                Some(ident) => Accessor::Name(Ident::from(ident)),
+                None => Accessor::Index(i),
+            },
+            element: Type::syn_to_type(field.ty, &mut generics.param_map),
+        })
+        .collect()
+}
+
+fn tracker_to_program(tracker: Tracker) -> Result<Program, ReflectError> {
+    let known_impls = TraitImplTable::default();
+    Ok(Program {
         crates: tracker.crates.into_inner(),
         impls: tracker
             .impls
             .into_inner()
             .into_iter()
-            .map(into_complete_impl)
+            .map(|batch| into_complete_impls(batch, &known_impls))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
             .collect(),
+    })
+}
+
+fn into_complete_impls(
+    batch: WipBatch,
+    known_impls: &TraitImplTable,
+) -> Result<Vec<CompleteImpl>, ReflectError> {
+    let WipBatch {
+        impls: batch,
+        per_impl_memo,
+    } = batch;
+    batch.iter().try_for_each(WipImpl::check_sized)?;
+    batch.iter().try_for_each(WipImpl::check_literals)?;
+    batch.iter().try_for_each(WipImpl::check_assoc_types)?;
+    if batch
+        .iter()
+        .any(|imp| imp.has_generics() || imp.has_inferred_type_args())
+    {
+        return WipImpl::compute_batch_trait_bounds(batch, per_impl_memo, known_impls);
     }
+    Ok(batch.into_iter().map(into_complete_impl).collect())
 }
 
 fn into_complete_impl(imp: WipImpl) -> CompleteImpl {
-    if imp.has_generics() {
-        return imp.compute_trait_bounds();
-    }
     CompleteImpl {
+        unsafety: imp.is_unsafe(),
         trait_ty: imp.trait_ty,
         ty: imp.ty,
         functions: imp
@@ -119,9 +220,14 @@ fn into_complete_impl(imp: WipImpl) -> CompleteImpl {
                     invokes: invokes.unwrap(),
                     macros: macros.unwrap(),
                     ret: function.ret,
+                    cfgs: function.cfgs.into_inner(),
+                    attrs: function.attrs.into_inner(),
+                    mut_args: function.mut_args.into_inner(),
                 }
             })
             .collect(),
+        assoc_types: imp.assoc_types.into_inner(),
+        cfgs: imp.cfgs.into_inner(),
         result: None,
     }
 }
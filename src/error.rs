@@ -0,0 +1,172 @@
+use crate::{Path, Print, Type, TypeNode};
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use ref_cast::RefCast;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+/// An error found while turning the reflection program into Rust code
+#[derive(Debug, Clone)]
+pub enum ReflectError {
+    /// Two types that must be equal can not be unified. The span is that of
+    /// the value whose type is wrong, if the value comes from the macro input.
+    TypeMismatch {
+        expected: Type,
+        found: Type,
+        span: Option<Span>,
+    },
+    /// A shared reference is used where a mutable reference is required
+    MutabilityMismatch {
+        expected: Type,
+        found: Type,
+        span: Option<Span>,
+    },
+    /// An unsized type, like `str`, a slice or a trait object, is passed or
+    /// returned by value instead of behind a pointer such as `&` or `Box`
+    UnsizedValue { ty: Type },
+    /// The `?` operator is applied to a `Result` in a function that does not
+    /// return a `Result`, or to an `Option` in one that does not return an
+    /// `Option`
+    TryMismatch { operand: Type, output: Type },
+    /// A struct literal is made of a `#[non_exhaustive]` struct, which other
+    /// crates can not build
+    NonExhaustiveLiteral { ty: Type },
+    /// An associated type is defined with `MakeImpl::make_assoc_type` that
+    /// the implemented trait does not declare
+    UnknownAssocType { name: String, trait_path: Path },
+    /// The trait inference gave up on a type that is too deeply nested, or
+    /// relates too many types, for the `InferenceLimits`
+    InferenceLimitExceeded { ty: Type },
+    /// The generated code does not parse, which is a bug in the code
+    /// generation
+    InvalidOutput { error: syn::Error },
+    /// A string given as generics, like to `Generics::from_str`, does not
+    /// parse
+    InvalidGenerics { error: syn::Error },
+    /// The input has a projection that is not of a single associated type,
+    /// like `<T as Trait>::A::B`
+    UnsupportedProjection { error: syn::Error },
+    /// The input has a const generic param, like `N` in `[T; N]`, which the
+    /// generics of the generated code can not express
+    UnsupportedConstParam { ident: proc_macro2::Ident },
+    /// `Type::common_supertype` is given no types to find the supertype of
+    NoTypes,
+}
+
+impl ReflectError {
+    /// The span in the macro input that the error is about, or the call site
+    /// if the error does not originate from the macro input
+    pub fn span(&self) -> Span {
+        match self {
+            ReflectError::TypeMismatch { span, .. }
+            | ReflectError::MutabilityMismatch { span, .. } => span.unwrap_or_else(Span::call_site),
+            ReflectError::UnsizedValue { .. }
+            | ReflectError::TryMismatch { .. }
+            | ReflectError::NonExhaustiveLiteral { .. }
+            | ReflectError::UnknownAssocType { .. }
+            | ReflectError::InferenceLimitExceeded { .. }
+            | ReflectError::NoTypes => Span::call_site(),
+            ReflectError::InvalidOutput { error }
+            | ReflectError::InvalidGenerics { error }
+            | ReflectError::UnsupportedProjection { error } => error.span(),
+            ReflectError::UnsupportedConstParam { ident } => ident.span(),
+        }
+    }
+
+    /// Points a mismatch without a span yet at `span`, the span of the value
+    /// whose type did not match
+    pub(crate) fn at_value(mut self, value_span: Option<Span>) -> Self {
+        if let ReflectError::TypeMismatch { span, .. }
+        | ReflectError::MutabilityMismatch { span, .. } = &mut self
+        {
+            if span.is_none() {
+                *span = value_span;
+            }
+        }
+        self
+    }
+
+    /// Render the error as a `compile_error!` invocation pointing at
+    /// `ReflectError::span`
+    pub fn to_compile_error(&self) -> TokenStream {
+        syn::Error::new(self.span(), self).to_compile_error()
+    }
+}
+
+impl Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReflectError::TypeMismatch {
+                expected, found, ..
+            } => write!(
+                f,
+                "mismatched types: expected `{}`, found `{}`",
+                error_type(expected),
+                error_type(found),
+            ),
+            ReflectError::MutabilityMismatch {
+                expected, found, ..
+            } => write!(
+                f,
+                "mismatched types: expected mutable reference `{}`, found `{}`",
+                error_type(expected),
+                error_type(found),
+            ),
+            ReflectError::UnsizedValue { ty } => write!(
+                f,
+                "the {} `{}` must be behind a pointer such as `&` or `Box`",
+                match ty.0 {
+                    TypeNode::TraitObject(_) => "trait object",
+                    _ => "unsized type",
+                },
+                error_type(ty),
+            ),
+            ReflectError::TryMismatch { operand, output } => write!(
+                f,
+                "the `?` operator on `{}` can not be used in a function that returns `{}`",
+                error_type(operand),
+                error_type(output),
+            ),
+            ReflectError::NonExhaustiveLiteral { ty } => write!(
+                f,
+                "cannot create a value of the non-exhaustive struct `{}` with a struct literal",
+                error_type(ty),
+            ),
+            ReflectError::UnknownAssocType { name, trait_path } => write!(
+                f,
+                "type `{}` is not a member of trait `{}`",
+                name,
+                Print::ref_cast(trait_path).to_token_stream(),
+            ),
+            ReflectError::InferenceLimitExceeded { ty } => write!(
+                f,
+                "reached the type inference limit while inferring `{}`",
+                error_type(ty),
+            ),
+            ReflectError::InvalidOutput { error } => {
+                write!(f, "the generated code does not parse: {}", error)
+            }
+            ReflectError::InvalidGenerics { error } => {
+                write!(f, "the generics do not parse: {}", error)
+            }
+            ReflectError::UnsupportedProjection { error } => write!(f, "{}", error),
+            ReflectError::UnsupportedConstParam { ident } => {
+                write!(
+                    f,
+                    "the const generic parameter `{}` is not supported",
+                    ident
+                )
+            }
+            ReflectError::NoTypes => write!(f, "there are no types to find a common supertype of"),
+        }
+    }
+}
+
+impl Error for ReflectError {}
+
+/// The type params and lifetimes of the generated code have names that mean
+/// nothing to the user, so like rustc does for the types it is inferring, they
+/// are written as `_` and `'_` in error messages
+fn error_type(ty: &Type) -> TokenStream {
+    Print::ref_cast(&ty.without_generated_names()).to_token_stream()
+}
@@ -1,5 +1,10 @@
-use crate::{Ident, MakeImpl, Module, Parent, Path, RuntimeTrait, RuntimeType, Type, WipImpl};
-use std::cell::RefCell;
+use crate::{
+    CodegenOptions, GenericParam, GlobalCounter, Ident, InferenceLimits, MakeImpl, Module, Parent,
+    Path, ProjectionRule, RuntimeTrait, RuntimeType, SynParamMap, Type, TypeNode, TypeParamBound,
+    WipImpl, CODEGEN_OPTIONS, COPY_TYPES, DROP_FREE_TYPES, INFERENCE_LIMITS, PROJECTIONS,
+    TYPE_PARAMS,
+};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 #[derive(Clone, Copy)]
@@ -11,7 +16,25 @@ pub struct Execution<'a> {
 #[derive(Debug, Clone)]
 pub(crate) struct Tracker {
     pub(crate) crates: RefCell<Vec<Ident>>,
-    pub(crate) impls: RefCell<Vec<WipImpl>>,
+    /// Each batch of impls shares its trait inference. A single impl is a
+    /// batch of its own.
+    pub(crate) impls: RefCell<Vec<WipBatch>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct WipBatch {
+    pub(crate) impls: Vec<WipImpl>,
+    /// Set by `ImplBatch::set_per_impl_memo`
+    pub(crate) per_impl_memo: bool,
+}
+
+/// Collects several trait impls for the same type, whose trait bounds are
+/// inferred together. Created by `Execution::make_impl_batch`.
+#[derive(Clone, Copy)]
+pub struct ImplBatch<'a> {
+    ty: &'a Type,
+    impls: &'a RefCell<Vec<WipImpl>>,
+    per_impl_memo: &'a Cell<bool>,
 }
 
 impl<'a> Execution<'a> {
@@ -32,9 +55,87 @@ impl<'a> Execution<'a> {
             .make_trait_impl(trait_type.SELF(), self_type.SELF(), run);
     }
 
+    /// Make a blanket impl of a trait for every type with the given bounds,
+    /// `impl<T: bounds> Trait for T`. The bounds are given as for
+    /// `Type::get_trait_object`, and may refer to the self type as `T`. The
+    /// trait inference adds the bounds that the functions of the impl need.
+    pub fn make_blanket_impl<TraitType>(
+        self,
+        trait_type: TraitType,
+        bounds: &[&str],
+        run: fn(MakeImpl),
+    ) where
+        TraitType: RuntimeTrait,
+    {
+        let param = TYPE_PARAMS.count();
+        let mut param_map = SynParamMap::new();
+        param_map
+            .map
+            .insert(String::from("T"), GenericParam::Type(param));
+        let bounds = bounds
+            .iter()
+            .flat_map(|bounds| TypeParamBound::get_type_param_bounds(bounds, &mut param_map))
+            .collect();
+        let mut wip = make_wip_impl(trait_type.SELF(), Type(TypeNode::TypeParam(param)), run);
+        wip.self_bounds = bounds;
+        self.tracker.impls.borrow_mut().push(WipBatch::single(wip));
+    }
+
+    /// Make several trait impls that share one trait inference, so that for
+    /// example `PartialEq` and `Eq` impls get the same bounds.
+    pub fn make_impl_batch(self, run: fn(ImplBatch)) {
+        let impls = RefCell::new(Vec::new());
+        let per_impl_memo = Cell::new(false);
+        run(ImplBatch {
+            ty: self.ty,
+            impls: &impls,
+            per_impl_memo: &per_impl_memo,
+        });
+        self.tracker.impls.borrow_mut().push(WipBatch {
+            impls: impls.into_inner(),
+            per_impl_memo: per_impl_memo.get(),
+        });
+    }
+
     pub fn target_type(self) -> Type {
         self.ty.clone()
     }
+
+    /// Declare what an associated type projection resolves to, so that the
+    /// trait inference can see through it, e.g.
+    /// `ex.register_projection(&["T"], "<Vec<T> as IntoIterator>::Item", "T")`.
+    /// The generic params match any type in the projection.
+    pub fn register_projection(self, generic_params: &[&str], projection: &str, resolved: &str) {
+        let rule = ProjectionRule::new(generic_params, projection, resolved);
+        PROJECTIONS.with(|rules| rules.borrow_mut().push(rule));
+    }
+
+    /// Declare that a type is `Copy`, for `Type::is_known_copy`. A generic
+    /// type is only known for exactly the registered arguments.
+    pub fn register_copy_type<T: RuntimeType>(self, ty: T) {
+        let ty = ty.SELF();
+        COPY_TYPES.with(|types| types.borrow_mut().insert(ty.0));
+    }
+
+    /// Declare that a type does not implement `Drop`, for
+    /// `Type::may_impl_drop`. A generic type is only known for exactly the
+    /// registered arguments.
+    pub fn register_drop_free_type<T: RuntimeType>(self, ty: T) {
+        let ty = ty.SELF();
+        DROP_FREE_TYPES.with(|types| types.borrow_mut().insert(ty.0));
+    }
+
+    /// Limit how much work the trait inference may do for this derive. A
+    /// type exceeding the limits gives `ReflectError::InferenceLimitExceeded`.
+    pub fn set_inference_limits(self, limits: InferenceLimits) {
+        INFERENCE_LIMITS.with(|global| global.set(limits));
+    }
+
+    /// Choose how the generated code names the types of the standard
+    /// library, e.g. through `::core` for a `no_std` crate
+    pub fn set_codegen_options(self, options: CodegenOptions) {
+        CODEGEN_OPTIONS.with(|global| global.set(options));
+    }
 }
 
 impl Tracker {
@@ -53,12 +154,57 @@ impl Tracker {
     }
 
     fn make_trait_impl(&self, trait_ty: Rc<Parent>, ty: Type, run: fn(MakeImpl)) {
-        let wip = WipImpl {
-            trait_ty: Some(trait_ty),
-            ty,
-            functions: RefCell::new(Vec::new()),
-        };
-        run(MakeImpl { wip: &wip });
+        let wip = make_wip_impl(trait_ty, ty, run);
+        self.impls.borrow_mut().push(WipBatch::single(wip));
+    }
+}
+
+impl<'a> ImplBatch<'a> {
+    pub fn make_trait_impl<TraitType, SelfType>(
+        self,
+        trait_type: TraitType,
+        self_type: SelfType,
+        run: fn(MakeImpl),
+    ) where
+        TraitType: RuntimeTrait,
+        SelfType: RuntimeType,
+    {
+        let wip = make_wip_impl(trait_type.SELF(), self_type.SELF(), run);
         self.impls.borrow_mut().push(wip);
     }
+
+    pub fn target_type(self) -> Type {
+        self.ty.clone()
+    }
+
+    /// Start every impl of the batch with an empty memo of the types made
+    /// most concrete, instead of sharing one memo across the batch. The memo
+    /// then never holds more than one impl's types, but a type used by
+    /// several impls is made concrete once per impl. What is inferred stays
+    /// the same.
+    pub fn set_per_impl_memo(self, per_impl_memo: bool) {
+        self.per_impl_memo.set(per_impl_memo);
+    }
+}
+
+impl WipBatch {
+    fn single(wip: WipImpl) -> Self {
+        WipBatch {
+            impls: vec![wip],
+            per_impl_memo: false,
+        }
+    }
+}
+
+fn make_wip_impl(trait_ty: Rc<Parent>, ty: Type, run: fn(MakeImpl)) -> WipImpl {
+    let wip = WipImpl {
+        trait_ty: Some(trait_ty),
+        ty,
+        functions: RefCell::new(Vec::new()),
+        assoc_types: RefCell::new(Vec::new()),
+        self_bounds: Vec::new(),
+        cfgs: RefCell::new(Vec::new()),
+    };
+    run(MakeImpl { wip: &wip });
+    wip
 }
@@ -1,5 +1,7 @@
 use crate::{attr, GlobalPush, Ident, Type, Value, ValueNode, VALUES};
+use proc_macro2::Span;
 use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
 use std::vec;
 use syn::Attribute;
 
@@ -22,11 +24,33 @@ pub(crate) enum Accessor {
     Index(usize),
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone)]
 pub struct Field<T> {
     pub(crate) accessor: Accessor,
     pub(crate) element: T,
     pub(crate) attrs: Vec<Attribute>,
+    /// The span of the type of the field in the macro input, for pointing
+    /// errors about the value of the field at it
+    pub(crate) span: Span,
+}
+
+// The span is where the field was written, not part of what the field is
+impl<T: PartialEq> PartialEq for Field<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.accessor == other.accessor
+            && self.element == other.element
+            && self.attrs == other.attrs
+    }
+}
+
+impl<T: Eq> Eq for Field<T> {}
+
+impl<T: Hash> Hash for Field<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.accessor.hash(state);
+        self.element.hash(state);
+        self.attrs.hash(state);
+    }
 }
 
 impl<T: Debug> Debug for Field<T> {
@@ -49,6 +73,25 @@ impl Display for Accessor {
     }
 }
 
+impl<T> Field<T> {
+    /// Whether the field is a named field of a struct, as opposed to a
+    /// positional field of a tuple struct
+    pub fn is_named(&self) -> bool {
+        match self.accessor {
+            Accessor::Name(_) => true,
+            Accessor::Index(_) => false,
+        }
+    }
+
+    /// The position of a tuple struct field, or `None` for a named field
+    pub fn get_index(&self) -> Option<usize> {
+        match self.accessor {
+            Accessor::Name(_) => None,
+            Accessor::Index(index) => Some(index),
+        }
+    }
+}
+
 impl Field<Value> {
     pub fn get_name(&self) -> Value {
         let node = ValueNode::Str(self.accessor.to_string());
@@ -66,6 +109,11 @@ impl Field<Type> {
     pub fn get_name(&self) -> String {
         self.accessor.to_string()
     }
+
+    /// The type of the field as it is declared
+    pub fn get_type(&self) -> Type {
+        self.element.clone()
+    }
 }
 
 impl Field<Value> {
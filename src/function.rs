@@ -1,4 +1,10 @@
-use crate::{Generics, GlobalPush, Invoke, Parent, Signature, Value, ValueNode, INVOKES, VALUES};
+use crate::trait_inference::params_to_args;
+use crate::{
+    GenericArgument, GenericConstraint, GenericParam, Generics, GlobalCounter, GlobalPush, Invoke,
+    Parent, ParentKind, PathArguments, PredicateType, Receiver, Signature, Type, TypeNode,
+    TypeParam, TypeParamBound, Value, ValueNode, ValueRef, INVOKES, TYPE_PARAMS, VALUES,
+};
+use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
 
 #[derive(Debug, Clone)]
@@ -6,18 +12,272 @@ pub struct Function {
     pub(crate) parent: Option<Rc<Parent>>,
     pub(crate) name: String,
     pub(crate) sig: Signature,
+    pub(crate) has_default: bool,
+}
+
+/// A function with some of its arguments fixed to values, created by
+/// `Function::partial_apply`. It is generated as a closure that takes the
+/// remaining arguments and calls the function.
+#[derive(Debug, Clone)]
+pub struct PartialFunction {
+    wrapper: Value,
+    arity: usize,
 }
 
 impl Function {
     pub fn invoke(self: Rc<Function>, args: &[Value]) -> Value {
-        let function = self.clone_with_fresh_generics();
-        let invoke = INVOKES.index_push(Invoke {
-            function,
-            args: args.iter().map(|value| value.index).collect(),
-        });
-        let node = ValueNode::Invoke(invoke);
-        Value {
-            index: VALUES.index_push(node),
+        let turbofish_params = self.turbofish_params();
+        let function = self.call_site(args);
+        let type_args = function.undetermined_type_args(turbofish_params);
+        push_invoke(function, args, None, type_args, true)
+    }
+
+    /// Invokes the function with explicit type arguments for its generic type
+    /// params, emitted as a turbofish: `collect::<Vec<_>>(args)`. This is
+    /// needed when nothing else at the call site decides the types. An
+    /// argument may contain `_` for the parts that Rust can infer.
+    ///
+    /// Panics if the number of type arguments does not match the number of
+    /// type params, or if the function has `impl Trait` arguments, which can
+    /// not be combined with a turbofish.
+    pub fn invoke_turbofish(self: Rc<Function>, type_args: &[Type], args: &[Value]) -> Value {
+        let type_params = self
+            .sig
+            .generics
+            .params
+            .iter()
+            .filter(|param| param.type_param().is_some())
+            .count();
+        assert_eq!(
+            type_args.len(),
+            type_params,
+            "Function::invoke_turbofish: wrong number of type arguments"
+        );
+        let is_impl_trait = &mut |node: &TypeNode| matches!(node, TypeNode::ImplTrait(..));
+        assert!(
+            !self
+                .sig
+                .inputs
+                .iter()
+                .any(|ty| ty.0.any_node(is_impl_trait)),
+            "Function::invoke_turbofish: impl Trait argument"
+        );
+        push_invoke(self.call_site(args), args, None, type_args.to_vec(), false)
+    }
+
+    /// The number of type params a turbofish of the function lists, which is
+    /// zero if it can not have one because of `impl Trait` arguments
+    fn turbofish_params(&self) -> usize {
+        let is_impl_trait = &mut |node: &TypeNode| matches!(node, TypeNode::ImplTrait(..));
+        if self
+            .sig
+            .inputs
+            .iter()
+            .any(|ty| ty.0.any_node(is_impl_trait))
+        {
+            return 0;
+        }
+        self.sig
+            .generics
+            .params
+            .iter()
+            .filter(|param| param.type_param().is_some())
+            .count()
+    }
+
+    /// The turbofish of a call to the function, with the type params that the
+    /// arguments do not determine and `_` for the others, so that the params
+    /// are given their inferred types. Empty if every type param is
+    /// determined by the arguments.
+    fn undetermined_type_args(&self, turbofish_params: usize) -> Vec<Type> {
+        let receiver = match &self.sig.receiver {
+            Receiver::SelfByWrapper(ty) => Some(ty),
+            _ => None,
+        };
+        let mut determined = BTreeSet::new();
+        let collect_params = &mut |node: &TypeNode| {
+            if let TypeNode::TypeParam(param) = node {
+                determined.insert(*param);
+            }
+            false
+        };
+        for ty in self.sig.inputs.iter().chain(receiver) {
+            ty.0.any_node(collect_params);
+        }
+        // A param in the bounds of a determined param is determined too,
+        // like `U` in `F: Fn(T) -> U`
+        loop {
+            let count = determined.len();
+            for constraint in &self.sig.generics.constraints {
+                if let GenericConstraint::Type(pred_ty) = constraint {
+                    let is_bounding_determined = pred_ty.bounded_ty.0.any_node(&mut |node| {
+                        matches!(node, TypeNode::TypeParam(param) if determined.contains(param))
+                    });
+                    if is_bounding_determined {
+                        pred_ty.any_node(&mut |node| {
+                            if let TypeNode::TypeParam(param) = node {
+                                determined.insert(*param);
+                            }
+                            false
+                        });
+                    }
+                }
+            }
+            if determined.len() == count {
+                break;
+            }
+        }
+
+        let type_args: Vec<_> = self
+            .sig
+            .generics
+            .params
+            .iter()
+            .filter_map(|param| param.type_param())
+            .take(turbofish_params)
+            .map(|param| {
+                if determined.contains(&param) {
+                    Type(TypeNode::Infer)
+                } else {
+                    Type(TypeNode::TypeParam(param))
+                }
+            })
+            .collect();
+        if type_args.iter().all(|arg| arg.0 == TypeNode::Infer) {
+            Vec::new()
+        } else {
+            type_args
+        }
+    }
+
+    /// The function as seen from a single call site
+    fn call_site(self: Rc<Function>, args: &[Value]) -> Rc<Function> {
+        // Every call site gets its own generic params, so that invoking the
+        // same generic function twice does not relate the two calls
+        let mut function = self.clone_with_fresh_generics();
+        let is_impl_trait = &mut |node: &TypeNode| matches!(node, TypeNode::ImplTrait(..));
+        if function
+            .sig
+            .inputs
+            .iter()
+            .any(|ty| ty.0.any_node(is_impl_trait))
+        {
+            Rc::make_mut(&mut function).desugar_impl_trait_args();
+        }
+        if function.is_static_trait_function() {
+            Rc::make_mut(&mut function).desugar_static_self();
+        }
+        // A trait method called on a receiver has `Self` as the type of that
+        // receiver, also where it is nested like in `Option<Self>`
+        if let Some(self_ty) = function.receiver_self_type(args) {
+            Rc::make_mut(&mut function).sig.replace_self(&self_ty);
+        }
+        function
+    }
+
+    fn receiver_self_type(&self, args: &[Value]) -> Option<TypeNode> {
+        match &self.parent {
+            Some(parent) if parent.parent_kind == ParentKind::Trait => {}
+            _ => return None,
+        }
+        let receiver = args.first()?;
+        self.sig.receiver.find_self(&receiver.get_type().0)
+    }
+
+    /// Invokes a trait function for an explicit `Self` type, like
+    /// `<Type as Trait>::method(args)`. The call is inferred with the bounds
+    /// of that trait for `self_ty`, even when no argument has the `Self`
+    /// type.
+    ///
+    /// Panics if the function is not declared in a trait.
+    pub fn invoke_qualified(self: Rc<Function>, self_ty: &Type, args: &[Value]) -> Value {
+        match &self.parent {
+            Some(parent) if parent.parent_kind == ParentKind::Trait => {}
+            _ => panic!("Function::invoke_qualified: Not a trait function"),
+        }
+        let mut function = self.clone_with_fresh_generics();
+        {
+            let function = Rc::make_mut(&mut function);
+            function.desugar_impl_trait_args();
+            function.sig.replace_self(&self_ty.0);
+        }
+        push_invoke(function, args, Some(self_ty.clone()), Vec::new(), false)
+    }
+
+    /// Fixes the arguments at the given positions, so that only the remaining
+    /// arguments need to be passed when invoking the returned function. This
+    /// is useful to call e.g. `with_capacity` style functions as if some of
+    /// their arguments had default values. A receiver counts as the first
+    /// argument.
+    ///
+    /// The wrapper is generated as a closure, e.g.
+    /// `let __v4 = |__v2| { .. Builder::with_capacity(__v2, __v1) .. };`
+    /// for the second argument fixed to `__v1`. The fixed values are
+    /// captured by the closure, so they must belong to the same function body
+    /// as the one the partially applied function is invoked in, and a wrapper
+    /// that passes a fixed value by value can only be invoked once.
+    pub fn partial_apply(self: Rc<Function>, fixed: &[(usize, Value)]) -> PartialFunction {
+        let arity = self.arity();
+        let mut fixed = fixed.to_vec();
+        fixed.sort_by_key(|&(position, _)| position);
+        for pair in fixed.windows(2) {
+            assert_ne!(
+                pair[0].0, pair[1].0,
+                "Function::partial_apply: argument fixed twice"
+            );
+        }
+        if let Some(&(position, _)) = fixed.last() {
+            assert!(
+                position < arity,
+                "Function::partial_apply: no argument at position {}",
+                position
+            );
+        }
+
+        // The parameters of the closure take the arguments that are not
+        // fixed, with types that the trait inference relates to the ones of
+        // the arguments at the calls of the closure
+        let start = ValueRef(VALUES.with_borrow(Vec::len));
+        let mut fixed = fixed.into_iter().peekable();
+        let mut params = Vec::new();
+        let args: Vec<_> = (0..arity)
+            .map(|position| match fixed.peek() {
+                Some(&(fixed_position, value)) if fixed_position == position => {
+                    fixed.next();
+                    value
+                }
+                _ => {
+                    let node = ValueNode::PatternBinding {
+                        ty: Type(TypeNode::TypeParam(TYPE_PARAMS.count())),
+                    };
+                    let param = VALUES.index_push(node);
+                    params.push(param);
+                    Value { index: param }
+                }
+            })
+            .collect();
+        let ret = self.invoke(&args).index;
+        let end = ValueRef(VALUES.with_borrow(Vec::len));
+
+        let arity = params.len();
+        let node = ValueNode::Closure {
+            params,
+            values: start..end,
+            ret,
+        };
+        PartialFunction {
+            wrapper: Value {
+                index: VALUES.index_push(node),
+            },
+            arity,
+        }
+    }
+
+    /// The number of arguments including the receiver
+    fn arity(&self) -> usize {
+        match self.sig.receiver {
+            Receiver::NoSelf => self.sig.inputs.len(),
+            _ => self.sig.inputs.len() + 1,
         }
     }
 
@@ -27,9 +287,21 @@ impl Function {
             parent: None,
             name: name.to_owned(),
             sig,
+            has_default: false,
         }
     }
 
+    /// Mark the function as a trait method with a default body
+    pub fn set_has_default(&mut self) {
+        self.has_default = true;
+    }
+
+    /// Whether the function is a trait method with a default body, which an
+    /// impl of the trait does not need to define
+    pub fn has_default(&self) -> bool {
+        self.has_default
+    }
+
     /// When calling `set_parent` it is important to use a reference to the
     /// same Parent struct for all functions declared inside of the same impl
     /// or trait definition. Otherwise the trait inference may not work
@@ -77,7 +349,16 @@ impl Function {
                         .map(|ty| ty.clone_with_fresh_generics(&param_map))
                         .collect(),
                     output: old_sig.output.clone_with_fresh_generics(&param_map),
+                    constness: old_sig.constness,
+                    asyncness: old_sig.asyncness,
+                    unsafety: old_sig.unsafety,
+                    elided_lifetimes: old_sig
+                        .elided_lifetimes
+                        .iter()
+                        .map(|lifetime| lifetime.clone_with_fresh_generics(&param_map))
+                        .collect(),
                 },
+                has_default: self.has_default,
             })
         } else if !self.sig.generics.params.is_empty() {
             let (sig_generics, param_map) = self.sig.generics.clone_with_fresh_generics();
@@ -95,10 +376,200 @@ impl Function {
                         .map(|ty| ty.clone_with_fresh_generics(&param_map))
                         .collect(),
                     output: old_sig.output.clone_with_fresh_generics(&param_map),
+                    constness: old_sig.constness,
+                    asyncness: old_sig.asyncness,
+                    unsafety: old_sig.unsafety,
+                    elided_lifetimes: old_sig
+                        .elided_lifetimes
+                        .iter()
+                        .map(|lifetime| lifetime.clone_with_fresh_generics(&param_map))
+                        .collect(),
                 },
+                has_default: self.has_default,
             })
         } else {
             self.clone()
         }
     }
 }
+
+impl Function {
+    /// An `impl Trait` argument is an anonymous generic param, so each one is
+    /// replaced by a fresh type param bounded by the trait. The call site
+    /// `print(x)` of `fn print(impl Display)` is then inferred like a call to
+    /// `fn print<P: Display>(P)`.
+    fn desugar_impl_trait_args(&mut self) {
+        let generics = &mut self.sig.generics;
+        let mut fresh = BTreeSet::new();
+        for input in &mut self.sig.inputs {
+            input.0.replace_impl_trait(&mut |bounds| {
+                let param = TYPE_PARAMS.count();
+                fresh.insert(param);
+                generics.params.push(GenericParam::Type(param));
+                generics
+                    .constraints
+                    .push(GenericConstraint::Type(PredicateType {
+                        lifetimes: Vec::new(),
+                        bounded_ty: Type(TypeNode::TypeParam(param)),
+                        bounds,
+                    }));
+                TypeNode::TypeParam(param)
+            });
+        }
+        project_bound_params(generics, &fresh);
+    }
+
+    /// A trait function without a receiver, like `Default::default`, that
+    /// mentions `Self`
+    fn is_static_trait_function(&self) -> bool {
+        let is_self = &mut |node: &TypeNode| node.is_self();
+        match &self.parent {
+            Some(parent) if parent.parent_kind == ParentKind::Trait => {
+                matches!(self.sig.receiver, Receiver::NoSelf)
+                    && (self.sig.output.0.any_node(is_self)
+                        || self.sig.inputs.iter().any(|ty| ty.0.any_node(is_self)))
+            }
+            _ => false,
+        }
+    }
+
+    /// Without a receiver the `Self` of a trait function is only known from
+    /// how the result is used, so it is replaced by a fresh type param
+    /// bounded by the trait. `Default::default()` is then inferred like a
+    /// call to `fn default<S: Default>() -> S`.
+    fn desugar_static_self(&mut self) {
+        let parent = self.parent.clone().unwrap();
+        let param = TYPE_PARAMS.count();
+        let self_ty = TypeNode::TypeParam(param);
+        let mut path = parent.path.clone();
+        path.path.last_mut().unwrap().args = params_to_args(&parent.generics.params);
+
+        let sig = &mut self.sig;
+        sig.generics.params.push(GenericParam::Type(param));
+        sig.generics
+            .constraints
+            .push(GenericConstraint::Type(PredicateType {
+                lifetimes: Vec::new(),
+                bounded_ty: Type(self_ty.clone()),
+                bounds: vec![TypeParamBound::from_path(path)],
+            }));
+        sig.replace_self(&self_ty);
+    }
+}
+
+fn push_invoke(
+    function: Rc<Function>,
+    args: &[Value],
+    qualified_self: Option<Type>,
+    type_args: Vec<Type>,
+    inferred_type_args: bool,
+) -> Value {
+    let invoke = INVOKES.index_push(Invoke {
+        function,
+        args: args.iter().map(|value| value.index).collect(),
+        qualified_self,
+        type_args,
+        inferred_type_args,
+    });
+    let node = ValueNode::Invoke(invoke);
+    Value {
+        index: VALUES.index_push(node),
+    }
+}
+
+impl PartialFunction {
+    /// Calls the wrapper, which invokes the function with the fixed arguments
+    /// in their positions and `args` filling the remaining positions in order
+    pub fn invoke(&self, args: &[Value]) -> Value {
+        assert_eq!(
+            args.len(),
+            self.arity,
+            "PartialFunction::invoke: wrong number of arguments"
+        );
+        let node = ValueNode::Call {
+            closure: self.wrapper.index,
+            args: args.iter().map(|value| value.index).collect(),
+        };
+        Value {
+            index: VALUES.index_push(node),
+        }
+    }
+}
+
+/// The fresh param of an `impl Trait` bound to an associated type, like the
+/// `Item` of `impl Iterator<Item = impl Display>`, is the associated type
+/// itself and not a param that the caller picks. The binding is removed and
+/// the param is replaced by the projection `<P as Iterator>::Item`.
+fn project_bound_params(generics: &mut Generics, fresh: &BTreeSet<TypeParam>) {
+    let mut projections = BTreeMap::new();
+    for constraint in &mut generics.constraints {
+        let predicate = match constraint {
+            GenericConstraint::Type(predicate) => predicate,
+            GenericConstraint::Lifetime(_) => continue,
+        };
+        let self_ty = match predicate.bounded_ty.0 {
+            TypeNode::TypeParam(param) if fresh.contains(&param) => TypeNode::TypeParam(param),
+            _ => continue,
+        };
+        for bound in &mut predicate.bounds {
+            let bound = match bound {
+                TypeParamBound::Trait(bound) => bound,
+                TypeParamBound::Lifetime(_) => continue,
+            };
+            let segment = bound.path.path.last_mut().unwrap();
+            let args = match &mut segment.args {
+                PathArguments::AngleBracketed(args) => &mut args.args.args,
+                _ => continue,
+            };
+            let mut assocs = Vec::new();
+            args.retain(|arg| match arg {
+                GenericArgument::Binding(binding) => match binding.ty.0 {
+                    TypeNode::TypeParam(param) if fresh.contains(&param) => {
+                        assocs.push((param, binding.ident.clone()));
+                        false
+                    }
+                    _ => true,
+                },
+                _ => true,
+            });
+            if args.is_empty() {
+                segment.args = PathArguments::None;
+            }
+            for (param, assoc) in assocs {
+                let projection = TypeNode::Projection {
+                    self_ty: Box::new(self_ty.clone()),
+                    trait_path: Some(Box::new(bound.path.clone())),
+                    assoc,
+                };
+                projections.insert(param, projection);
+            }
+        }
+    }
+    if projections.is_empty() {
+        return;
+    }
+
+    // A nested `impl Trait` is counted before the one whose bounds hold it,
+    // so a projection only refers to params with a higher index
+    let params: Vec<_> = projections.keys().rev().copied().collect();
+    for param in params {
+        let mut projection = projections[&param].clone();
+        projection.substitute(&projections);
+        projections.insert(param, projection);
+    }
+    generics.params.retain(|param| match param {
+        GenericParam::Type(param) => !projections.contains_key(param),
+        _ => true,
+    });
+    for constraint in &mut generics.constraints {
+        if let GenericConstraint::Type(predicate) = constraint {
+            predicate.bounded_ty.0.substitute(&projections);
+            for bound in &mut predicate.bounds {
+                bound.walk_types_mut(&mut |node| {
+                    node.substitute(&projections);
+                    false
+                });
+            }
+        }
+    }
+}
@@ -1,7 +1,16 @@
-use crate::{GlobalCounter, Ident, Path, Type, TypeNode, LIFETIMES, STATIC_LIFETIME, TYPE_PARAMS};
+use crate::print::with_param_names;
+use crate::ty::param_names;
+use crate::{
+    GlobalCounter, Ident, Path, Print, ReflectError, Type, TypeNode, ANONYMOUS_LIFETIME, LIFETIMES,
+    STATIC_LIFETIME, TYPE_PARAMS,
+};
+use quote::quote;
+use ref_cast::RefCast;
 use std::collections::BTreeMap;
 use std::default::Default;
-use syn::{parse_str, BoundLifetimes, PredicateLifetime, WhereClause, WherePredicate};
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{parse_str, BoundLifetimes, PredicateLifetime, Token, WhereClause, WherePredicate};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Generics {
@@ -28,8 +37,10 @@ pub(crate) enum GenericParam {
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub(crate) struct TypeParam(pub usize);
 
+/// A lifetime in a reflected type, e.g. `'a` in `Wrapper<'a, T>`. Lifetimes
+/// are interned, so two `Lifetime`s are equal if they are the same lifetime.
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
-pub(crate) struct Lifetime(pub usize);
+pub struct Lifetime(pub(crate) usize);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum GenericConstraint {
@@ -45,17 +56,27 @@ pub(crate) struct PredicateType {
     pub(crate) bounds: Vec<TypeParamBound>,
 }
 
+/// A bound of a type param, like `Clone` or `'a` in `T: Clone + 'a`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) enum TypeParamBound {
+pub enum TypeParamBound {
     Trait(TraitBound),
     Lifetime(Lifetime),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct TraitBound {
+pub struct TraitBound {
     /// A set of bound Lifetimes: `for<'a, 'b, 'c>`.
     pub(crate) lifetimes: Vec<Lifetime>,
     pub(crate) path: Path,
+    pub(crate) modifier: TraitBoundModifier,
+}
+
+/// Whether a trait bound is written `?Trait`, which only relaxes the implicit
+/// `Sized` bound as `?Sized`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum TraitBoundModifier {
+    None,
+    Maybe,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -74,8 +95,9 @@ pub(crate) struct GenericArguments {
     pub(crate) args: Vec<GenericArgument>,
 }
 
+/// A generic argument of a path, e.g. `'a`, `T` or `Item = T`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) enum GenericArgument {
+pub enum GenericArgument {
     Type(Type),
     Lifetime(Lifetime),
     Binding(Binding),
@@ -83,20 +105,24 @@ pub(crate) enum GenericArgument {
     Const(Expr),
 }
 
+/// An associated type binding like `Item = T` in `Iterator<Item = T>`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Binding {
+pub struct Binding {
     pub(crate) ident: Ident,
     pub(crate) ty: Type,
 }
 
+/// An associated type constraint like `Item: Display` in
+/// `Iterator<Item: Display>`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Constraint {
+pub struct Constraint {
     pub(crate) ident: Ident,
     pub(crate) bounds: Vec<TypeParamBound>,
 }
 
+/// A const generic argument, which is not supported yet
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Expr {
+pub struct Expr {
     pub(crate) private: (),
 }
 
@@ -106,15 +132,21 @@ pub struct SynParamMap {
     pub(crate) map: BTreeMap<String, GenericParam>,
 }
 
+#[derive(Clone)]
 pub struct ParamMap {
     pub(crate) map: BTreeMap<GenericParam, GenericParam>,
 }
 
 impl SynParamMap {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         let static_lifetime = "'static".to_string();
+        let anonymous_lifetime = "'_".to_string();
         let mut param_map = BTreeMap::new();
         param_map.insert(static_lifetime, GenericParam::Lifetime(STATIC_LIFETIME));
+        param_map.insert(
+            anonymous_lifetime,
+            GenericParam::Lifetime(ANONYMOUS_LIFETIME),
+        );
         SynParamMap { map: param_map }
     }
 
@@ -156,6 +188,10 @@ impl ParamMap {
             GenericParam::Lifetime(STATIC_LIFETIME),
             GenericParam::Lifetime(STATIC_LIFETIME),
         );
+        map.insert(
+            GenericParam::Lifetime(ANONYMOUS_LIFETIME),
+            GenericParam::Lifetime(ANONYMOUS_LIFETIME),
+        );
         ParamMap { map }
     }
 
@@ -170,9 +206,46 @@ impl ParamMap {
     pub(crate) fn get(&self, key: &GenericParam) -> Option<&GenericParam> {
         self.map.get(key)
     }
+
+    /// The `for<'a>` lifetimes of a bound are not generic params of the item,
+    /// so each copy of the bound gets fresh ones. Returns them together with
+    /// the map to use for the rest of the bound.
+    fn with_fresh_bound_lifetimes(&self, lifetimes: &[Lifetime]) -> (Vec<Lifetime>, ParamMap) {
+        let mut param_map = self.clone();
+        let lifetimes = lifetimes
+            .iter()
+            .map(|lifetime| {
+                let fresh = LIFETIMES.count();
+                param_map.insert(
+                    GenericParam::Lifetime(*lifetime),
+                    GenericParam::Lifetime(fresh),
+                );
+                fresh
+            })
+            .collect();
+        (lifetimes, param_map)
+    }
 }
 
 impl TypeParamBound {
+    /// A trait bound for the trait at `path`, without any `for<'a>` lifetimes
+    ///
+    /// ```
+    /// use reflect::{Path, SynParamMap, TypeParamBound};
+    ///
+    /// let mut param_map = SynParamMap::new();
+    /// let path = Path::path_from_str("::std::fmt::Debug", &mut param_map);
+    /// let bound = TypeParamBound::from_path(path);
+    /// assert!(matches!(bound, TypeParamBound::Trait(_)));
+    /// ```
+    pub fn from_path(path: Path) -> Self {
+        TypeParamBound::Trait(TraitBound {
+            lifetimes: Vec::new(),
+            path,
+            modifier: TraitBoundModifier::None,
+        })
+    }
+
     pub(crate) fn get_type_param_bound(
         type_param_bound: &str,
         param_map: &mut SynParamMap,
@@ -184,23 +257,74 @@ impl TypeParamBound {
         )
     }
 
+    /// Parse one or more `+`-separated bounds, e.g. `Read + Write + 'static`
+    pub(crate) fn get_type_param_bounds(
+        type_param_bounds: &str,
+        param_map: &mut SynParamMap,
+    ) -> Vec<Self> {
+        let bounds = Punctuated::<syn::TypeParamBound, Token![+]>::parse_separated_nonempty
+            .parse_str(type_param_bounds)
+            .expect("TypeParamBound::get_type_param_bounds: Not a list of TypeParamBounds");
+        syn_to_type_param_bounds(bounds, param_map).collect()
+    }
+
     pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
         match self {
             TypeParamBound::Lifetime(lifetime) => {
                 TypeParamBound::Lifetime(lifetime.clone_with_fresh_generics(param_map))
             }
 
-            TypeParamBound::Trait(bound) => TypeParamBound::Trait(TraitBound {
-                lifetimes: bound
-                    .lifetimes
-                    .iter()
-                    .map(|lifetime| lifetime.clone_with_fresh_generics(param_map))
-                    .collect(),
+            TypeParamBound::Trait(bound) => {
+                let (lifetimes, param_map) = param_map.with_fresh_bound_lifetimes(&bound.lifetimes);
+                TypeParamBound::Trait(TraitBound {
+                    lifetimes,
+                    path: bound.path.clone_with_fresh_generics(&param_map),
+                    modifier: bound.modifier,
+                })
+            }
+        }
+    }
 
-                path: bound.path.clone_with_fresh_generics(param_map),
-            }),
+    pub(crate) fn elide_lifetimes(&mut self) {
+        if let TypeParamBound::Trait(bound) = self {
+            bound.lifetimes.clear();
+            bound.path.elide_lifetimes();
         }
     }
+
+    pub(crate) fn walk_paths_mut(&mut self, f: &mut dyn FnMut(&mut Path)) {
+        if let TypeParamBound::Trait(bound) = self {
+            bound.path.walk_paths_mut(f);
+        }
+    }
+
+    pub(crate) fn walk_types_mut(&mut self, f: &mut dyn FnMut(&mut TypeNode) -> bool) {
+        if let TypeParamBound::Trait(bound) = self {
+            bound.path.walk_types_mut(f);
+        }
+    }
+
+    pub(crate) fn walk_lifetimes_mut(&mut self, f: &mut dyn FnMut(&mut Lifetime)) {
+        match self {
+            TypeParamBound::Trait(bound) => {
+                bound.lifetimes.iter_mut().for_each(&mut *f);
+                bound.path.walk_lifetimes_mut(f);
+            }
+            TypeParamBound::Lifetime(lifetime) => f(lifetime),
+        }
+    }
+}
+
+impl Binding {
+    /// The name of the associated type
+    pub fn name(&self) -> String {
+        self.ident.to_string()
+    }
+
+    /// The type the associated type is bound to
+    pub fn ty(&self) -> &Type {
+        &self.ty
+    }
 }
 
 impl Lifetime {
@@ -238,22 +362,135 @@ impl GenericParam {
     }
 }
 
+/// The key that `GenericConstraint::sort_key` returns. It compares params
+/// and lifetimes by their index instead of by their printed name, so that
+/// `__T10` comes after `__T2`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct ConstraintKey {
+    /// Type predicates come before lifetime predicates
+    is_lifetime: bool,
+    /// The type params of the bounded type in the order they appear, or the
+    /// bounded lifetime
+    params: Vec<usize>,
+    bounds: Vec<BoundKey>,
+    /// The whole bounded type, which orders the predicates on different
+    /// types of the same params, like `T` and `Vec<T>`
+    bounded_ty: Vec<KeyPart>,
+}
+
+/// Trait bounds come before lifetime bounds
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum BoundKey {
+    Trait(Vec<KeyPart>),
+    Lifetime(usize),
+}
+
+/// A node of a type, flattened in the order the type is walked
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum KeyPart {
+    Param(usize),
+    Lifetime(usize),
+    Ident(String),
+    Node(&'static str),
+}
+
+fn type_key(ty: &TypeNode) -> Vec<KeyPart> {
+    let mut key = Vec::new();
+    let mut ty = ty.clone();
+    ty.walk_types_mut(&mut |node| {
+        match node {
+            TypeNode::TypeParam(param) => key.push(KeyPart::Param(param.0)),
+            TypeNode::Path(path) => key.extend(
+                path.path
+                    .iter()
+                    .map(|segment| KeyPart::Ident(segment.ident.to_string())),
+            ),
+            TypeNode::Projection { assoc, .. } => {
+                key.push(KeyPart::Node("Projection"));
+                key.push(KeyPart::Ident(assoc.to_string()));
+            }
+            TypeNode::DataStructure(data) => key.push(KeyPart::Ident(data.name.to_string())),
+            TypeNode::Array(_, len) | TypeNode::Verbatim(len) => {
+                key.push(KeyPart::Ident(len.0.to_string()))
+            }
+            TypeNode::Reference { is_mut: true, .. } => key.push(KeyPart::Node("&mut")),
+            TypeNode::Reference { is_mut: false, .. } => key.push(KeyPart::Node("&")),
+            TypeNode::Infer => key.push(KeyPart::Node("_")),
+            TypeNode::Tuple(_) => key.push(KeyPart::Node("()")),
+            TypeNode::PrimitiveStr => key.push(KeyPart::Node("str")),
+            TypeNode::Dereference(_) => key.push(KeyPart::Node("*")),
+            TypeNode::TraitObject(_) => key.push(KeyPart::Node("dyn")),
+            TypeNode::ImplTrait(..) => key.push(KeyPart::Node("impl")),
+            TypeNode::Slice(_) => key.push(KeyPart::Node("[]")),
+            TypeNode::BareFn(_) => key.push(KeyPart::Node("fn")),
+            TypeNode::Never => key.push(KeyPart::Node("!")),
+        }
+        true
+    });
+    ty.walk_lifetimes_mut(&mut |lifetime| key.push(KeyPart::Lifetime(lifetime.0)));
+    key
+}
+
 impl GenericConstraint {
-    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
+    /// The key the constraints of a where clause are sorted by, so that the
+    /// generated code does not depend on the order they were found in: the
+    /// params of the bounded type followed by the bounds, with all type
+    /// predicates before the lifetime predicates
+    pub(crate) fn sort_key(&self) -> ConstraintKey {
         match self {
-            Self::Type(predicate) => Self::Type(PredicateType {
-                lifetimes: predicate
-                    .lifetimes
+            Self::Type(predicate) => {
+                let mut params = Vec::new();
+                predicate.bounded_ty.0.any_node(&mut |node| {
+                    if let TypeNode::TypeParam(param) = node {
+                        params.push(param.0);
+                    }
+                    false
+                });
+                let bounds = predicate
+                    .bounds
                     .iter()
-                    .map(|lifetime| lifetime.clone_with_fresh_generics(param_map))
-                    .collect(),
-                bounded_ty: predicate.bounded_ty.clone_with_fresh_generics(param_map),
-                bounds: predicate
+                    .map(|bound| match bound {
+                        TypeParamBound::Trait(bound) => {
+                            BoundKey::Trait(type_key(&TypeNode::Path(bound.path.clone())))
+                        }
+                        TypeParamBound::Lifetime(lifetime) => BoundKey::Lifetime(lifetime.0),
+                    })
+                    .collect();
+                ConstraintKey {
+                    is_lifetime: false,
+                    params,
+                    bounds,
+                    bounded_ty: type_key(&predicate.bounded_ty.0),
+                }
+            }
+            Self::Lifetime(lifetime_def) => ConstraintKey {
+                is_lifetime: true,
+                params: vec![lifetime_def.lifetime.0],
+                bounds: lifetime_def
                     .bounds
                     .iter()
-                    .map(|bound| bound.clone_with_fresh_generics(param_map))
+                    .map(|lifetime| BoundKey::Lifetime(lifetime.0))
                     .collect(),
-            }),
+                bounded_ty: Vec::new(),
+            },
+        }
+    }
+
+    pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
+        match self {
+            Self::Type(predicate) => {
+                let (lifetimes, param_map) =
+                    param_map.with_fresh_bound_lifetimes(&predicate.lifetimes);
+                Self::Type(PredicateType {
+                    lifetimes,
+                    bounded_ty: predicate.bounded_ty.clone_with_fresh_generics(&param_map),
+                    bounds: predicate
+                        .bounds
+                        .iter()
+                        .map(|bound| bound.clone_with_fresh_generics(&param_map))
+                        .collect(),
+                })
+            }
             Self::Lifetime(lifetime_def) => Self::Lifetime(LifetimeDef {
                 lifetime: lifetime_def.lifetime.clone_with_fresh_generics(param_map),
                 bounds: lifetime_def
@@ -267,6 +504,12 @@ impl GenericConstraint {
 }
 
 impl Generics {
+    /// Whether there are neither generic params nor constraints, in which case
+    /// no angle brackets or where clause should be emitted
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty() && self.constraints.is_empty()
+    }
+
     pub fn set_generic_params(&mut self, params: &[&str]) {
         let syn_params = params.iter().map(|param| parse_str(param).unwrap());
         let (params, constraints, mut param_map) = syn_to_generic_params(syn_params);
@@ -284,6 +527,64 @@ impl Generics {
         self.constraints.extend(constraints);
     }
 
+    /// Whether a bound of the generics relaxes the implicit `Sized` bound of
+    /// `ty` with `?Sized`, as in `<T: ?Sized>` or `where T: ?Sized`
+    pub(crate) fn relaxes_sized(&self, ty: &Type) -> bool {
+        self.constraints.iter().any(|constraint| match constraint {
+            GenericConstraint::Type(pred_ty) => {
+                pred_ty.bounded_ty == *ty
+                    && pred_ty.bounds.iter().any(|bound| match bound {
+                        TypeParamBound::Trait(bound) => {
+                            bound.modifier == TraitBoundModifier::Maybe
+                                && bound
+                                    .path
+                                    .path
+                                    .last()
+                                    .is_some_and(|segment| segment.ident == Ident::new("Sized"))
+                        }
+                        TypeParamBound::Lifetime(_) => false,
+                    })
+            }
+            GenericConstraint::Lifetime(_) => false,
+        })
+    }
+
+    /// Parses generics with an optional where clause, like
+    /// `<'a, T: Clone + 'a> where T: Copy`. The params are added to
+    /// `param_map`, so that types parsed afterwards with the same map can
+    /// refer to them. The bounds can only refer to the params in `s`.
+    pub fn from_str(s: &str, param_map: &mut SynParamMap) -> Result<Self, ReflectError> {
+        let parser = |input: ParseStream| {
+            let mut generics: syn::Generics = input.parse()?;
+            generics.where_clause = input.parse()?;
+            Ok(generics)
+        };
+        let generics = parser
+            .parse_str(s)
+            .map_err(|error| ReflectError::InvalidGenerics { error })?;
+        reject_const_params(&generics)?;
+        let generics = Generics::syn_to_generics(generics);
+        param_map.append(&mut generics.param_map.clone());
+        Ok(generics)
+    }
+
+    /// Render the generics using the names of their params, e.g.
+    /// `< 'a , T > where T : Clone`. The bounds of the params are part of
+    /// the where clause.
+    pub fn display(&self) -> String {
+        let names = param_names(&self.param_map);
+        with_param_names(names, || {
+            let params = Print::ref_cast(self);
+            let where_clause = if self.constraints.is_empty() {
+                None
+            } else {
+                let constraints = self.constraints.iter().map(Print::ref_cast);
+                Some(quote!(where #(#constraints),*))
+            };
+            quote!(<#params> #where_clause).to_string()
+        })
+    }
+
     pub(crate) fn syn_to_generics(generics: syn::Generics) -> Self {
         let (params, mut constraints, mut param_map) = syn_to_generic_params(generics.params);
         if let Some(where_clause) = generics.where_clause {
@@ -376,11 +677,15 @@ where
             bounded_ty,
             bounds,
             ..
-        }) => GenericConstraint::Type(PredicateType {
-            lifetimes: syn_to_bound_lifetimes(lifetimes, param_map),
-            bounded_ty: Type::syn_to_type(bounded_ty, param_map),
-            bounds: syn_to_type_param_bounds(bounds, param_map).collect(),
-        }),
+        }) => {
+            // The `for<'a>` lifetimes may be used in the bounded type
+            let lifetimes = syn_to_bound_lifetimes(lifetimes, param_map);
+            GenericConstraint::Type(PredicateType {
+                lifetimes,
+                bounded_ty: Type::syn_to_type(bounded_ty, param_map),
+                bounds: syn_to_type_param_bounds(bounds, param_map).collect(),
+            })
+        }
         WherePredicate::Lifetime(PredicateLifetime {
             lifetime, bounds, ..
         }) => GenericConstraint::Lifetime(LifetimeDef {
@@ -446,6 +751,17 @@ where
     (params, constraints, param_map)
 }
 
+/// Const params are not modeled by `Generics`, so they are refused before the
+/// conversion instead of producing code that can not refer to them
+pub(crate) fn reject_const_params(generics: &syn::Generics) -> Result<(), ReflectError> {
+    match generics.const_params().next() {
+        Some(param) => Err(ReflectError::UnsupportedConstParam {
+            ident: param.ident.clone(),
+        }),
+        None => Ok(()),
+    }
+}
+
 pub(crate) fn param_mapping(param: &syn::GenericParam, param_map: &mut SynParamMap) {
     match &param {
         syn::GenericParam::Type(syn::TypeParam { ident, .. }) => {
@@ -478,10 +794,17 @@ pub(crate) fn syn_to_type_param_bound(
 ) -> TypeParamBound {
     match type_param_bound {
         syn::TypeParamBound::Trait(syn::TraitBound {
-            lifetimes, path, ..
+            lifetimes,
+            path,
+            modifier,
+            ..
         }) => TypeParamBound::Trait(TraitBound {
             lifetimes: syn_to_bound_lifetimes(lifetimes, param_map),
             path: Path::syn_to_path(path, param_map),
+            modifier: match modifier {
+                syn::TraitBoundModifier::None => TraitBoundModifier::None,
+                syn::TraitBoundModifier::Maybe(_) => TraitBoundModifier::Maybe,
+            },
         }),
         syn::TypeParamBound::Lifetime(lifetime) => {
             TypeParamBound::Lifetime(param_map.get_lifetime(&lifetime.to_string()))
@@ -499,9 +822,122 @@ impl GenericArguments {
                 .collect(),
         }
     }
+
+    pub(crate) fn elide_lifetimes(&mut self) {
+        self.args
+            .retain(|arg| !matches!(arg, GenericArgument::Lifetime(_)));
+        for arg in &mut self.args {
+            match arg {
+                GenericArgument::Type(ty) => ty.0.elide_lifetimes(),
+                GenericArgument::Binding(binding) => binding.ty.0.elide_lifetimes(),
+                GenericArgument::Constraint(constraint) => constraint
+                    .bounds
+                    .iter_mut()
+                    .for_each(TypeParamBound::elide_lifetimes),
+                GenericArgument::Lifetime(_) | GenericArgument::Const(_) => {}
+            }
+        }
+    }
+
+    pub(crate) fn walk_paths_mut(&mut self, f: &mut dyn FnMut(&mut Path)) {
+        for arg in &mut self.args {
+            match arg {
+                GenericArgument::Type(ty) => ty.0.walk_paths_mut(f),
+                GenericArgument::Binding(binding) => binding.ty.0.walk_paths_mut(f),
+                GenericArgument::Constraint(constraint) => constraint
+                    .bounds
+                    .iter_mut()
+                    .for_each(|bound| bound.walk_paths_mut(f)),
+                GenericArgument::Lifetime(_) | GenericArgument::Const(_) => {}
+            }
+        }
+    }
+
+    pub(crate) fn walk_types_mut(&mut self, f: &mut dyn FnMut(&mut TypeNode) -> bool) {
+        for arg in &mut self.args {
+            match arg {
+                GenericArgument::Type(ty) => ty.0.walk_types_mut(f),
+                GenericArgument::Binding(binding) => binding.ty.0.walk_types_mut(f),
+                GenericArgument::Constraint(constraint) => constraint
+                    .bounds
+                    .iter_mut()
+                    .for_each(|bound| bound.walk_types_mut(f)),
+                GenericArgument::Lifetime(_) | GenericArgument::Const(_) => {}
+            }
+        }
+    }
+
+    pub(crate) fn walk_lifetimes_mut(&mut self, f: &mut dyn FnMut(&mut Lifetime)) {
+        for arg in &mut self.args {
+            match arg {
+                GenericArgument::Lifetime(lifetime) => f(lifetime),
+                GenericArgument::Type(ty) => ty.0.walk_lifetimes_mut(f),
+                GenericArgument::Binding(binding) => binding.ty.0.walk_lifetimes_mut(f),
+                GenericArgument::Constraint(constraint) => constraint
+                    .bounds
+                    .iter_mut()
+                    .for_each(|bound| bound.walk_lifetimes_mut(f)),
+                GenericArgument::Const(_) => {}
+            }
+        }
+    }
 }
 
 impl GenericArgument {
+    /// The type, if the argument is a type.
+    ///
+    /// ```
+    /// use reflect::{Path, SynParamMap};
+    ///
+    /// let mut param_map = SynParamMap::new();
+    /// let path = Path::path_from_str("::std::borrow::Cow<'static, str>", &mut param_map);
+    /// let args = path.generic_arguments();
+    /// assert!(args[0].as_type().is_none());
+    /// assert!(args[1].as_type().unwrap().is_primitive());
+    /// ```
+    pub fn as_type(&self) -> Option<&Type> {
+        match self {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    /// The lifetime, if the argument is a lifetime.
+    ///
+    /// ```
+    /// use reflect::{Path, SynParamMap};
+    ///
+    /// let mut param_map = SynParamMap::new();
+    /// let path = Path::path_from_str("::std::borrow::Cow<'static, str>", &mut param_map);
+    /// let args = path.generic_arguments();
+    /// assert!(args[0].as_lifetime().is_some());
+    /// assert!(args[1].as_lifetime().is_none());
+    /// ```
+    pub fn as_lifetime(&self) -> Option<Lifetime> {
+        match self {
+            GenericArgument::Lifetime(lifetime) => Some(*lifetime),
+            _ => None,
+        }
+    }
+
+    /// The associated type binding, if the argument is one.
+    ///
+    /// ```
+    /// use reflect::{Path, SynParamMap};
+    ///
+    /// let mut param_map = SynParamMap::new();
+    /// let path = Path::path_from_str("::std::iter::Iterator<Item = u8>", &mut param_map);
+    /// let binding = path.generic_arguments()[0].as_binding().unwrap();
+    /// assert_eq!(binding.name(), "Item");
+    /// assert!(binding.ty().is_primitive());
+    /// ```
+    pub fn as_binding(&self) -> Option<&Binding> {
+        match self {
+            GenericArgument::Binding(binding) => Some(binding),
+            _ => None,
+        }
+    }
+
     pub(crate) fn syn_to_generic_argument(
         arg: syn::GenericArgument,
         param_map: &mut SynParamMap,
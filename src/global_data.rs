@@ -1,4 +1,9 @@
-use crate::{Invoke, Lifetime, MacroInvoke, Push, TypeParam, TypedIndex, ValueNode};
+use crate::print::ParamNames;
+use crate::{
+    CodegenOptions, InferenceLimits, Invoke, Lifetime, MacroInvoke, ProjectionRule, Push, TypeNode,
+    TypeParam, TypedIndex, ValueNode,
+};
+use fxhash::FxHashSet;
 use std::cell::{Cell, RefCell};
 use std::thread::LocalKey;
 
@@ -8,9 +13,24 @@ thread_local! {
     pub(crate) static MACROS: RefCell<Vec<MacroInvoke>> = RefCell::new(Vec::new());
     pub(crate) static TYPE_PARAMS: Cell<usize> = Cell::new(0);
     pub(crate) static LIFETIMES: Cell<usize> = Cell::new(1);
+    // The associated type projections registered with `Execution::register_projection`
+    pub(crate) static PROJECTIONS: RefCell<Vec<ProjectionRule>> = RefCell::new(Vec::new());
+    // The types registered with `Execution::register_copy_type`
+    pub(crate) static COPY_TYPES: RefCell<FxHashSet<TypeNode>> = RefCell::new(FxHashSet::default());
+    // The types registered with `Execution::register_drop_free_type`
+    pub(crate) static DROP_FREE_TYPES: RefCell<FxHashSet<TypeNode>> = RefCell::new(FxHashSet::default());
+    // The limits set with `Execution::set_inference_limits`
+    pub(crate) static INFERENCE_LIMITS: Cell<InferenceLimits> = Cell::new(InferenceLimits::default());
+    // The options set with `Execution::set_codegen_options`
+    pub(crate) static CODEGEN_OPTIONS: Cell<CodegenOptions> = Cell::new(CodegenOptions::default());
+    // The names that params are printed with inside of `print::with_param_names`
+    pub(crate) static PARAM_NAMES: RefCell<Option<ParamNames>> = RefCell::new(None);
 }
 
 pub(crate) const STATIC_LIFETIME: Lifetime = Lifetime(0);
+// The anonymous lifetime `'_`. In a signature it is a fresh lifetime like an
+// elided one, anywhere else it is kept as it is.
+pub(crate) const ANONYMOUS_LIFETIME: Lifetime = Lifetime(usize::MAX);
 
 pub(crate) trait GlobalBorrow<T> {
     fn with_borrow<R, F>(&'static self, f: F) -> R
@@ -86,4 +106,9 @@ pub(crate) fn clear() {
     VALUES.with(|data| data.borrow_mut().clear());
     INVOKES.with(|data| data.borrow_mut().clear());
     MACROS.with(|data| data.borrow_mut().clear());
+    PROJECTIONS.with(|data| data.borrow_mut().clear());
+    COPY_TYPES.with(|data| data.borrow_mut().clear());
+    DROP_FREE_TYPES.with(|data| data.borrow_mut().clear());
+    INFERENCE_LIMITS.with(|limits| limits.set(InferenceLimits::default()));
+    CODEGEN_OPTIONS.with(|options| options.set(CodegenOptions::default()));
 }
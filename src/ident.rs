@@ -1,4 +1,4 @@
-use proc_macro2::{Span, TokenStream};
+use proc_macro2::{Punct, Spacing, Span, TokenStream};
 use quote::ToTokens;
 use std::fmt::{self, Display};
 
@@ -14,6 +14,9 @@ impl Ident {
 impl Display for Ident {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == "$crate" {
+            return f.write_str(&self.0);
+        }
         proc_macro2::Ident::new(&self.0, Span::call_site()).fmt(f)
     }
 }
@@ -26,6 +29,13 @@ impl From<proc_macro2::Ident> for Ident {
 
 impl ToTokens for Ident {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        // `$crate` is not an ident on its own, it is emitted as `$` followed
+        // by the `crate` keyword
+        if self.0 == "$crate" {
+            Punct::new('$', Spacing::Alone).to_tokens(tokens);
+            proc_macro2::Ident::new("crate", Span::call_site()).to_tokens(tokens);
+            return;
+        }
         proc_macro2::Ident::new(&self.0, Span::call_site()).to_tokens(tokens);
     }
 }
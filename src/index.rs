@@ -22,7 +22,7 @@ pub(crate) trait TypedIndex {
     fn index(i: usize) -> Self::Index;
 }
 
-#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub(crate) struct ValueRef(pub usize);
 
 impl TypedIndex for ValueNode {
@@ -332,6 +332,7 @@ mod attr;
 mod compiler;
 mod data;
 mod derive;
+mod error;
 mod execution;
 mod field;
 mod function;
@@ -344,48 +345,66 @@ mod module;
 mod node;
 mod parent;
 mod path;
+#[cfg(feature = "pretty")]
+mod pretty;
 mod print;
+mod projection;
 mod signature;
 mod trait_inference;
 mod ty;
 mod value;
 mod wip;
 
+pub use crate::compiler::CompleteImpl;
 pub use crate::data::{
     Data, Enum, Struct, StructStruct, StructVariant, TupleStruct, TupleVariant, UnitStruct,
     UnitVariant, Variant,
 };
-pub use crate::derive::derive;
-pub use crate::execution::Execution;
+#[cfg(feature = "pretty")]
+pub use crate::derive::derive_to_pretty_string;
+pub use crate::derive::{derive, derive_with_impls};
+pub use crate::error::ReflectError;
+pub use crate::execution::{Execution, ImplBatch};
 pub use crate::field::{Field, Fields};
-pub use crate::function::Function;
-pub use crate::generics::{Generics, SynParamMap};
+pub use crate::function::{Function, PartialFunction};
+pub use crate::generics::{
+    Binding, GenericArgument, Generics, Lifetime, SynParamMap, TraitBound, TypeParamBound,
+};
 pub use crate::module::Module;
 pub use crate::parent::{Parent, ParentBuilder, ParentKind};
-pub use crate::path::Path;
+pub use crate::path::{Path, PathSegment};
+#[cfg(feature = "pretty")]
+pub use crate::pretty::pretty_print;
 pub use crate::signature::Signature;
-pub use crate::ty::Type;
+pub use crate::trait_inference::{
+    ConstraintSet, InferenceLimits, Snapshot, TraitImplTable, Unifier,
+};
+pub use crate::ty::{CodegenOptions, ErasedType, Type, WhereClauseStyle};
 pub use crate::value::Value;
 pub use crate::wip::{MakeFunction, MakeImpl};
 
-use crate::compiler::{CompleteFunction, CompleteImpl, Program};
-use crate::execution::Tracker;
+use crate::compiler::{CompleteFunction, Program};
+use crate::execution::{Tracker, WipBatch};
 use crate::field::Accessor;
 use crate::generics::{
-    GenericArgument, GenericArguments, GenericConstraint, GenericParam, Lifetime, LifetimeDef,
-    ParamMap, PredicateType, TraitBound, TypeParam, TypeParamBound,
+    GenericArguments, GenericConstraint, GenericParam, LifetimeDef, ParamMap, PredicateType,
+    TypeParam,
 };
 use crate::global_data::{
-    GlobalBorrow, GlobalCounter, GlobalPush, INVOKES, LIFETIMES, MACROS, STATIC_LIFETIME,
-    TYPE_PARAMS, VALUES,
+    GlobalBorrow, GlobalCounter, GlobalPush, ANONYMOUS_LIFETIME, CODEGEN_OPTIONS, COPY_TYPES,
+    DROP_FREE_TYPES, INFERENCE_LIMITS, INVOKES, LIFETIMES, MACROS, PARAM_NAMES, PROJECTIONS,
+    STATIC_LIFETIME, TYPE_PARAMS, VALUES,
 };
 use crate::ident::Ident;
 use crate::index::{InvokeRef, MacroInvokeRef, Push, TypeEqualitySetRef, TypedIndex, ValueRef};
-use crate::node::ValueNode;
-use crate::path::{AngleBracketedGenericArguments, PathArguments, SimplePath};
-use crate::print::Print;
+use crate::node::{MatchArm, ValueNode};
+use crate::path::{
+    AngleBracketedGenericArguments, ParenthesizedGenericArguments, PathArguments, SimplePath,
+};
+use crate::print::{Print, Turbofish};
+use crate::projection::ProjectionRule;
 use crate::runtime::{RuntimeFunction, RuntimeTrait, RuntimeType};
 use crate::signature::Receiver;
 use crate::trait_inference::{TraitInferenceResult, TypeEqualitySet};
-use crate::ty::{DataStructure, TypeNode};
+use crate::ty::{DataStructure, TypeNode, Verbatim};
 use crate::wip::{Invoke, MacroInvoke, WipFunction, WipImpl};
@@ -1,11 +1,15 @@
 use crate::{
     Accessor, Data, GlobalBorrow, Ident, InvokeRef, MacroInvokeRef, Type, TypeNode, ValueRef,
-    INVOKES, VALUES,
+    Variant, INVOKES, VALUES,
 };
+use proc_macro2::Span;
+use std::ops::Range;
 
 #[derive(Debug, Clone)]
 pub(crate) enum ValueNode {
     Tuple(Vec<ValueRef>),
+    /// An array literal `[a, b, c]`
+    Array(Vec<ValueRef>),
     Str(String),
     // TODO: Add lifetime parameter
     Reference {
@@ -17,8 +21,9 @@ pub(crate) enum ValueNode {
         name: Ident,
         ty: Type,
     },
+    /// A struct literal `Self { .. }` of the macro input type
     DataStructure {
-        name: String,
+        ty: Type,
         data: Data<ValueRef>,
     },
     Invoke(InvokeRef),
@@ -26,8 +31,47 @@ pub(crate) enum ValueNode {
         parent: ValueRef,
         accessor: Accessor,
         ty: Type,
+        /// The span of the field in the macro input, if it is a field of
+        /// the input type
+        span: Option<Span>,
     },
     MacroInvocation(MacroInvokeRef),
+    /// The `?` operator applied to a `Result` or an `Option`
+    Try(ValueRef),
+    /// A `match` on the variants of an enum value
+    Match {
+        value: ValueRef,
+        arms: Vec<MatchArm>,
+        /// Whether a `_` arm follows the arms of the variants
+        wildcard: bool,
+    },
+    /// A field bound by the pattern of a match arm, or a parameter of a
+    /// closure
+    PatternBinding {
+        ty: Type,
+    },
+    /// A closure `|params| { .. }`, like the wrapper generated by
+    /// `Function::partial_apply`. The values of the body are evaluated when
+    /// the closure is called, and `ret` is the value of the body.
+    Closure {
+        params: Vec<ValueRef>,
+        values: Range<ValueRef>,
+        ret: ValueRef,
+    },
+    /// A call of a closure
+    Call {
+        closure: ValueRef,
+        args: Vec<ValueRef>,
+    },
+}
+
+/// An arm of a `ValueNode::Match`. The values of the arm are evaluated
+/// inside of the arm, and `ret` is the value of the arm.
+#[derive(Debug, Clone)]
+pub(crate) struct MatchArm {
+    pub(crate) variant: Variant<ValueRef>,
+    pub(crate) values: Range<ValueRef>,
+    pub(crate) ret: ValueRef,
 }
 
 impl ValueNode {
@@ -36,6 +80,15 @@ impl ValueNode {
             ValueNode::Tuple(types) => Type(TypeNode::Tuple(
                 types.iter().map(|type_ref| type_ref.get_type().0).collect(),
             )),
+            // The elements are made equal by the trait inference, so the
+            // first one stands for all of them
+            ValueNode::Array(values) => {
+                let elem = match values.first() {
+                    Some(value) => value.get_type(),
+                    None => Type(TypeNode::Infer),
+                };
+                elem.array(&values.len().to_string())
+            }
             ValueNode::Str(_) => Type(TypeNode::PrimitiveStr),
             ValueNode::Reference { is_mut, value } => Type(TypeNode::Reference {
                 is_mut: *is_mut,
@@ -47,11 +100,33 @@ impl ValueNode {
                 parent,
                 accessor,
                 ty,
+                ..
             } => ty.clone(),
             ValueNode::Invoke(invoke_ref) => {
-                INVOKES.with_borrow(|invokes| invokes[invoke_ref.0].function.sig.output.clone())
+                INVOKES.with_borrow(|invokes| invokes[invoke_ref.0].function.sig.call_output())
+            }
+            ValueNode::Try(value) => {
+                let ty = value.get_type();
+                match (ty.result_types(), ty.option_type()) {
+                    (Some((ok, _)), _) | (None, Some(ok)) => ok.clone(),
+                    // `Value::try_unwrap` only makes a `?` on a `Result` or
+                    // an `Option`, so this is not reached
+                    (None, None) => Type(TypeNode::Infer),
+                }
             }
 
+            ValueNode::Match { arms, .. } => match arms.first() {
+                Some(arm) => arm.ret.get_type(),
+                None => Type::unit(),
+            },
+            ValueNode::PatternBinding { ty } | ValueNode::DataStructure { ty, .. } => ty.clone(),
+            // A closure has no type that can be written down
+            ValueNode::Closure { .. } => Type(TypeNode::Infer),
+            ValueNode::Call { closure, .. } => match closure.node() {
+                ValueNode::Closure { ret, .. } => ret.get_type(),
+                _ => unreachable!("ValueNode::get_type: call of a value that is not a closure"),
+            },
+
             node => panic!("ValueNode::get_type"),
         }
     }
@@ -76,18 +151,23 @@ impl ValueNode {
                 ValueNode::Str(types)
             }
             ValueNode::Str(_) => ValueNode::Str(String::from("str")),
-            ValueNode::DataStructure { name, .. } => ValueNode::Str(name.to_owned()),
+            ValueNode::DataStructure { ty, .. } => ValueNode::Str(ty.0.get_name()),
             ValueNode::Reference { value, .. } => value.get_type_name(),
             ValueNode::Binding { ty, .. } => ValueNode::Str(ty.0.get_name()),
-            ValueNode::Destructure {
-                parent,
-                accessor,
-                ty,
-            } => ValueNode::Str(ty.0.get_name()),
-            ValueNode::Invoke(invoke_ref) => ValueNode::Str(
-                INVOKES
-                    .with_borrow(|invokes| invokes[invoke_ref.0].function.sig.output.0.get_name()),
-            ),
+            ValueNode::Destructure { ty, .. } => ValueNode::Str(ty.0.get_name()),
+            ValueNode::Invoke(invoke_ref) => ValueNode::Str(INVOKES.with_borrow(|invokes| {
+                invokes[invoke_ref.0]
+                    .function
+                    .sig
+                    .call_output()
+                    .0
+                    .get_name()
+            })),
+            ValueNode::Array(_)
+            | ValueNode::Try(_)
+            | ValueNode::Match { .. }
+            | ValueNode::PatternBinding { .. }
+            | ValueNode::Call { .. } => ValueNode::Str(self.get_type().0.get_name()),
             node => panic!("ValueNode::get_type_name"),
         }
     }
@@ -95,14 +175,13 @@ impl ValueNode {
     pub(crate) fn is_unit_type(&self) -> bool {
         match self {
             ValueNode::Tuple(values) if values.is_empty() => true,
-            ValueNode::Invoke(invoke_ref) => {
-                INVOKES.with_borrow(
-                    |invokes| match &invokes[invoke_ref.0].function.sig.output.0 {
-                        TypeNode::Tuple(types) => types.is_empty(),
-                        _ => false,
-                    },
-                )
-            }
+            ValueNode::Match { arms, .. } => arms.first().is_none_or(|arm| arm.ret.is_unit_type()),
+            ValueNode::Invoke(invoke_ref) => INVOKES.with_borrow(|invokes| {
+                match invokes[invoke_ref.0].function.sig.call_output().0 {
+                    TypeNode::Tuple(types) => types.is_empty(),
+                    _ => false,
+                }
+            }),
             _ => false,
         }
     }
@@ -117,6 +196,18 @@ impl ValueRef {
         VALUES.with_borrow(|values| values[self.0].get_type_name())
     }
 
+    /// The span in the macro input of the field the value was taken from,
+    /// looking through references to it and `?` applied to it
+    pub(crate) fn span(self) -> Option<Span> {
+        match self.node() {
+            ValueNode::Destructure { span, .. } => span,
+            ValueNode::Reference { value, .. }
+            | ValueNode::Dereference(value)
+            | ValueNode::Try(value) => value.span(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn is_unit_type(&self) -> bool {
         VALUES.with_borrow(|values| values[self.0].is_unit_type())
     }
@@ -1,4 +1,4 @@
-use crate::{Generics, ParamMap, Path, SynParamMap};
+use crate::{Generics, Ident, ParamMap, Path, SynParamMap};
 use std::default::Default;
 
 #[derive(Debug, Clone)]
@@ -6,12 +6,18 @@ pub struct Parent {
     pub(crate) path: Path,
     pub(crate) generics: Generics,
     pub(crate) parent_kind: ParentKind,
+    /// Whether the trait is an `unsafe trait`
+    pub(crate) unsafety: bool,
+    /// The associated types declared by the trait
+    pub(crate) assoc_types: Vec<Ident>,
 }
 
 pub struct ParentBuilder {
     pub(crate) path: Option<Path>,
     pub(crate) generics: Generics,
     pub(crate) parent_kind: ParentKind,
+    pub(crate) unsafety: bool,
+    pub(crate) assoc_types: Vec<Ident>,
 }
 
 pub trait SetPath<'a, P> {
@@ -39,6 +45,8 @@ impl ParentBuilder {
             path: None,
             generics: Default::default(),
             parent_kind,
+            unsafety: false,
+            assoc_types: Vec::new(),
         }
     }
 
@@ -47,6 +55,8 @@ impl ParentBuilder {
             path: self.path.unwrap(),
             generics: self.generics,
             parent_kind: self.parent_kind,
+            unsafety: self.unsafety,
+            assoc_types: self.assoc_types,
         }
     }
 
@@ -66,6 +76,18 @@ impl ParentBuilder {
     pub fn set_generic_constraints(&mut self, constraints: &[&str]) {
         self.generics.set_generic_constraints(constraints)
     }
+
+    /// Mark the trait as an `unsafe trait`, so that it is implemented with
+    /// an `unsafe impl`
+    pub fn set_unsafe(&mut self) {
+        self.unsafety = true;
+    }
+
+    /// Declare the associated types of the trait by name, which are the only
+    /// ones an impl of it can define
+    pub fn set_assoc_types(&mut self, names: &[&str]) {
+        self.assoc_types = names.iter().copied().map(Ident::new).collect();
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -86,6 +108,8 @@ impl Parent {
                 path: self.path.clone_with_fresh_generics(&param_map),
                 generics,
                 parent_kind: self.parent_kind,
+                unsafety: self.unsafety,
+                assoc_types: self.assoc_types.clone(),
             },
             param_map,
         )
@@ -1,4 +1,7 @@
-use crate::{GenericArgument, GenericArguments, Ident, ParamMap, SynParamMap, Type};
+use crate::ty::PRIMITIVE_TYPES;
+use crate::{
+    GenericArgument, GenericArguments, Ident, Lifetime, ParamMap, SynParamMap, Type, TypeNode,
+};
 use ref_cast::RefCast;
 use syn::parse::{Parse, ParseStream, Result};
 use syn::{parse_str, ReturnType, Token};
@@ -15,8 +18,9 @@ pub(crate) struct SimplePath {
     pub(crate) path: Path,
 }
 
+/// A segment of a path, e.g. `Vec<T>` in `::std::vec::Vec<T>`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct PathSegment {
+pub struct PathSegment {
     pub(crate) ident: Ident,
     pub(crate) args: PathArguments,
 }
@@ -42,6 +46,35 @@ pub(crate) struct ParenthesizedGenericArguments {
     pub(crate) output: Option<Type>,
 }
 
+impl PathSegment {
+    /// The name of the segment, e.g. `Vec` for `Vec<T>`
+    pub fn name(&self) -> String {
+        self.ident.to_string()
+    }
+
+    /// The angle bracketed generic arguments of the segment, e.g. `T` in
+    /// `Vec<T>`. Empty for a segment without them, also for `Fn(T) -> U`.
+    pub fn generic_arguments(&self) -> &[GenericArgument] {
+        match &self.args {
+            PathArguments::AngleBracketed(args) => &args.args.args,
+            _ => &[],
+        }
+    }
+
+    /// Replaces the arguments of the segment with the angle bracketed `args`,
+    /// e.g. to turn `Vec<T>` into `Vec<u8>`. An empty `args` removes the
+    /// angle brackets.
+    pub fn set_generic_arguments(&mut self, args: Vec<GenericArgument>) {
+        self.args = if args.is_empty() {
+            PathArguments::None
+        } else {
+            PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+                args: GenericArguments { args },
+            })
+        };
+    }
+}
+
 impl Path {
     pub(crate) fn root() -> Self {
         Path {
@@ -69,6 +102,136 @@ impl Path {
         path
     }
 
+    /// Appends a segment without generic arguments to the end of the path
+    pub fn push_segment(&mut self, ident: &str) {
+        *self = self.get_simple_path(ident);
+    }
+
+    /// Returns the segments of `self` followed by the segments of `other`.
+    ///
+    /// Like `std::path::Path::join`, joining a global path such as
+    /// `::std::fmt` replaces `self` entirely.
+    pub fn join(&self, other: &Path) -> Self {
+        if other.global {
+            return other.clone();
+        }
+        let mut path = self.clone();
+        path.path.extend(other.path.iter().cloned());
+        path
+    }
+
+    /// Returns the path with a leading `::` if `global` is true, and without
+    /// one otherwise
+    pub fn with_global(mut self, global: bool) -> Self {
+        self.global = global;
+        self
+    }
+
+    /// Returns the path with `segments` inserted before its first segment,
+    /// e.g. prepending `["my_crate"]` to `a::B` gives `my_crate::a::B`. The
+    /// result keeps the leading `::` of `self`, if any.
+    pub fn prepend(&self, segments: &[&str]) -> Self {
+        let mut path = Path {
+            global: self.global,
+            path: Vec::new(),
+        };
+        for segment in segments {
+            path.push_segment(segment);
+        }
+        path.path.extend(self.path.iter().cloned());
+        path
+    }
+
+    pub(crate) fn is_primitive(&self) -> bool {
+        match self.path.as_slice() {
+            [segment] if !self.global && segment.args == PathArguments::None => PRIMITIVE_TYPES
+                .iter()
+                .any(|&primitive| segment.ident == Ident::new(primitive)),
+            _ => false,
+        }
+    }
+
+    /// If the first segment of the path is `root`, returns the path with that
+    /// segment replaced by `replacement`, e.g. `crate::Foo` becomes
+    /// `$crate::Foo`. Otherwise the path is returned unchanged.
+    pub fn replace_root(&self, root: &str, replacement: &str) -> Self {
+        let mut path = self.clone();
+        match path.path.first_mut() {
+            Some(segment) if !path.global && segment.ident == Ident::new(root) => {
+                segment.ident = Ident::new(replacement);
+            }
+            _ => {}
+        }
+        path
+    }
+
+    /// Rewrites a path written relative to its crate or module so that it
+    /// also works from generated code: `crate::a::B` becomes
+    /// `::crate_name::a::B`, and a leading `self::` is dropped. A path
+    /// starting with `super::` is returned unchanged, as where it points
+    /// depends on the module that the generated code ends up in.
+    pub fn resolve_relative(&self, crate_name: &str) -> Self {
+        let mut path = self.clone();
+        if path.global {
+            return path;
+        }
+        while path.path.len() > 1 && path.path[0].ident == Ident::new("self") {
+            path.path.remove(0);
+        }
+        if path
+            .path
+            .first()
+            .is_some_and(|segment| segment.ident == Ident::new("crate"))
+        {
+            path.path.remove(0);
+            return Path::root().get_simple_path(crate_name).join(&path);
+        }
+        path
+    }
+
+    /// The generic arguments of the last segment of the path, e.g. `'a` and
+    /// `T` in `Wrapper<'a, T>`. Empty if the path has no angle bracketed
+    /// arguments.
+    pub fn generic_arguments(&self) -> &[GenericArgument] {
+        self.path
+            .last()
+            .map_or(&[], |segment| segment.generic_arguments())
+    }
+
+    /// The last segment of the path, to be changed in place. `None` only for
+    /// a path without any segments, like the bare `::` that paths are built
+    /// up from. A parsed path always has a last segment.
+    pub fn last_segment_mut(&mut self) -> Option<&mut PathSegment> {
+        self.path.last_mut()
+    }
+
+    /// The number of type arguments of the last segment of the path, e.g. 2
+    /// for `HashMap<K, V>` and 1 for `Wrapper<'a, T>`
+    pub fn type_arg_count(&self) -> usize {
+        self.generic_arguments()
+            .iter()
+            .filter(|arg| arg.as_type().is_some())
+            .count()
+    }
+
+    /// Whether any segment of the path has generic arguments, e.g. true for
+    /// `Vec<T>` and `Fn(T)`, false for `::std::string::String`
+    pub fn has_generics(&self) -> bool {
+        self.path
+            .iter()
+            .any(|segment| !matches!(segment.args, PathArguments::None))
+    }
+
+    /// Returns the path without any generic arguments, e.g. `Vec<T>` becomes
+    /// `Vec`
+    pub fn strip_generics(&self) -> Self {
+        let mut path = self.clone();
+        for segment in &mut path.path {
+            segment.args = PathArguments::None;
+        }
+        path
+    }
+
     pub(crate) fn get_path(&self, segment: &str, param_map: &mut SynParamMap) -> Self {
         let mut path = self.clone();
         path.path.push(Path::syn_to_path_segment(
@@ -128,9 +291,13 @@ impl Path {
                         .into_iter()
                         .map(|input| Type::syn_to_type(input, param_map))
                         .collect(),
+                    // `Fn(T) -> ()` is the same as `Fn(T)`, so an explicit unit
+                    // output is stored the same way as the default one
                     output: match parenthesized.output {
                         ReturnType::Default => None,
-                        ReturnType::Type(_, ty) => Some(Type::syn_to_type(*ty, param_map)),
+                        ReturnType::Type(_, ty) => {
+                            Some(Type::syn_to_type(*ty, param_map)).filter(|ty| *ty != Type::unit())
+                        }
                     },
                 }),
             },
@@ -176,6 +343,66 @@ impl Path {
                 .collect(),
         }
     }
+
+    pub(crate) fn elide_lifetimes(&mut self) {
+        for segment in &mut self.path {
+            match &mut segment.args {
+                PathArguments::None => {}
+                PathArguments::AngleBracketed(args) => {
+                    args.args.elide_lifetimes();
+                    if args.args.args.is_empty() {
+                        segment.args = PathArguments::None;
+                    }
+                }
+                PathArguments::Parenthesized(args) => {
+                    args.inputs.iter_mut().for_each(|ty| ty.0.elide_lifetimes());
+                    if let Some(output) = &mut args.output {
+                        output.0.elide_lifetimes();
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn walk_paths_mut(&mut self, f: &mut dyn FnMut(&mut Path)) {
+        for segment in &mut self.path {
+            match &mut segment.args {
+                PathArguments::None => {}
+                PathArguments::AngleBracketed(args) => args.args.walk_paths_mut(f),
+                PathArguments::Parenthesized(args) => {
+                    args.inputs.iter_mut().for_each(|ty| ty.0.walk_paths_mut(f));
+                    if let Some(output) = &mut args.output {
+                        output.0.walk_paths_mut(f);
+                    }
+                }
+            }
+        }
+        f(self);
+    }
+
+    pub(crate) fn walk_types_mut(&mut self, f: &mut dyn FnMut(&mut TypeNode) -> bool) {
+        for segment in &mut self.path {
+            match &mut segment.args {
+                PathArguments::None => {}
+                PathArguments::AngleBracketed(args) => args.args.walk_types_mut(f),
+                PathArguments::Parenthesized(args) => {
+                    args.types_mut().for_each(|ty| ty.0.walk_types_mut(f))
+                }
+            }
+        }
+    }
+
+    pub(crate) fn walk_lifetimes_mut(&mut self, f: &mut dyn FnMut(&mut Lifetime)) {
+        for segment in &mut self.path {
+            match &mut segment.args {
+                PathArguments::None => {}
+                PathArguments::AngleBracketed(args) => args.args.walk_lifetimes_mut(f),
+                PathArguments::Parenthesized(args) => {
+                    args.types_mut().for_each(|ty| ty.0.walk_lifetimes_mut(f))
+                }
+            }
+        }
+    }
 }
 
 impl AngleBracketedGenericArguments {
@@ -187,6 +414,16 @@ impl AngleBracketedGenericArguments {
 }
 
 impl ParenthesizedGenericArguments {
+    /// The output type, where a default output is the unit type
+    pub(crate) fn output_type(&self) -> Type {
+        self.output.clone().unwrap_or_else(Type::unit)
+    }
+
+    /// The inputs followed by the output, if it is written
+    pub(crate) fn types_mut(&mut self) -> impl Iterator<Item = &mut Type> {
+        self.inputs.iter_mut().chain(self.output.as_mut())
+    }
+
     pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
         ParenthesizedGenericArguments {
             inputs: self
@@ -0,0 +1,245 @@
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Keywords that are followed by a space even before `::`, `(` or `[`
+const KEYWORDS: &[&str] = &[
+    "as", "dyn", "else", "for", "if", "impl", "in", "let", "match", "move", "mut", "return",
+    "unsafe", "where", "while",
+];
+
+/// Formats generated code with one statement per line, indented blocks and a
+/// where clause with one predicate per line. This is not rustfmt, but enough
+/// to read the generated impls. The tokens are printed as they are, without
+/// checking that they parse.
+pub fn pretty_print(tokens: TokenStream) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        line: String::new(),
+        indent: 0,
+        prev: None,
+        in_where: false,
+        angles: Vec::new(),
+        closure_params: false,
+    };
+    printer.print_block(tokens, false);
+    printer.flush();
+    printer.out
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Prev {
+    Ident,
+    Keyword,
+    Lifetime,
+    Literal,
+    Punct(char, Spacing),
+    /// The second `:` of a `::`
+    PathSep,
+    /// The `>` closing the generics of `impl<T>` or `for<'a>`, which may be
+    /// followed by a path starting with `::`
+    BinderEnd,
+    /// The `>` of `->` or `=>`
+    Arrow,
+    /// The `|` opening the params of a closure
+    ClosureStart,
+    /// The `|` closing the params of a closure
+    ClosureEnd,
+    Open,
+    Close,
+}
+
+struct Printer {
+    out: String,
+    line: String,
+    indent: usize,
+    prev: Option<Prev>,
+    in_where: bool,
+    /// For each open `<`, whether it starts the generics of `impl` or `for`
+    angles: Vec<bool>,
+    /// Whether the printer is between the `|`s of a closure
+    closure_params: bool,
+}
+
+impl Printer {
+    /// Prints the contents of a `{}` block, or the top level, where `;` and
+    /// braces end lines. In the body of a `match`, the `,` after an arm
+    /// ends the line too.
+    fn print_block(&mut self, tokens: TokenStream, is_match: bool) {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        let mut saw_match = false;
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                    let body_is_match = std::mem::take(&mut saw_match);
+                    if self.in_where {
+                        self.in_where = false;
+                        self.flush();
+                        self.indent -= 1;
+                    } else {
+                        self.line.push(' ');
+                    }
+                    if group.stream().is_empty() {
+                        self.line.push_str("{}");
+                    } else {
+                        self.line.push('{');
+                        self.flush();
+                        self.indent += 1;
+                        self.print_block(group.stream(), body_is_match);
+                        self.flush();
+                        self.indent -= 1;
+                        self.line.push('}');
+                    }
+                    self.prev = Some(Prev::Close);
+                    match tokens.get(i + 1) {
+                        Some(TokenTree::Punct(punct)) if matches!(punct.as_char(), ';' | ',') => {}
+                        Some(TokenTree::Ident(ident)) if ident == "else" => {}
+                        _ => self.flush(),
+                    }
+                }
+                TokenTree::Ident(ident) if ident == "where" => {
+                    self.flush();
+                    self.line.push_str("where");
+                    self.flush();
+                    self.indent += 1;
+                    self.in_where = true;
+                }
+                TokenTree::Ident(ident) if ident == "match" => {
+                    saw_match = true;
+                    self.print_inline(token);
+                }
+                TokenTree::Punct(punct)
+                    if punct.as_char() == ';'
+                        || (self.in_where || is_match) && punct.as_char() == ',' =>
+                {
+                    saw_match = false;
+                    self.line.push(punct.as_char());
+                    self.flush();
+                }
+                TokenTree::Group(group)
+                    if group.delimiter() == Delimiter::Bracket
+                        && self.prev == Some(Prev::Punct('#', Spacing::Alone)) =>
+                {
+                    // An attribute goes on a line of its own
+                    self.print_inline(token);
+                    self.flush();
+                }
+                _ => self.print_inline(token),
+            }
+        }
+    }
+
+    fn print_inline(&mut self, token: &TokenTree) {
+        let (next, text) = match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{ ", " }"),
+                    Delimiter::None => ("", ""),
+                };
+                self.space_before(Prev::Open);
+                self.line.push_str(open);
+                self.prev = Some(Prev::Open);
+                for token in group.stream() {
+                    self.print_inline(&token);
+                }
+                self.line.push_str(close);
+                self.prev = Some(Prev::Close);
+                return;
+            }
+            TokenTree::Ident(ident) => {
+                let text = ident.to_string();
+                let next = if self.prev == Some(Prev::Punct('\'', Spacing::Joint)) {
+                    Prev::Lifetime
+                } else if KEYWORDS.contains(&text.as_str()) {
+                    Prev::Keyword
+                } else {
+                    Prev::Ident
+                };
+                (next, text)
+            }
+            TokenTree::Punct(punct) => {
+                let next = match (self.prev, punct.as_char()) {
+                    (Some(Prev::Punct(':', Spacing::Joint)), ':') => Prev::PathSep,
+                    (Some(Prev::Punct('-' | '=', Spacing::Joint)), '>') => Prev::Arrow,
+                    (_, '|') if self.closure_params => {
+                        self.closure_params = false;
+                        Prev::ClosureEnd
+                    }
+                    (
+                        None
+                        | Some(
+                            Prev::Open
+                            | Prev::Keyword
+                            | Prev::Punct('=', Spacing::Alone)
+                            | Prev::Punct(',', _),
+                        ),
+                        '|',
+                    ) if punct.spacing() == Spacing::Alone => {
+                        self.closure_params = true;
+                        Prev::ClosureStart
+                    }
+                    (prev, '<') => {
+                        let binder = matches!(prev, Some(Prev::Keyword));
+                        self.angles.push(binder);
+                        Prev::Punct('<', punct.spacing())
+                    }
+                    (_, '>') => match self.angles.pop() {
+                        Some(true) => Prev::BinderEnd,
+                        _ => Prev::Punct('>', punct.spacing()),
+                    },
+                    (_, c) => Prev::Punct(c, punct.spacing()),
+                };
+                (next, punct.as_char().to_string())
+            }
+            TokenTree::Literal(literal) => (Prev::Literal, literal.to_string()),
+        };
+        self.space_before(next);
+        self.line.push_str(&text);
+        self.prev = Some(next);
+    }
+
+    fn space_before(&mut self, next: Prev) {
+        let prev = match self.prev {
+            Some(prev) if !self.line.is_empty() => prev,
+            _ => return,
+        };
+        let no_space = matches!(
+            (prev, next),
+            (Prev::Open, _)
+                | (Prev::Punct(_, Spacing::Joint), _)
+                | (
+                    Prev::Punct('.' | '&' | '!' | '#' | '<', _) | Prev::PathSep,
+                    _
+                )
+                | (
+                    _,
+                    Prev::Punct(',' | ';' | '.' | '?' | '>', _) | Prev::BinderEnd
+                )
+                | (_, Prev::Punct(':', Spacing::Alone) | Prev::PathSep)
+                | (
+                    Prev::Ident | Prev::Punct('>', _) | Prev::Close,
+                    Prev::Punct(':', Spacing::Joint) | Prev::Open
+                )
+                | (Prev::Ident | Prev::Keyword, Prev::Punct('<' | '!', _))
+                | (_, Prev::Close)
+                | (Prev::ClosureStart, _)
+                | (_, Prev::ClosureEnd)
+        );
+        if !no_space {
+            self.line.push(' ');
+        }
+    }
+
+    fn flush(&mut self) {
+        let line = self.line.trim();
+        if !line.is_empty() {
+            for _ in 0..self.indent {
+                self.out.push_str("    ");
+            }
+            self.out.push_str(line);
+            self.out.push('\n');
+        }
+        self.line.clear();
+        self.prev = None;
+    }
+}
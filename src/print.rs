@@ -1,5 +1,9 @@
 use crate::generics::*;
-use crate::{path, Accessor, Lifetime, SimplePath, Type, TypeNode, TypeParam};
+use crate::{
+    path, Accessor, Lifetime, SimplePath, Type, TypeNode, TypeParam, ANONYMOUS_LIFETIME,
+    PARAM_NAMES, STATIC_LIFETIME,
+};
+use fxhash::FxHashMap;
 use proc_macro2::{Punct, Spacing, Span, TokenStream};
 use quote::{quote, ToTokens, TokenStreamExt};
 use ref_cast::RefCast;
@@ -9,6 +13,23 @@ use syn::{Ident, LitInt, Token};
 #[repr(C)]
 pub(crate) struct Print<T>(T);
 
+/// The names to print type params and lifetimes with, instead of generated
+/// names like `__T3` and `'__a1`
+pub(crate) type ParamNames = FxHashMap<GenericParam, String>;
+
+/// Runs `f` with the params printed by the names in `names`
+pub(crate) fn with_param_names<R>(names: ParamNames, f: impl FnOnce() -> R) -> R {
+    let outer = PARAM_NAMES.with(|cell| cell.replace(Some(names)));
+    let result = f();
+    PARAM_NAMES.with(|cell| cell.replace(outer));
+    result
+}
+
+/// The name set by `with_param_names` for `param`, if any
+fn param_name(param: GenericParam) -> Option<String> {
+    PARAM_NAMES.with(|cell| cell.borrow().as_ref()?.get(&param).cloned())
+}
+
 impl ToTokens for Print<Accessor> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         use Accessor::*;
@@ -40,6 +61,16 @@ impl ToTokens for Print<TypeNode> {
                 }
             }
             PrimitiveStr => quote!(str),
+            Never => quote!(!),
+            Verbatim(verbatim) => verbatim.0.clone(),
+            BareFn(args) => {
+                let inputs = args.inputs.iter().map(Print::ref_cast);
+                let output = args.output.as_ref().map(|output| {
+                    let output = Print::ref_cast(output);
+                    quote!(-> #output)
+                });
+                quote!(fn(#(#inputs),*) #output)
+            }
             Reference {
                 is_mut,
                 lifetime,
@@ -56,6 +87,15 @@ impl ToTokens for Print<TypeNode> {
             }
 
             Dereference(inner) => panic!("Type::Dereference::to_tokens"),
+            Slice(elem) => {
+                let elem = Print::ref_cast(&**elem);
+                quote!([#elem])
+            }
+            Array(elem, len) => {
+                let elem = Print::ref_cast(&**elem);
+                let len = &len.0;
+                quote!([#elem; #len])
+            }
             DataStructure(data) => {
                 let name = &data.name;
                 quote!(#name)
@@ -64,10 +104,15 @@ impl ToTokens for Print<TypeNode> {
                 let bounds = bounds.iter().map(Print::ref_cast);
                 quote!((dyn #(#bounds)+*))
             }
-            ImplTrait(bounds) => {
+            ImplTrait(bounds, None) => {
                 let bounds = bounds.iter().map(Print::ref_cast);
                 quote!((impl #(#bounds)+*))
             }
+            ImplTrait(bounds, Some(captures)) => {
+                let bounds = bounds.iter().map(Print::ref_cast);
+                let captures = captures.iter().map(Print::ref_cast);
+                quote!((impl #(#bounds +)* use<#(#captures),*>))
+            }
             Path(path) => {
                 let path = Print::ref_cast(path);
                 quote!(#path)
@@ -76,6 +121,24 @@ impl ToTokens for Print<TypeNode> {
                 let type_param = Print::ref_cast(type_param);
                 quote!(#type_param)
             }
+            Projection {
+                self_ty,
+                trait_path,
+                assoc,
+            } => {
+                let self_ty = Print::ref_cast(&**self_ty);
+                match trait_path {
+                    Some(trait_path) => {
+                        let trait_path = Print::ref_cast(&**trait_path);
+                        quote!(<#self_ty as #trait_path>::#assoc)
+                    }
+                    // The shorthand `T::Assoc` only works for a type param
+                    None => match self_ty.0 {
+                        TypeParam(_) => quote!(#self_ty::#assoc),
+                        _ => quote!(<#self_ty>::#assoc),
+                    },
+                }
+            }
         });
     }
 }
@@ -108,7 +171,9 @@ impl ToTokens for Print<GenericConstraint> {
 
 impl ToTokens for Print<TypeParam> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        Ident::new(&format!("__T{}", (self.0).0), Span::call_site()).to_tokens(tokens);
+        let name =
+            param_name(GenericParam::Type(self.0)).unwrap_or_else(|| format!("__T{}", (self.0).0));
+        Ident::new(&name, Span::call_site()).to_tokens(tokens);
     }
 }
 
@@ -131,7 +196,11 @@ impl ToTokens for Print<TraitBound> {
         } else {
             Some(quote!(for <#(#lifetimes)+*>))
         };
-        tokens.append_all(quote!(#lifetimes #path))
+        let modifier = match self.0.modifier {
+            TraitBoundModifier::None => None,
+            TraitBoundModifier::Maybe => Some(quote!(?)),
+        };
+        tokens.append_all(quote!(#modifier #lifetimes #path))
     }
 }
 
@@ -143,7 +212,12 @@ impl ToTokens for Print<PredicateType> {
         } else {
             Some(quote!(for <#(#lifetimes)+*>))
         };
-        let ty = Print::ref_cast(&self.0.bounded_ty);
+        // A bound on the target type is written as a bound on `Self`, which
+        // stays correct however the impl generics were made concrete
+        let ty = match &self.0.bounded_ty.0 {
+            TypeNode::DataStructure(_) => quote!(Self),
+            _ => Print::ref_cast(&self.0.bounded_ty).to_token_stream(),
+        };
         let bounds = self.0.bounds.iter().map(Print::ref_cast);
         let colon = if self.0.bounds.is_empty() {
             None
@@ -158,10 +232,14 @@ impl ToTokens for Print<Lifetime> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let apostrophe = Punct::new('\'', Spacing::Joint);
         tokens.append(apostrophe);
-        if (self.0).0 == 0 {
+        if self.0 == STATIC_LIFETIME {
             tokens.append(Ident::new("static", Span::call_site()));
+        } else if self.0 == ANONYMOUS_LIFETIME {
+            tokens.append(Ident::new("_", Span::call_site()));
         } else {
-            tokens.append(Ident::new(&format!("__a{}", (self.0).0), Span::call_site()));
+            let name = param_name(GenericParam::Lifetime(self.0))
+                .unwrap_or_else(|| format!("__a{}", (self.0).0));
+            tokens.append(Ident::new(&name, Span::call_site()));
         }
     }
 }
@@ -205,6 +283,13 @@ impl ToTokens for Print<GenericArgument> {
         match &self.0 {
             GenericArgument::Lifetime(lifetime) => Print::ref_cast(lifetime).to_tokens(tokens),
 
+            // A trait object needs no parentheses as a generic argument, so
+            // `Box<dyn Error + Send>` is printed as it is written
+            GenericArgument::Type(Type(TypeNode::TraitObject(bounds))) => {
+                let bounds = bounds.iter().map(Print::ref_cast);
+                tokens.append_all(quote!(dyn #(#bounds)+*))
+            }
+
             GenericArgument::Type(ty) => Print::ref_cast(ty).to_tokens(tokens),
 
             GenericArgument::Binding(binding) => Print::ref_cast(binding).to_tokens(tokens),
@@ -220,35 +305,49 @@ impl ToTokens for Print<GenericArgument> {
 
 impl ToTokens for Print<path::Path> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        use path::PathArguments;
-        let leading = if self.0.global {
-            Some(quote!(::))
-        } else {
-            None
-        };
-        let path = self.0.path.iter().map(|segment| {
-            let ident = &segment.ident;
-            let args = match &segment.args {
-                PathArguments::None => None,
-                PathArguments::AngleBracketed(args) => {
-                    let args = args.args.args.iter().map(Print::ref_cast);
-                    Some(quote!(<#(#args),*>))
-                }
-                PathArguments::Parenthesized(args) => {
-                    let inputs = args.inputs.iter().map(Print::ref_cast);
-                    let output = args.output.as_ref().map(|output| {
-                        let output = Print::ref_cast(output);
-                        quote! {-> output}
-                    });
-                    Some(quote! { (#(#inputs),*) #output})
-                }
-            };
-            quote!(#ident #args)
-        });
-        tokens.append_all(quote!(#leading #(#path)::*));
+        path_to_tokens(&self.0, false, tokens);
     }
 }
 
+/// Prints a path in expression position, where angle bracketed args need a
+/// turbofish: `Vec::<u32>` instead of `Vec<u32>`. Only the segments of the
+/// path itself are affected, since its args are in type position again.
+#[derive(RefCast)]
+#[repr(C)]
+pub(crate) struct Turbofish<T>(T);
+
+impl ToTokens for Turbofish<path::Path> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        path_to_tokens(&self.0, true, tokens);
+    }
+}
+
+fn path_to_tokens(path: &path::Path, turbofish: bool, tokens: &mut TokenStream) {
+    use path::PathArguments;
+    let leading = if path.global { Some(quote!(::)) } else { None };
+    let colons = if turbofish { Some(quote!(::)) } else { None };
+    let path = path.path.iter().map(|segment| {
+        let ident = &segment.ident;
+        let args = match &segment.args {
+            PathArguments::None => None,
+            PathArguments::AngleBracketed(args) => {
+                let args = args.args.args.iter().map(Print::ref_cast);
+                Some(quote!(#colons <#(#args),*>))
+            }
+            PathArguments::Parenthesized(args) => {
+                let inputs = args.inputs.iter().map(Print::ref_cast);
+                let output = args.output.as_ref().map(|output| {
+                    let output = Print::ref_cast(output);
+                    quote! {-> #output}
+                });
+                Some(quote! { (#(#inputs),*) #output})
+            }
+        };
+        quote!(#ident #args)
+    });
+    tokens.append_all(quote!(#leading #(#path)::*));
+}
+
 impl ToTokens for Print<SimplePath> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let leading = if self.0.path.global {
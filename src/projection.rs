@@ -0,0 +1,228 @@
+use crate::{
+    GenericArgument, Generics, Path, PathArguments, Type, TypeNode, TypeParam, PROJECTIONS,
+};
+use std::collections::BTreeMap;
+use syn::parse_str;
+
+/// An associated type projection together with the type it resolves to,
+/// e.g. `<Vec<T> as IntoIterator>::Item` resolves to `T`. The params are the
+/// type params of the rule, which match any type.
+#[derive(Debug, Clone)]
+pub(crate) struct ProjectionRule {
+    params: Vec<TypeParam>,
+    projection: TypeNode,
+    resolved: TypeNode,
+}
+
+impl ProjectionRule {
+    pub(crate) fn new(generic_params: &[&str], projection: &str, resolved: &str) -> Self {
+        let mut generics = Generics::default();
+        generics.set_generic_params(generic_params);
+        let params = generics
+            .params
+            .iter()
+            .filter_map(|param| param.type_param())
+            .collect();
+        let param_map = &mut generics.param_map;
+        let projection = Type::syn_to_type(
+            parse_str(projection).expect("register_projection: Not a type"),
+            param_map,
+        )
+        .0;
+        match projection {
+            TypeNode::Projection { .. } => {}
+            _ => panic!("register_projection: Not an associated type projection"),
+        }
+        let resolved = Type::syn_to_type(
+            parse_str(resolved).expect("register_projection: Not a type"),
+            param_map,
+        )
+        .0;
+
+        ProjectionRule {
+            params,
+            projection,
+            resolved,
+        }
+    }
+
+    fn resolve(&self, node: &TypeNode) -> Option<TypeNode> {
+        let mut bindings = BTreeMap::new();
+        if self.matches(&self.projection, node, &mut bindings) {
+            let mut resolved = self.resolved.clone();
+            resolved.substitute(&bindings);
+            Some(resolved)
+        } else {
+            None
+        }
+    }
+
+    /// Matches `node` against `pattern`, where the params of the rule match
+    /// any type, as long as each param matches the same type everywhere
+    fn matches(
+        &self,
+        pattern: &TypeNode,
+        node: &TypeNode,
+        bindings: &mut BTreeMap<TypeParam, TypeNode>,
+    ) -> bool {
+        use TypeNode::*;
+        match (pattern, node) {
+            (TypeParam(param), node) if self.params.contains(param) => match bindings.get(param) {
+                Some(bound) => bound == node,
+                None => {
+                    bindings.insert(*param, node.clone());
+                    true
+                }
+            },
+            (Tuple(patterns), Tuple(nodes)) => {
+                patterns.len() == nodes.len()
+                    && patterns
+                        .iter()
+                        .zip(nodes)
+                        .all(|(pattern, node)| self.matches(pattern, node, bindings))
+            }
+            (
+                Reference {
+                    is_mut: is_mut1,
+                    inner: inner1,
+                    ..
+                },
+                Reference {
+                    is_mut: is_mut2,
+                    inner: inner2,
+                    ..
+                },
+            ) => is_mut1 == is_mut2 && self.matches(inner1, inner2, bindings),
+            (Path(path1), Path(path2)) => self.matches_path(path1, path2, bindings),
+            (
+                Projection {
+                    self_ty: self_ty1,
+                    trait_path: trait_path1,
+                    assoc: assoc1,
+                },
+                Projection {
+                    self_ty: self_ty2,
+                    trait_path: trait_path2,
+                    assoc: assoc2,
+                },
+            ) => {
+                assoc1 == assoc2
+                    && match (trait_path1, trait_path2) {
+                        (Some(path1), Some(path2)) => self.matches_path(path1, path2, bindings),
+                        _ => false,
+                    }
+                    && self.matches(self_ty1, self_ty2, bindings)
+            }
+            (pattern, node) => pattern == node,
+        }
+    }
+
+    fn matches_path(
+        &self,
+        path1: &Path,
+        path2: &Path,
+        bindings: &mut BTreeMap<TypeParam, TypeNode>,
+    ) -> bool {
+        path1.global == path2.global
+            && path1.path.len() == path2.path.len()
+            && path1
+                .path
+                .iter()
+                .zip(&path2.path)
+                .all(|(segment1, segment2)| {
+                    segment1.ident == segment2.ident
+                        && match (&segment1.args, &segment2.args) {
+                            (
+                                PathArguments::AngleBracketed(args1),
+                                PathArguments::AngleBracketed(args2),
+                            ) => {
+                                args1.args.args.len() == args2.args.args.len()
+                                    && args1.args.args.iter().zip(&args2.args.args).all(
+                                        |(arg1, arg2)| match (arg1, arg2) {
+                                            (
+                                                GenericArgument::Type(ty1),
+                                                GenericArgument::Type(ty2),
+                                            ) => self.matches(&ty1.0, &ty2.0, bindings),
+                                            // Lifetimes do not change what a projection resolves to
+                                            (
+                                                GenericArgument::Lifetime(_),
+                                                GenericArgument::Lifetime(_),
+                                            ) => true,
+                                            (arg1, arg2) => arg1 == arg2,
+                                        },
+                                    )
+                            }
+                            (args1, args2) => args1 == args2,
+                        }
+                })
+    }
+}
+
+impl TypeNode {
+    /// Replaces every projection inside the type that matches a registered
+    /// `ProjectionRule` with the type it resolves to
+    pub(crate) fn resolve_projections(self) -> TypeNode {
+        if PROJECTIONS.with(|rules| rules.borrow().is_empty()) {
+            return self;
+        }
+        self.resolve_projections_inner()
+    }
+
+    fn resolve_projections_inner(self) -> TypeNode {
+        use TypeNode::*;
+        match self {
+            Projection {
+                self_ty,
+                trait_path,
+                assoc,
+            } => {
+                let projection = Projection {
+                    self_ty: Box::new(self_ty.resolve_projections_inner()),
+                    trait_path,
+                    assoc,
+                };
+                let resolved = PROJECTIONS.with(|rules| {
+                    rules
+                        .borrow()
+                        .iter()
+                        .find_map(|rule| rule.resolve(&projection))
+                });
+                match resolved {
+                    Some(resolved) if resolved != projection => {
+                        resolved.resolve_projections_inner()
+                    }
+                    _ => projection,
+                }
+            }
+            Tuple(types) => Tuple(
+                types
+                    .into_iter()
+                    .map(TypeNode::resolve_projections_inner)
+                    .collect(),
+            ),
+            Reference {
+                is_mut,
+                lifetime,
+                inner,
+            } => Reference {
+                is_mut,
+                lifetime,
+                inner: Box::new(inner.resolve_projections_inner()),
+            },
+            Path(mut path) => {
+                for segment in &mut path.path {
+                    if let PathArguments::AngleBracketed(args) = &mut segment.args {
+                        for arg in &mut args.args.args {
+                            if let GenericArgument::Type(ty) = arg {
+                                let node = std::mem::replace(&mut ty.0, Infer);
+                                ty.0 = node.resolve_projections_inner();
+                            }
+                        }
+                    }
+                }
+                Path(path)
+            }
+            node => node,
+        }
+    }
+}
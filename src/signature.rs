@@ -1,8 +1,9 @@
 use crate::{
-    GenericArgument, GenericParam, Generics, GlobalCounter, Lifetime, ParamMap, Path,
-    PathArguments, SynParamMap, Type,
+    AngleBracketedGenericArguments, Binding, GenericArgument, GenericArguments, GenericConstraint,
+    GenericParam, Generics, GlobalCounter, Ident, Lifetime, ParamMap, Path, PathArguments,
+    ReflectError, SynParamMap, Type,
     TypeNode::{self, *},
-    TypeParamBound, LIFETIMES,
+    TypeParamBound, ANONYMOUS_LIFETIME, LIFETIMES,
 };
 use std::default::Default;
 
@@ -12,9 +13,17 @@ pub struct Signature {
     pub(crate) receiver: Receiver,
     pub(crate) inputs: Vec<Type>,
     pub(crate) output: Type,
+    pub(crate) constness: bool,
+    /// Whether this is an `async fn`, in which case `output` is the type of
+    /// the awaited future
+    pub(crate) asyncness: bool,
+    pub(crate) unsafety: bool,
+    /// The lifetimes that `insert_elided_lifetimes` made explicit, which can
+    /// be elided again in the generated signature
+    pub(crate) elided_lifetimes: Vec<Lifetime>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub(crate) enum Receiver {
     NoSelf,
     SelfByValue,
@@ -22,6 +31,10 @@ pub(crate) enum Receiver {
         is_mut: bool,
         lifetime: Option<Lifetime>,
     },
+    /// An arbitrary self type like `self: Box<Self>` or `self: Pin<&mut Self>`.
+    /// `Self` is stored as a plain path, and is replaced by the actual self
+    /// type with `Receiver::self_type`.
+    SelfByWrapper(Type),
 }
 
 pub trait AddInput<'a, T> {
@@ -42,6 +55,37 @@ impl Receiver {
                 is_mut,
                 lifetime: Some(lifetime.unwrap().clone_with_fresh_generics(param_map)),
             },
+            SelfByWrapper(ref ty) => SelfByWrapper(ty.clone_with_fresh_generics(param_map)),
+        }
+    }
+
+    /// The type of the receiver, given the type that `Self` refers to
+    pub(crate) fn self_type(&self, self_ty: TypeNode) -> Option<TypeNode> {
+        use Receiver::*;
+        match self {
+            NoSelf => None,
+            SelfByValue => Some(self_ty),
+            SelfByReference { is_mut, lifetime } => Some(Reference {
+                is_mut: *is_mut,
+                lifetime: *lifetime,
+                inner: Box::new(self_ty),
+            }),
+            SelfByWrapper(ty) => Some(ty.0.replace_self(&self_ty)),
+        }
+    }
+
+    /// Find the type that `Self` refers to, given the type of the value
+    /// passed as the receiver
+    pub(crate) fn find_self(&self, receiver_ty: &TypeNode) -> Option<TypeNode> {
+        use Receiver::*;
+        match self {
+            NoSelf => None,
+            SelfByValue => Some(receiver_ty.clone()),
+            SelfByReference { .. } => match receiver_ty {
+                Reference { inner, .. } => Some((**inner).clone()),
+                _ => None,
+            },
+            SelfByWrapper(ty) => ty.0.find_self(receiver_ty),
         }
     }
 }
@@ -83,13 +127,102 @@ impl Signature {
             receiver: Receiver::NoSelf,
             inputs: Vec::new(),
             output: Type::unit(),
+            constness: false,
+            asyncness: false,
+            unsafety: false,
+            elided_lifetimes: Vec::new(),
+        }
+    }
+
+    /// Replace every `Self` in the inputs, the output and the `where` clause
+    /// of the signature with the type the function is called for, so that a
+    /// method bound like `where Self: AsRef<str>` becomes a bound on it
+    pub(crate) fn replace_self(&mut self, self_ty: &TypeNode) {
+        self.output.0 = self.output.0.replace_self(self_ty);
+        for input in &mut self.inputs {
+            input.0 = input.0.replace_self(self_ty);
+        }
+        for constraint in &mut self.generics.constraints {
+            if let GenericConstraint::Type(predicate) = constraint {
+                predicate.bounded_ty.0 = predicate.bounded_ty.0.replace_self(self_ty);
+                predicate.bounds = replace_self_in_bounds(&predicate.bounds, self_ty);
+            }
+        }
+    }
+
+    /// Checks that no unsized type, like `str`, a slice or a trait object, is
+    /// taken or returned by value, since an unsized type can only be used
+    /// behind a pointer
+    pub(crate) fn check_sized(&self) -> Result<(), ReflectError> {
+        fn check(ty: &Type, generics: &Generics) -> Result<(), ReflectError> {
+            if ty.is_definitely_unsized(generics) {
+                return Err(ReflectError::UnsizedValue { ty: ty.clone() });
+            }
+            match &ty.0 {
+                Tuple(types) => types
+                    .iter()
+                    .try_for_each(|node| check(&Type(node.clone()), generics)),
+                Array(inner, _) => check(&Type((**inner).clone()), generics),
+                _ => Ok(()),
+            }
+        }
+
+        self.inputs
+            .iter()
+            .chain(std::iter::once(&self.output))
+            .try_for_each(|ty| check(ty, &self.generics))
+    }
+
+    /// Mark the function as a `const fn`. The qualifier is left out when the
+    /// function is generated in a trait impl, where it is not allowed.
+    pub fn set_const(&mut self) {
+        self.constness = true;
+    }
+
+    /// Mark the function as an `async fn`
+    pub fn set_async(&mut self) {
+        self.asyncness = true;
+    }
+
+    /// The type of a call to the function, which for an `async fn` is the
+    /// future `impl ::core::future::Future<Output = T>` of its output `T`
+    pub(crate) fn call_output(&self) -> Type {
+        if !self.asyncness {
+            return self.output.clone();
         }
+        let mut future = Path::root()
+            .get_simple_path("core")
+            .get_simple_path("future")
+            .get_simple_path("Future");
+        future.path.last_mut().unwrap().args =
+            PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+                args: GenericArguments {
+                    args: vec![GenericArgument::Binding(Binding {
+                        ident: Ident::new("Output"),
+                        ty: self.output.clone(),
+                    })],
+                },
+            });
+        Type(ImplTrait(vec![TypeParamBound::from_path(future)], None))
+    }
+
+    /// Mark the function as an `unsafe fn`
+    pub fn set_unsafe(&mut self) {
+        self.unsafety = true;
     }
 
     pub fn set_self_by_value(&mut self) {
         self.receiver = Receiver::SelfByValue;
     }
 
+    /// Set an arbitrary self type as receiver, e.g. `self: Box<Self>`.
+    pub fn set_self_by_wrapper<F>(&mut self, wrapper: F)
+    where
+        F: FnOnce(&mut SynParamMap) -> Type,
+    {
+        self.receiver = Receiver::SelfByWrapper(wrapper(&mut self.generics.param_map));
+    }
+
     pub fn set_self_by_reference(&mut self) {
         self.receiver = Receiver::SelfByReference {
             is_mut: false,
@@ -176,8 +309,10 @@ impl Signature {
                 lifetime: option_lifetime,
                 ..
             } => {
-                let lifetime = if let Some(lifetime) = option_lifetime {
-                    *lifetime
+                let lifetime = if let Some(lifetime) =
+                    option_lifetime.filter(|&lifetime| lifetime != ANONYMOUS_LIFETIME)
+                {
+                    lifetime
                 } else {
                     let lifetime = LIFETIMES.count();
                     generics.params.push(GenericParam::Lifetime(lifetime));
@@ -191,13 +326,179 @@ impl Signature {
                     .0
                     .insert_lifetime(lifetime, &mut generics.params);
             }
+            SelfByWrapper(ty) => {
+                // A lifetime in the receiver, e.g. in `Pin<&mut Self>`, is
+                // preferred for the output, just like for `&self`
+                ty.0.insert_new_lifetimes(&mut generics.params, &mut total_lifetimes);
+                let receiver_lifetime = total_lifetimes.first().copied();
+                for ty in &mut self.inputs {
+                    ty.0.insert_new_lifetimes(&mut generics.params, &mut total_lifetimes);
+                }
+                if let Some(lifetime) = receiver_lifetime.or_else(|| {
+                    if total_lifetimes.len() == 1 {
+                        Some(total_lifetimes[0])
+                    } else {
+                        None
+                    }
+                }) {
+                    self.output
+                        .0
+                        .insert_lifetime(lifetime, &mut generics.params);
+                }
+            }
         }
+        // The params added above are exactly the lifetimes that were elided
+        self.elided_lifetimes
+            .extend(generics.params.iter().filter_map(|param| param.lifetime()));
         // Insert the old params back into place
         generics.params.extend(params);
     }
+
+    /// Elide again the lifetimes that were elided in the source, removing
+    /// them from the params and from the references of the signature, so
+    /// that `fn get(&self) -> &str` is emitted as it was written. Lifetimes
+    /// are only elided when the signature has no `where` clause that could
+    /// name them.
+    pub(crate) fn restore_elided_lifetimes(&mut self) {
+        if !self.generics.constraints.is_empty() {
+            return;
+        }
+        let elided = &self.elided_lifetimes;
+        self.generics.params.retain(|param| match param.lifetime() {
+            Some(lifetime) => !elided.contains(&lifetime),
+            None => true,
+        });
+        if let Receiver::SelfByReference { lifetime, .. } = &mut self.receiver {
+            if lifetime.is_some_and(|lifetime| elided.contains(&lifetime)) {
+                *lifetime = None;
+            }
+        }
+        if let Receiver::SelfByWrapper(ty) = &mut self.receiver {
+            ty.0.remove_lifetimes(elided);
+        }
+        for ty in self.inputs.iter_mut().chain(Some(&mut self.output)) {
+            ty.0.remove_lifetimes(elided);
+        }
+    }
 }
 
 impl TypeNode {
+    pub(crate) fn is_self(&self) -> bool {
+        match self {
+            Path(path) => {
+                !path.global
+                    && path.path.len() == 1
+                    && path.path[0].ident == Ident::new("Self")
+                    && path.path[0].args == PathArguments::None
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace every `Self` in a receiver or signature type with `self_ty`,
+    /// however deeply it is nested, e.g. in `Option<Self>` or `&[Self]`
+    pub(crate) fn replace_self(&self, self_ty: &TypeNode) -> TypeNode {
+        match self {
+            node if node.is_self() => self_ty.clone(),
+            Reference {
+                is_mut,
+                lifetime,
+                inner,
+            } => Reference {
+                is_mut: *is_mut,
+                lifetime: *lifetime,
+                inner: Box::new(inner.replace_self(self_ty)),
+            },
+            Dereference(inner) => Dereference(Box::new(inner.replace_self(self_ty))),
+            Tuple(types) => Tuple(types.iter().map(|ty| ty.replace_self(self_ty)).collect()),
+            Slice(elem) => Slice(Box::new(elem.replace_self(self_ty))),
+            Array(elem, len) => Array(Box::new(elem.replace_self(self_ty)), len.clone()),
+            BareFn(args) => {
+                let mut args = args.clone();
+                args.types_mut()
+                    .for_each(|ty| ty.0 = ty.0.replace_self(self_ty));
+                BareFn(args)
+            }
+            TraitObject(bounds) => TraitObject(replace_self_in_bounds(bounds, self_ty)),
+            ImplTrait(bounds, captures) => {
+                ImplTrait(replace_self_in_bounds(bounds, self_ty), captures.clone())
+            }
+            Path(path) => Path(path.replace_self(self_ty)),
+            Projection {
+                self_ty: projected,
+                trait_path,
+                assoc,
+            } => Projection {
+                self_ty: Box::new(projected.replace_self(self_ty)),
+                trait_path: trait_path
+                    .as_ref()
+                    .map(|path| Box::new(path.replace_self(self_ty))),
+                assoc: assoc.clone(),
+            },
+            node => node.clone(),
+        }
+    }
+
+    /// Replace a `Self::Name` type with the type an impl defines for the
+    /// associated type `Name`
+    pub(crate) fn replace_assoc_type(&self, assoc_types: &[(Ident, Type)]) -> TypeNode {
+        if let Path(path) = self {
+            if !path.global
+                && path.path.len() == 2
+                && path.path[0].ident == Ident::new("Self")
+                && path
+                    .path
+                    .iter()
+                    .all(|segment| segment.args == PathArguments::None)
+            {
+                if let Some((_, ty)) = assoc_types
+                    .iter()
+                    .find(|(name, _)| *name == path.path[1].ident)
+                {
+                    return ty.0.clone();
+                }
+            }
+        }
+        self.clone()
+    }
+
+    /// Walk a receiver type and the type of an actual value in parallel, and
+    /// return the part of the value type at the position of `Self`
+    fn find_self(&self, receiver_ty: &TypeNode) -> Option<TypeNode> {
+        match (self, receiver_ty) {
+            (node, receiver_ty) if node.is_self() => Some(receiver_ty.clone()),
+            (Reference { inner: inner1, .. }, Reference { inner: inner2, .. }) => {
+                inner1.find_self(inner2)
+            }
+            (Tuple(types1), Tuple(types2)) => types1
+                .iter()
+                .zip(types2)
+                .find_map(|(ty1, ty2)| ty1.find_self(ty2)),
+            (Path(path1), Path(path2)) => {
+                let args1 = &path1.path.last()?.args;
+                let args2 = &path2.path.last()?.args;
+                match (args1, args2) {
+                    (
+                        PathArguments::AngleBracketed(args1),
+                        PathArguments::AngleBracketed(args2),
+                    ) => args1
+                        .args
+                        .args
+                        .iter()
+                        .zip(&args2.args.args)
+                        .find_map(|args| match args {
+                            (GenericArgument::Type(ty1), GenericArgument::Type(ty2)) => {
+                                ty1.0.find_self(&ty2.0)
+                            }
+                            _ => None,
+                        }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
     fn insert_new_lifetimes(
         &mut self,
         params: &mut Vec<GenericParam>,
@@ -207,7 +508,7 @@ impl TypeNode {
             Reference {
                 inner, lifetime, ..
             } => {
-                if lifetime.is_none() {
+                if lifetime.is_none() || *lifetime == Some(ANONYMOUS_LIFETIME) {
                     let new_lifetime = LIFETIMES.count();
                     params.push(GenericParam::Lifetime(new_lifetime));
                     *lifetime = Some(new_lifetime)
@@ -222,7 +523,9 @@ impl TypeNode {
                     ty.insert_new_lifetimes(params, total_lifetimes);
                 }
             }
-            Dereference(node) => node.insert_new_lifetimes(params, total_lifetimes),
+            Dereference(node) | Slice(node) | Array(node, _) => {
+                node.insert_new_lifetimes(params, total_lifetimes)
+            }
             TraitObject(bounds) => {
                 for bound in bounds.iter_mut() {
                     if let TypeParamBound::Trait(bound) = bound {
@@ -230,7 +533,7 @@ impl TypeNode {
                     }
                 }
             }
-            ImplTrait(bounds) => {
+            ImplTrait(bounds, _) => {
                 for bound in bounds.iter_mut() {
                     if let TypeParamBound::Trait(bound) = bound {
                         bound.path.insert_new_lifetimes(params, total_lifetimes);
@@ -247,7 +550,7 @@ impl TypeNode {
             Reference {
                 inner, lifetime, ..
             } => {
-                if lifetime.is_none() {
+                if lifetime.is_none() || *lifetime == Some(ANONYMOUS_LIFETIME) {
                     *lifetime = Some(new_lifetime);
                 };
                 inner.insert_lifetime(new_lifetime, params);
@@ -257,7 +560,9 @@ impl TypeNode {
                     ty.insert_lifetime(new_lifetime, params);
                 }
             }
-            Dereference(node) => node.insert_lifetime(new_lifetime, params),
+            Dereference(node) | Slice(node) | Array(node, _) => {
+                node.insert_lifetime(new_lifetime, params)
+            }
             TraitObject(bounds) => {
                 for bound in bounds.iter_mut() {
                     if let TypeParamBound::Trait(bound) = bound {
@@ -269,6 +574,35 @@ impl TypeNode {
             _ => {}
         }
     }
+
+    /// Clear the lifetimes of the references that are one of `lifetimes`
+    fn remove_lifetimes(&mut self, lifetimes: &[Lifetime]) {
+        match self {
+            Reference {
+                inner, lifetime, ..
+            } => {
+                if lifetime.is_some_and(|lifetime| lifetimes.contains(&lifetime)) {
+                    *lifetime = None;
+                }
+                inner.remove_lifetimes(lifetimes);
+            }
+            Tuple(types) => {
+                for ty in types.iter_mut() {
+                    ty.remove_lifetimes(lifetimes);
+                }
+            }
+            Dereference(node) | Slice(node) | Array(node, _) => node.remove_lifetimes(lifetimes),
+            TraitObject(bounds) | ImplTrait(bounds, _) => {
+                for bound in bounds.iter_mut() {
+                    if let TypeParamBound::Trait(bound) = bound {
+                        bound.path.remove_lifetimes(lifetimes);
+                    }
+                }
+            }
+            Path(path) => path.remove_lifetimes(lifetimes),
+            _ => {}
+        }
+    }
 }
 
 impl Path {
@@ -287,7 +621,17 @@ impl Path {
                                 ty.0.insert_new_lifetimes(params, total_lifetimes)
                             }
 
-                            GenericArgument::Lifetime(lifetime) => total_lifetimes.push(*lifetime),
+                            GenericArgument::Lifetime(lifetime) => {
+                                if *lifetime == ANONYMOUS_LIFETIME {
+                                    *lifetime = LIFETIMES.count();
+                                    params.push(GenericParam::Lifetime(*lifetime));
+                                }
+                                total_lifetimes.push(*lifetime)
+                            }
+
+                            GenericArgument::Binding(binding) => {
+                                binding.ty.0.insert_new_lifetimes(params, total_lifetimes)
+                            }
 
                             _ => unimplemented!("Path::insert_elided_lifetimes: GenericArgument"),
                         }
@@ -306,8 +650,12 @@ impl Path {
                 PathArguments::None => {}
                 PathArguments::AngleBracketed(args) => {
                     for arg in &mut args.args.args {
-                        if let GenericArgument::Type(ty) = arg {
-                            ty.0.insert_lifetime(lifetime, params)
+                        match arg {
+                            GenericArgument::Type(ty) => ty.0.insert_lifetime(lifetime, params),
+                            GenericArgument::Lifetime(arg) if *arg == ANONYMOUS_LIFETIME => {
+                                *arg = lifetime
+                            }
+                            _ => {}
                         }
                     }
                 }
@@ -317,4 +665,78 @@ impl Path {
             }
         }
     }
+
+    fn remove_lifetimes(&mut self, lifetimes: &[Lifetime]) {
+        for segment in &mut self.path {
+            match &mut segment.args {
+                PathArguments::None => {}
+                PathArguments::AngleBracketed(args) => {
+                    for arg in &mut args.args.args {
+                        match arg {
+                            GenericArgument::Type(ty) => ty.0.remove_lifetimes(lifetimes),
+                            // A lifetime arg can not be left out, so it is
+                            // elided with `'_` instead
+                            GenericArgument::Lifetime(arg) if lifetimes.contains(arg) => {
+                                *arg = ANONYMOUS_LIFETIME
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                PathArguments::Parenthesized(args) => {
+                    args.types_mut()
+                        .for_each(|ty| ty.0.remove_lifetimes(lifetimes));
+                }
+            }
+        }
+    }
+}
+
+impl Path {
+    /// Replace every `Self` in the generic arguments of the path
+    fn replace_self(&self, self_ty: &TypeNode) -> Path {
+        let mut path = self.clone();
+        for segment in &mut path.path {
+            match &mut segment.args {
+                PathArguments::None => {}
+                PathArguments::AngleBracketed(args) => {
+                    for arg in &mut args.args.args {
+                        match arg {
+                            GenericArgument::Type(ty) => ty.0 = ty.0.replace_self(self_ty),
+                            GenericArgument::Binding(binding) => {
+                                binding.ty.0 = binding.ty.0.replace_self(self_ty)
+                            }
+                            GenericArgument::Constraint(constraint) => {
+                                constraint.bounds =
+                                    replace_self_in_bounds(&constraint.bounds, self_ty)
+                            }
+                            GenericArgument::Lifetime(_) | GenericArgument::Const(_) => {}
+                        }
+                    }
+                }
+                PathArguments::Parenthesized(args) => {
+                    for input in &mut args.inputs {
+                        input.0 = input.0.replace_self(self_ty);
+                    }
+                    if let Some(output) = &mut args.output {
+                        output.0 = output.0.replace_self(self_ty);
+                    }
+                }
+            }
+        }
+        path
+    }
+}
+
+fn replace_self_in_bounds(bounds: &[TypeParamBound], self_ty: &TypeNode) -> Vec<TypeParamBound> {
+    bounds
+        .iter()
+        .map(|bound| {
+            let mut bound = bound.clone();
+            if let TypeParamBound::Trait(bound) = &mut bound {
+                bound.path = bound.path.replace_self(self_ty);
+            }
+            bound
+        })
+        .collect()
 }
@@ -1,15 +1,17 @@
+use crate::ty::PRIMITIVE_TYPES;
 use crate::{
     AngleBracketedGenericArguments, CompleteFunction, CompleteImpl, Function, GenericArgument,
-    GenericArguments, GenericConstraint, GenericParam, GlobalBorrow, Lifetime, LifetimeDef, Parent,
-    ParentKind, Path, PathArguments, PredicateType, Push, Receiver, TraitBound, Type,
-    TypeEqualitySetRef, TypeNode, TypeParamBound, WipFunction, WipImpl, INVOKES, STATIC_LIFETIME,
-    VALUES,
+    GenericArguments, GenericConstraint, GenericParam, GlobalBorrow, Ident, Lifetime, LifetimeDef,
+    Parent, ParentKind, Path, PathArguments, PredicateType, Push, Receiver, ReflectError,
+    SynParamMap, Type, TypeEqualitySetRef, TypeNode, TypeParamBound, ValueNode, ValueRef,
+    WipFunction, WipImpl, ANONYMOUS_LIFETIME, INFERENCE_LIMITS, INVOKES, STATIC_LIFETIME, VALUES,
 };
 // FxHasher is used because it is a faster hashing algorithm than the
 // default one, but most importantly because it has a hasher with a default
 // seed, which is useful for testing purposes, and consistent output between
 // compiles.
 use fxhash::{FxHashMap, FxHashSet};
+use std::cell::Cell;
 use std::cmp::Ordering;
 use std::collections::{btree_map, BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::identity;
@@ -17,7 +19,30 @@ use std::hash::Hash;
 use std::iter::Extend;
 use std::ops::{Index, IndexMut};
 use std::rc::Rc;
+use syn::parse_str;
+
+/// Bounds on how much work the trait inference may do, so that huge or
+/// deeply nested types from the macro input give an error instead of
+/// overflowing the stack. Set with `Execution::set_inference_limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InferenceLimits {
+    /// How deeply making a type most concrete may recurse through its inner
+    /// types
+    pub max_depth: usize,
+    /// How many distinct types the type equality sets may hold in total
+    pub max_set_size: usize,
+}
+
+impl Default for InferenceLimits {
+    fn default() -> Self {
+        InferenceLimits {
+            max_depth: 64,
+            max_set_size: 1 << 16,
+        }
+    }
+}
 
+#[derive(Clone)]
 pub(crate) struct EqualitySet<T> {
     pub(crate) set: FxHashSet<T>,
 }
@@ -32,12 +57,33 @@ pub(crate) struct EqualitySet<T> {
 /// equal
 pub(crate) type TypeEqualitySet = EqualitySet<TypeNode>;
 
+/// The facts gathered from the functions of one or more impls, that the
+/// trait bounds of those impls are inferred from
+#[derive(Clone)]
+pub(crate) struct InferenceSession {
+    constraints: ConstraintSet,
+    type_equality_sets: TypeEqualitySets,
+    subtypes: LifetimeSubtypeMap,
+}
+
 /// A set of constraints used in the where clause in the final impl
-#[derive(Debug)]
-pub(crate) struct ConstraintSet {
+#[derive(Debug, Clone)]
+pub struct ConstraintSet {
     pub(crate) set: FxHashSet<GenericConstraint>,
+    /// The constraints written in the where clause of the data structure or
+    /// the trait. These are kept even when they look irrelevant, as long as
+    /// they still bound one of the params of the impl.
+    declared: FxHashSet<GenericConstraint>,
 }
 
+/// Traits that are known to be implemented for some concrete types, so that
+/// bounds such as `u32: Copy` can be left out of the where clause. It is
+/// built once per execution and shared by all of its impls.
+pub struct TraitImplTable {
+    impls: FxHashSet<(TypeNode, Path)>,
+}
+
+#[derive(Clone)]
 pub(crate) struct EqualitySets<SetRef, T> {
     pub(crate) set_map: FxHashMap<T, SetRef>,
     pub(crate) sets: Vec<EqualitySet<T>>,
@@ -56,9 +102,17 @@ pub(crate) type TypeEqualitySets = EqualitySets<TypeEqualitySetRef, TypeNode>;
 /// the types: { `T`, `String`, .. }. The most concrete type for the set
 /// containing `Option<T>` may actually be `Option<String>`, even though
 /// `Option<String>` was not contained in that set.
+///
+/// The impls of a batch share one `ConcreteMapAndSets`, so a set that is made
+/// concrete for one impl is looked up, not recomputed, for the others, unless
+/// `ImplBatch::set_per_impl_memo` is called.
 pub(crate) struct ConcreteMapAndSets {
     most_concrete_type_map: BTreeMap<TypeEqualitySetRef, TypeNode>,
     type_equality_sets: TypeEqualitySets,
+    depth: usize,
+    max_depth: usize,
+    /// The first type that could not be made concrete within `max_depth`
+    limit_exceeded: Option<TypeNode>,
 }
 
 pub(crate) struct OriginalGenercs {
@@ -76,6 +130,7 @@ pub(crate) struct TraitInferenceResult {
 }
 
 /// A mapping between a lifetime and it's subtypes
+#[derive(Clone)]
 pub(crate) struct LifetimeSubtypeMap {
     subtypes: BTreeSet<(Lifetime, Lifetime)>,
 }
@@ -307,12 +362,170 @@ impl TransitiveClosure {
             *lifetime = most_concrete;
         };
     }
+
+    /// Unifies a lifetime that is not relevant for the impl with the
+    /// shortest relevant lifetime that outlives it, if there is one.
+    fn unify_with_relevant(
+        &mut self,
+        lifetime: Lifetime,
+        relevant_generic_params: &BTreeSet<GenericParam>,
+    ) {
+        let mut concrete = lifetime;
+        self.make_most_concrete(&mut concrete);
+        if concrete.is_relevant_for_constraint(relevant_generic_params) {
+            return;
+        }
+        let lifetime_index = match self.lifetime_index_mapping.get(&concrete) {
+            Some(&index) => index,
+            None => return,
+        };
+
+        let candidates: Vec<_> = (0..self.transitive_closure.size)
+            .filter(|&i| {
+                self.transitive_closure[(i, lifetime_index)]
+                    && self.index_lifetime_mapping[&i]
+                        .is_relevant_for_constraint(relevant_generic_params)
+            })
+            .collect();
+        let shortest = candidates.iter().copied().find(|&i| {
+            candidates.iter().all(|&j| {
+                i == j || !self.transitive_closure[(i, j)] || self.transitive_closure[(j, i)]
+            })
+        });
+
+        if let Some(shortest) = shortest {
+            let shortest = self.index_lifetime_mapping[&shortest];
+            let unified: Vec<_> = self
+                .most_concrete_lifetime_map
+                .iter()
+                .filter(|(_, &most_concrete)| most_concrete == concrete)
+                .map(|(&lifetime, _)| lifetime)
+                .chain(vec![lifetime, concrete])
+                .collect();
+            unified.into_iter().for_each(|lifetime| {
+                self.most_concrete_lifetime_map.insert(lifetime, shortest);
+            });
+        }
+    }
+}
+
+impl InferenceSession {
+    fn new() -> Self {
+        InferenceSession {
+            constraints: ConstraintSet::new(),
+            type_equality_sets: TypeEqualitySets::new(),
+            subtypes: LifetimeSubtypeMap::new(),
+        }
+    }
+
+    fn add_function(
+        &mut self,
+        function: &WipFunction,
+        assoc_types: &[(Ident, Type)],
+    ) -> Result<(), ReflectError> {
+        function.compute_trait_bounds(
+            &mut self.constraints,
+            &mut self.type_equality_sets,
+            &mut self.subtypes,
+            assoc_types,
+        )
+    }
+}
+
+/// Unifies types one pair at a time, for code that tries out a candidate
+/// type before committing to it. The state can be saved with `snapshot`
+/// and restored with `rollback`, which undoes every unification since.
+pub struct Unifier {
+    session: InferenceSession,
+}
+
+/// The state of a `Unifier` at the time of `Unifier::snapshot`
+pub struct Snapshot {
+    session: InferenceSession,
+}
+
+impl Unifier {
+    pub fn new() -> Self {
+        Unifier {
+            session: InferenceSession::new(),
+        }
+    }
+
+    /// Considers the two types equal from now on. Fails with a type mismatch
+    /// if they can not be the same type, in which case the unifier may be
+    /// left partially updated, so take a `snapshot` first to back out.
+    pub fn unify(&mut self, ty1: &Type, ty2: &Type) -> Result<(), ReflectError> {
+        let session = &mut self.session;
+        session.type_equality_sets.insert_types_as_equal(
+            ty1.0.clone(),
+            ty2.0.clone(),
+            &mut session.constraints,
+            &mut session.subtypes,
+        )
+    }
+
+    /// The most concrete type that `ty` is equal to, e.g. `Vec<u32>` for
+    /// `Vec<T>` after unifying `T` with `u32`
+    pub fn resolve(&self, ty: &Type) -> Result<Type, ReflectError> {
+        let session = self.session.clone();
+        let mut transitive_closure = session.subtypes.transitive_closure();
+        let mut concrete_maps_and_sets = ConcreteMapAndSets::new(session.type_equality_sets);
+        let mut node = ty.0.clone();
+        node.make_most_concrete(&mut concrete_maps_and_sets, &mut transitive_closure);
+        concrete_maps_and_sets.check_depth()?;
+        Ok(Type(node))
+    }
+
+    /// Saves the current state, to go back to with `rollback`
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Restores the state saved by `snapshot`, forgetting the unifications
+    /// made after it
+    pub fn rollback(&mut self, snapshot: Snapshot) {
+        self.session = snapshot.session;
+    }
+}
+
+impl Default for Unifier {
+    fn default() -> Self {
+        Unifier::new()
+    }
+}
+
+impl Type {
+    /// Returns the most concrete type that all of `types` can be equal to,
+    /// e.g. `Wrapper<T>` and `Wrapper<String>` give `Wrapper<String>`. Fails
+    /// with a type mismatch if two of the types can not be the same type, and
+    /// with `ReflectError::NoTypes` if `types` is empty.
+    pub fn common_supertype(types: &[Type]) -> Result<Type, ReflectError> {
+        if types.is_empty() {
+            return Err(ReflectError::NoTypes);
+        }
+        // The never type coerces to the others, so it only decides the
+        // supertype if all the types are never
+        let mut rest = types.iter().filter(|ty| !ty.is_never());
+        let first = match rest.next() {
+            Some(first) => first,
+            None => return Ok(Type::never()),
+        };
+
+        let mut unifier = Unifier::new();
+        for ty in rest {
+            unifier.unify(first, ty)?;
+        }
+        unifier.resolve(first)
+    }
 }
 
 impl ConstraintSet {
     fn new() -> Self {
         ConstraintSet {
             set: HashSet::default(),
+            declared: HashSet::default(),
         }
     }
 
@@ -324,19 +537,101 @@ impl ConstraintSet {
         self.set.contains(constraint)
     }
 
+    /// Adds the constraints from `other` that are not already implied by
+    /// this set. Predicates on the same type are compared by their bounds
+    /// regardless of order, and a predicate that makes existing predicates
+    /// redundant replaces them.
+    pub(crate) fn merge(&mut self, other: ConstraintSet) {
+        other
+            .set
+            .into_iter()
+            .for_each(|constraint| self.insert_canonical(constraint));
+        self.declared.extend(other.declared);
+    }
+
+    /// Like `merge`, but the constraints are marked as declared by the user
+    fn merge_declared(&mut self, other: ConstraintSet) {
+        self.declared.extend(other.set.iter().cloned());
+        self.merge(other);
+    }
+
+    fn insert_canonical(&mut self, constraint: GenericConstraint) {
+        if let GenericConstraint::Type(pred_ty) = &constraint {
+            let is_implied = self.set.iter().any(|existing| match existing {
+                GenericConstraint::Type(existing) => existing.implies(pred_ty),
+                GenericConstraint::Lifetime(_) => false,
+            });
+            if is_implied {
+                return;
+            }
+            self.set.retain(|existing| match existing {
+                GenericConstraint::Type(existing) => !pred_ty.implies(existing),
+                GenericConstraint::Lifetime(_) => true,
+            });
+        }
+        self.set.insert(constraint);
+    }
+
+    /// Adds a `subtype: supertype` constraint for every pair of distinct
+    /// lifetimes in the transitive closure. A constraint that follows from a
+    /// chain of declared ones is left out, so the declared bounds are reduced
+    /// transitively and `where 'a: 'b, 'b: 'c, 'c: 'd` is emitted as written,
+    /// without an extra `'a: 'c`, `'b: 'd` or `'a: 'd`.
     fn add_subtypes(&mut self, transitive_closure: &mut TransitiveClosure) {
-        let lifetime_index_mapping = &mut transitive_closure.index_lifetime_mapping;
-        let transitive_closure = &mut transitive_closure.transitive_closure;
-        for subtype in 0..transitive_closure.size {
-            for supertype in 0..transitive_closure.size {
+        let declared: HashSet<(Lifetime, Lifetime)> = self
+            .declared
+            .iter()
+            .filter_map(|constraint| match constraint {
+                GenericConstraint::Lifetime(lifetime_def) => Some(lifetime_def),
+                GenericConstraint::Type(_) => None,
+            })
+            .flat_map(|lifetime_def| {
+                lifetime_def
+                    .bounds
+                    .iter()
+                    .map(move |&bound| (lifetime_def.lifetime, bound))
+            })
+            .collect();
+        let lifetime_index_mapping = &transitive_closure.index_lifetime_mapping;
+        let transitive_closure = &transitive_closure.transitive_closure;
+        let size = transitive_closure.size;
+
+        // The lifetimes that are reachable through declared bounds only
+        let mut declared_reach = vec![vec![false; size]; size];
+        for (subtype, row) in declared_reach.iter_mut().enumerate() {
+            for (supertype, reachable) in row.iter_mut().enumerate() {
+                *reachable = declared.contains(&(
+                    lifetime_index_mapping[&subtype],
+                    lifetime_index_mapping[&supertype],
+                ));
+            }
+        }
+        for middle in 0..size {
+            for subtype in 0..size {
+                for supertype in 0..size {
+                    if declared_reach[subtype][middle] && declared_reach[middle][supertype] {
+                        declared_reach[subtype][supertype] = true;
+                    }
+                }
+            }
+        }
+
+        for subtype in 0..size {
+            for supertype in 0..size {
                 // Add the subtype: supertype constraint if subtype is a
                 // subtype of supertype, and subtype is not equal supertype
                 if transitive_closure[(subtype, supertype)]
                     && transitive_closure[(subtype, supertype)]
                         != transitive_closure[(supertype, subtype)]
+                    && !(0..size).any(|middle| {
+                        middle != subtype
+                            && middle != supertype
+                            && declared_reach[subtype][middle]
+                            && declared_reach[middle][supertype]
+                    })
                 {
-                    let subtype = *lifetime_index_mapping.get(&subtype).unwrap();
-                    let supertype = *lifetime_index_mapping.get(&supertype).unwrap();
+                    let subtype = lifetime_index_mapping[&subtype];
+                    let supertype = lifetime_index_mapping[&supertype];
                     self.set.insert(GenericConstraint::Lifetime(LifetimeDef {
                         lifetime: subtype,
                         bounds: vec![supertype],
@@ -352,17 +647,33 @@ impl ConstraintSet {
         concrete_maps_and_sets: &mut ConcreteMapAndSets,
         transitive_closure: &mut TransitiveClosure,
     ) -> Self {
+        // Lifetime arguments of trait bounds that are only known to be
+        // outlived by a relevant lifetime are unified with that lifetime
+        // first, so that every constraint agrees on the chosen lifetime
+        self.set.iter().for_each(|constraint| {
+            if let GenericConstraint::Type(pred_ty) = constraint {
+                pred_ty.unify_bound_lifetimes(relevant_generic_params, transitive_closure)
+            }
+        });
+
+        let declared = self.declared;
         ConstraintSet {
             set: self
                 .set
                 .into_iter()
                 .map(|mut constraint| {
+                    let is_declared = declared.contains(&constraint);
                     let is_relevant = constraint.make_relevant(
                         concrete_maps_and_sets,
                         relevant_generic_params,
                         transitive_closure,
                     );
-                    if is_relevant {
+                    // Only inferred constraints are pruned. A declared one is
+                    // only dropped once it no longer bounds any param, like
+                    // `T: Clone` where `T` is inferred to be `String`.
+                    if is_relevant
+                        || is_declared && constraint.bounds_param(relevant_generic_params)
+                    {
                         Some(constraint)
                     } else {
                         None
@@ -370,8 +681,75 @@ impl ConstraintSet {
                 })
                 .filter_map(identity)
                 .collect(),
+            declared: HashSet::default(),
         }
     }
+
+    /// Drops the bounds that `known` says always hold, together with the
+    /// predicates that are left without any bounds
+    pub fn eliminate_trivial(&mut self, known: &TraitImplTable) {
+        self.set = std::mem::take(&mut self.set)
+            .into_iter()
+            .filter_map(|constraint| match constraint {
+                GenericConstraint::Type(mut pred_ty) => {
+                    let PredicateType {
+                        bounded_ty, bounds, ..
+                    } = &mut pred_ty;
+                    bounds.retain(|bound| !known.is_satisfied_by(&bounded_ty.0, bound));
+                    if pred_ty.bounds.is_empty() {
+                        None
+                    } else {
+                        Some(GenericConstraint::Type(pred_ty))
+                    }
+                }
+                GenericConstraint::Lifetime(lifetime) => {
+                    Some(GenericConstraint::Lifetime(lifetime))
+                }
+            })
+            .collect();
+    }
+}
+
+impl TraitImplTable {
+    /// Records that the trait at `trait_path` is implemented for `ty`, e.g.
+    /// `insert("u32", "::core::marker::Copy")`
+    pub fn insert(&mut self, ty: &str, trait_path: &str) {
+        let mut param_map = SynParamMap::new();
+        let ty = Type::syn_to_type(
+            parse_str(ty).expect("TraitImplTable::insert: Not a Type"),
+            &mut param_map,
+        );
+        let trait_path = Path::path_from_str(trait_path, &mut param_map);
+        self.impls.insert((ty.0, trait_path));
+    }
+
+    /// Whether `bound` always holds for the concrete type `ty`
+    fn is_satisfied_by(&self, ty: &TypeNode, bound: &TypeParamBound) -> bool {
+        match bound {
+            TypeParamBound::Trait(bound) => {
+                bound.lifetimes.is_empty() && self.impls.contains(&(ty.clone(), bound.path.clone()))
+            }
+            TypeParamBound::Lifetime(_) => false,
+        }
+    }
+}
+
+impl Default for TraitImplTable {
+    /// The primitive types other than `str` are `Sized` and `Copy`
+    fn default() -> Self {
+        let mut table = TraitImplTable {
+            impls: HashSet::default(),
+        };
+        let traits = ["Sized", "Copy"];
+        for primitive in PRIMITIVE_TYPES.iter().filter(|&&ty| ty != "str") {
+            for trait_name in &traits {
+                table.insert(primitive, trait_name);
+                table.insert(primitive, &format!("::std::marker::{}", trait_name));
+                table.insert(primitive, &format!("::core::marker::{}", trait_name));
+            }
+        }
+        table
+    }
 }
 
 impl<T> EqualitySet<T>
@@ -393,6 +771,31 @@ where
     }
 }
 
+impl ConcreteMapAndSets {
+    fn new(type_equality_sets: TypeEqualitySets) -> Self {
+        ConcreteMapAndSets {
+            most_concrete_type_map: BTreeMap::new(),
+            type_equality_sets,
+            depth: 0,
+            max_depth: INFERENCE_LIMITS.with(Cell::get).max_depth,
+            limit_exceeded: None,
+        }
+    }
+
+    /// Forget the types made most concrete so far, keeping the equality sets
+    fn clear_memo(&mut self) {
+        self.most_concrete_type_map.clear();
+    }
+
+    /// Fails if some type was too deeply nested to be made concrete
+    fn check_depth(&self) -> Result<(), ReflectError> {
+        match &self.limit_exceeded {
+            Some(node) => Err(inference_limit_exceeded(node.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
 impl TypeEqualitySetRef {
     /// The most concrete type is what the inferred type for a value must be.
     /// What is meant by making something more concrete, is essentially making
@@ -413,6 +816,13 @@ impl TypeEqualitySetRef {
         let most_concrete = concrete_maps_and_sets.most_concrete_type_map.get(&self);
         match most_concrete {
             Some(node) => node.clone(),
+            None if concrete_maps_and_sets.depth >= concrete_maps_and_sets.max_depth => {
+                if concrete_maps_and_sets.limit_exceeded.is_none() {
+                    let set = &concrete_maps_and_sets.type_equality_sets.sets[self.0].set;
+                    concrete_maps_and_sets.limit_exceeded = set.iter().next().cloned();
+                }
+                TypeNode::Infer
+            }
             None => {
                 // Adding the Infer type as a temporary value is done for safety in case
                 // of self referential type constraints. Say we have deduced that
@@ -430,6 +840,7 @@ impl TypeEqualitySetRef {
                 // Temprorarily take the set out of the equlity set to avoid borrowing issues
                 let set =
                     std::mem::take(&mut concrete_maps_and_sets.type_equality_sets.sets[self.0].set);
+                concrete_maps_and_sets.depth += 1;
                 let mut iterator = set.iter().peekable();
                 let mut first = iterator.next().unwrap().clone();
                 let most_concrete = if iterator.peek().is_none() {
@@ -445,6 +856,7 @@ impl TypeEqualitySetRef {
                         )
                     })
                 };
+                concrete_maps_and_sets.depth -= 1;
                 concrete_maps_and_sets
                     .most_concrete_type_map
                     .insert(self, most_concrete.clone());
@@ -486,7 +898,12 @@ impl TypeEqualitySets {
         set_ref
     }
 
-    fn insert_as_equal(&mut self, ty1: TypeNode, ty2: TypeNode) {
+    fn insert_as_equal(&mut self, ty1: TypeNode, ty2: TypeNode) -> Result<(), ReflectError> {
+        let new_types =
+            usize::from(!self.contains_key(&ty1)) + usize::from(!self.contains_key(&ty2));
+        if self.set_map.len() + new_types > INFERENCE_LIMITS.with(Cell::get).max_set_size {
+            return Err(inference_limit_exceeded(ty2));
+        }
         match (self.set_map.get(&ty1), self.set_map.get(&ty2)) {
             (Some(&set_ref1), Some(&set_ref2)) => {
                 let set2 = std::mem::replace(&mut self.sets[set_ref2.0], EqualitySet::new());
@@ -515,6 +932,7 @@ impl TypeEqualitySets {
                 self.set_map.insert(ty1, set_ref);
             }
         }
+        Ok(())
     }
 
     /// Insert two types as equal to each other. In case one of the types is a
@@ -526,44 +944,51 @@ impl TypeEqualitySets {
         supertype: TypeNode,
         constraints: &mut ConstraintSet,
         subtypes: &mut LifetimeSubtypeMap,
-    ) {
+    ) -> Result<(), ReflectError> {
         use TypeNode::*;
+        let subtype = subtype.resolve_projections();
+        let supertype = supertype.resolve_projections();
         match (subtype, supertype) {
             (TraitObject(bounds1), TraitObject(bounds2)) => {
                 if bounds1.len() != bounds2.len() {
-                    panic!("TypeEqualitySets::insert_types_as_equal: TraitObjects have different number of bounds")
+                    return Err(type_mismatch(TraitObject(bounds2), TraitObject(bounds1)));
                 }
                 self.insert_inner_type_as_equal(
                     &TraitObject(bounds1),
                     &TraitObject(bounds2),
                     constraints,
                     subtypes,
-                );
+                )
             }
-            (ImplTrait(bounds1), ImplTrait(bounds2)) => {
+            (ImplTrait(bounds1, captures1), ImplTrait(bounds2, captures2)) => {
                 if bounds1.len() != bounds2.len() {
-                    panic!("TypeEqualitySets::insert_types_as_equal: ImplTraits have different number of bounds")
+                    return Err(type_mismatch(
+                        ImplTrait(bounds2, captures2),
+                        ImplTrait(bounds1, captures1),
+                    ));
                 }
                 self.insert_inner_type_as_equal(
-                    &ImplTrait(bounds1),
-                    &ImplTrait(bounds2),
+                    &ImplTrait(bounds1, captures1),
+                    &ImplTrait(bounds2, captures2),
                     constraints,
                     subtypes,
-                );
+                )
             }
-            (TraitObject(bounds), supertype) | (ImplTrait(bounds), supertype) => {
+            (TraitObject(bounds), supertype) | (ImplTrait(bounds, _), supertype) => {
                 constraints.insert(GenericConstraint::Type(PredicateType {
                     lifetimes: Vec::new(),
                     bounded_ty: Type(supertype),
                     bounds,
                 }));
+                Ok(())
             }
-            (subtype, TraitObject(bounds)) | (subtype, ImplTrait(bounds)) => {
+            (subtype, TraitObject(bounds)) | (subtype, ImplTrait(bounds, _)) => {
                 constraints.insert(GenericConstraint::Type(PredicateType {
                     lifetimes: Vec::new(),
                     bounded_ty: Type(subtype),
                     bounds,
                 }));
+                Ok(())
             }
             // A reference and a mutable reference are not equal, but a mutable reference may conform to a
             // normal reference, so the inner types may be considered equal
@@ -582,15 +1007,35 @@ impl TypeEqualitySets {
                 if let (Some(lifetime1), Some(lifetime2)) = (lifetime1, lifetime2) {
                     subtypes.insert_as_equal(lifetime1, lifetime2);
                 }
-                self.insert_types_as_equal(*inner1, *inner2, constraints, subtypes);
+                self.insert_types_as_equal(*inner1, *inner2, constraints, subtypes)
             }
             (subtype, supertype) => {
-                self.insert_inner_type_as_equal(&subtype, &supertype, constraints, subtypes);
+                self.insert_inner_type_as_equal(&subtype, &supertype, constraints, subtypes)?;
                 self.insert_as_equal(subtype, supertype)
             }
         }
     }
 
+    /// Insert the type of a value as a subtype of, or equal to, the type it is
+    /// used as. A diverging value coerces to any type, but only here at the
+    /// top level of the expression, so `!` is not special in the recursion.
+    /// A mismatch points at the value, if it comes from the macro input.
+    fn insert_value_type(
+        &mut self,
+        value: ValueRef,
+        expected: TypeNode,
+        constraints: &mut ConstraintSet,
+        subtypes: &mut LifetimeSubtypeMap,
+        supertype_map: &mut SupertypeMap,
+    ) -> Result<(), ReflectError> {
+        let value_type = value.get_type().0;
+        if value_type == TypeNode::Never {
+            return Ok(());
+        }
+        self.insert_as_subtype_or_equal(value_type, expected, constraints, subtypes, supertype_map)
+            .map_err(|error| error.at_value(value.span()))
+    }
+
     fn insert_as_subtype_or_equal(
         &mut self,
         subtype: TypeNode,
@@ -598,62 +1043,68 @@ impl TypeEqualitySets {
         constraints: &mut ConstraintSet,
         subtypes: &mut LifetimeSubtypeMap,
         supertype_map: &mut SupertypeMap,
-    ) {
+    ) -> Result<(), ReflectError> {
         use TypeNode::*;
+        let subtype = subtype.resolve_projections();
+        let supertype = supertype.resolve_projections();
         match (subtype, supertype) {
             (Tuple(types1), Tuple(types2)) => {
-                if types1.len() == types2.len() {
-                    types1
-                        .into_iter()
-                        .zip(types2.into_iter())
-                        .for_each(|(subtype, supertype)| {
-                            self.insert_as_subtype_or_equal(
-                                subtype,
-                                supertype,
-                                constraints,
-                                subtypes,
-                                supertype_map,
-                            )
-                        })
-                } else {
-                    panic!("TypeEqualitySets::insert_as_subtype_or_equal: Tuples have different number of arguments")
+                if types1.len() != types2.len() {
+                    return Err(type_mismatch(Tuple(types2), Tuple(types1)));
                 }
+                types1
+                    .into_iter()
+                    .zip(types2.into_iter())
+                    .try_for_each(|(subtype, supertype)| {
+                        self.insert_as_subtype_or_equal(
+                            subtype,
+                            supertype,
+                            constraints,
+                            subtypes,
+                            supertype_map,
+                        )
+                    })
             }
             (TraitObject(bounds1), TraitObject(bounds2)) => {
                 if bounds1.len() != bounds2.len() {
-                    panic!("TypeEqualitySets::insert_as_subtype_or_equal: TraitObjects have different number of bounds")
+                    return Err(type_mismatch(TraitObject(bounds2), TraitObject(bounds1)));
                 }
                 self.insert_inner_type_as_equal(
                     &TraitObject(bounds1),
                     &TraitObject(bounds2),
                     constraints,
                     subtypes,
-                );
+                )
             }
-            (ImplTrait(bounds1), ImplTrait(bounds2)) => {
+            (ImplTrait(bounds1, captures1), ImplTrait(bounds2, captures2)) => {
                 if bounds1.len() != bounds2.len() {
-                    panic!("TypeEqualitySets::insert_as_subtype_or_equal: ImplTraits have different number of bounds")
+                    return Err(type_mismatch(
+                        ImplTrait(bounds2, captures2),
+                        ImplTrait(bounds1, captures1),
+                    ));
                 }
                 self.insert_inner_type_as_equal(
-                    &ImplTrait(bounds1),
-                    &ImplTrait(bounds2),
+                    &ImplTrait(bounds1, captures1),
+                    &ImplTrait(bounds2, captures2),
                     constraints,
                     subtypes,
-                );
+                )
             }
-            (TraitObject(bounds), supertype) | (ImplTrait(bounds), supertype) => {
+            (TraitObject(bounds), supertype) | (ImplTrait(bounds, _), supertype) => {
                 constraints.insert(GenericConstraint::Type(PredicateType {
                     lifetimes: Vec::new(),
                     bounded_ty: Type(supertype),
                     bounds,
                 }));
+                Ok(())
             }
-            (subtype, TraitObject(bounds)) | (subtype, ImplTrait(bounds)) => {
+            (subtype, TraitObject(bounds)) | (subtype, ImplTrait(bounds, _)) => {
                 constraints.insert(GenericConstraint::Type(PredicateType {
                     lifetimes: Vec::new(),
                     bounded_ty: Type(subtype),
                     bounds,
                 }));
+                Ok(())
             }
             (
                 Reference {
@@ -667,12 +1118,17 @@ impl TypeEqualitySets {
                     inner: inner2,
                 },
             ) => {
+                // A reference to an array coerces to a reference to a slice
+                let inner1 = match (*inner1, &*inner2) {
+                    (Array(elem, _), Slice(_)) => Box::new(Slice(elem)),
+                    (inner1, _) => Box::new(inner1),
+                };
                 if is_mut1 {
                     if let (Some(subtype), Some(supertype)) = (lifetime1, lifetime2) {
                         subtypes.insert(subtype, supertype);
                     }
-                    self.insert_inner_type_as_equal(&*inner1, &*inner2, constraints, subtypes);
-                    self.insert_as_equal(*inner1, *inner2);
+                    self.insert_inner_type_as_equal(&*inner1, &*inner2, constraints, subtypes)?;
+                    self.insert_as_equal(*inner1, *inner2)?;
                 } else if !is_mut1 && !is_mut2 {
                     if let (Some(subtype), Some(supertype)) = (lifetime1, lifetime2) {
                         subtypes.insert(subtype, supertype);
@@ -683,16 +1139,29 @@ impl TypeEqualitySets {
                         constraints,
                         subtypes,
                         supertype_map,
-                    )
+                    )?;
                 } else {
-                    panic!("TypeEqualitySets::insert_as_subtype_or_equal: Cannot use a mutable reference in this context")
+                    return Err(ReflectError::MutabilityMismatch {
+                        expected: Type(Reference {
+                            is_mut: is_mut2,
+                            lifetime: lifetime2,
+                            inner: inner2,
+                        }),
+                        found: Type(Reference {
+                            is_mut: is_mut1,
+                            lifetime: lifetime1,
+                            inner: inner1,
+                        }),
+                        span: None,
+                    });
                 }
                 if let (Some(subtype), Some(supertype)) = (lifetime1, lifetime2) {
                     supertype_map.entry(supertype).or_default().push(subtype);
                 }
+                Ok(())
             }
             (subtype, supertype) => {
-                self.insert_inner_type_as_equal(&subtype, &supertype, constraints, subtypes);
+                self.insert_inner_type_as_equal(&subtype, &supertype, constraints, subtypes)?;
                 self.insert_as_equal(subtype, supertype)
             }
         }
@@ -707,17 +1176,16 @@ impl TypeEqualitySets {
         ty2: &TypeNode,
         constraints: &mut ConstraintSet,
         subtypes: &mut LifetimeSubtypeMap,
-    ) {
+    ) -> Result<(), ReflectError> {
         use TypeNode::*;
         match (ty1, ty2) {
             (Tuple(types1), Tuple(types2)) => {
-                if types1.len() == types2.len() {
-                    types1.iter().zip(types2.iter()).for_each(|(ty1, ty2)| {
-                        self.insert_types_as_equal(ty1.clone(), ty2.clone(), constraints, subtypes)
-                    })
-                } else {
-                    panic!("TypeEqualitySets::insert_inner_type_as_equal: Tuples have different number of arguments")
+                if types1.len() != types2.len() {
+                    return Err(type_mismatch(ty2.clone(), ty1.clone()));
                 }
+                types1.iter().zip(types2.iter()).try_for_each(|(ty1, ty2)| {
+                    self.insert_types_as_equal(ty1.clone(), ty2.clone(), constraints, subtypes)
+                })
             }
             (
                 Reference {
@@ -736,28 +1204,129 @@ impl TypeEqualitySets {
                 }
                 self.insert_types_as_equal(*inner1.clone(), *inner2.clone(), constraints, subtypes)
             }
-            (Path(path1), Path(path2)) => {
-                self.insert_path_arguments_as_equal(path1, path2, constraints, subtypes);
+            (Slice(elem1), Slice(elem2)) => {
+                self.insert_types_as_equal(*elem1.clone(), *elem2.clone(), constraints, subtypes)
             }
-            (TraitObject(bounds1), TraitObject(bounds2))
-            | (ImplTrait(bounds1), ImplTrait(bounds2)) => bounds1
-                .iter()
-                .zip(bounds2.iter())
-                .for_each(|bounds| match bounds {
-                    (TypeParamBound::Trait(trait_bound1), TypeParamBound::Trait(trait_bound2)) => {
-                        self.insert_path_arguments_as_equal(
-                            &trait_bound1.path,
-                            &trait_bound2.path,
+            (Array(elem1, len1), Array(elem2, len2)) => {
+                if len1 != len2 {
+                    return Err(type_mismatch(ty2.clone(), ty1.clone()));
+                }
+                self.insert_types_as_equal(*elem1.clone(), *elem2.clone(), constraints, subtypes)
+            }
+            (BareFn(args1), BareFn(args2)) if args1.inputs.len() == args2.inputs.len() => {
+                args1
+                    .inputs
+                    .iter()
+                    .zip(&args2.inputs)
+                    .try_for_each(|(ty1, ty2)| {
+                        self.insert_types_as_equal(
+                            ty1.0.clone(),
+                            ty2.0.clone(),
                             constraints,
                             subtypes,
-                        );
-                    }
-                    (TypeParamBound::Lifetime(lifetime1), TypeParamBound::Lifetime(lifetime2)) => {
-                        subtypes.insert_as_equal(*lifetime1, *lifetime2);
+                        )
+                    })?;
+                self.insert_types_as_equal(
+                    args1.output_type().0,
+                    args2.output_type().0,
+                    constraints,
+                    subtypes,
+                )
+            }
+            (BareFn(_), BareFn(_)) => Err(type_mismatch(ty2.clone(), ty1.clone())),
+            // Inside another type, `!` is a type like any other: `Option<!>`
+            // is not an `Option<String>`
+            (
+                Never,
+                Tuple(_)
+                | PrimitiveStr
+                | Reference { .. }
+                | Path(_)
+                | Slice(_)
+                | Array(..)
+                | BareFn(_)
+                | Verbatim(_),
+            )
+            | (
+                Tuple(_)
+                | PrimitiveStr
+                | Reference { .. }
+                | Path(_)
+                | Slice(_)
+                | Array(..)
+                | BareFn(_)
+                | Verbatim(_),
+                Never,
+            ) => Err(type_mismatch(ty2.clone(), ty1.clone())),
+            (Verbatim(verbatim1), Verbatim(verbatim2)) if verbatim1 != verbatim2 => {
+                Err(type_mismatch(ty2.clone(), ty1.clone()))
+            }
+            // A verbatim type is opaque, so it is not any type with a
+            // structure, even if that type is spelled the same
+            (
+                Verbatim(_),
+                Tuple(_)
+                | PrimitiveStr
+                | Reference { .. }
+                | Path(_)
+                | Slice(_)
+                | Array(..)
+                | BareFn(_)
+                | TraitObject(_),
+            )
+            | (
+                Tuple(_)
+                | PrimitiveStr
+                | Reference { .. }
+                | Path(_)
+                | Slice(_)
+                | Array(..)
+                | BareFn(_)
+                | TraitObject(_),
+                Verbatim(_),
+            ) => Err(type_mismatch(ty2.clone(), ty1.clone())),
+            (Path(path1), Path(path2)) => {
+                self.insert_path_arguments_as_equal(path1, path2, constraints, subtypes)
+            }
+            // The bounds are a set, so `dyn A + B` is the same type as
+            // `dyn B + A`. Each trait bound is therefore matched with the
+            // bound for the same trait, regardless of its position.
+            (TraitObject(bounds1), TraitObject(bounds2))
+            | (ImplTrait(bounds1, _), ImplTrait(bounds2, _)) => {
+                let mut lifetimes2 = bounds2.iter().filter_map(|bound| match bound {
+                    TypeParamBound::Lifetime(lifetime) => Some(*lifetime),
+                    TypeParamBound::Trait(_) => None,
+                });
+                bounds1.iter().try_for_each(|bound1| match bound1 {
+                    TypeParamBound::Trait(trait_bound1) => {
+                        let trait_bound2 = bounds2.iter().find_map(|bound2| match bound2 {
+                            TypeParamBound::Trait(trait_bound2)
+                                if is_same_trait(&trait_bound1.path, &trait_bound2.path) =>
+                            {
+                                Some(trait_bound2)
+                            }
+                            _ => None,
+                        });
+                        match trait_bound2 {
+                            Some(trait_bound2) => self.insert_path_arguments_as_equal(
+                                &trait_bound1.path,
+                                &trait_bound2.path,
+                                constraints,
+                                subtypes,
+                            ),
+                            None => Err(type_mismatch(ty2.clone(), ty1.clone())),
+                        }
                     }
-                    _ => panic!("Bound types doesn't match"),
-                }),
-            _ => (),
+                    TypeParamBound::Lifetime(lifetime1) => match lifetimes2.next() {
+                        Some(lifetime2) => {
+                            subtypes.insert_as_equal(*lifetime1, lifetime2);
+                            Ok(())
+                        }
+                        None => Err(type_mismatch(ty2.clone(), ty1.clone())),
+                    },
+                })
+            }
+            _ => Ok(()),
         }
     }
 
@@ -773,21 +1342,31 @@ impl TypeEqualitySets {
     /// each other.
     ///
     /// For cases where two paths have an unequal number of parameters, we
-    /// assume that the path with the fewest paramters is a type alias for
-    /// the type with more parameters. Unfortunately, it is not possible to
-    /// know which paramter corresponds to which, and thus the parameters can
-    /// not be compared.
+    /// assume that the path with the fewest parameters leaves out arguments
+    /// for defaulted type parameters, like `HashMap<K, V>` for
+    /// `HashMap<K, V, S = RandomState>`. The leading lifetimes and the
+    /// leading types of the two paths are then compared, and the rest are
+    /// ignored.
+    ///
+    /// Paths whose last segments have different idents name different types,
+    /// like `Vec<T>` and `HashSet<U>`, so their arguments are never related.
     fn insert_path_arguments_as_equal(
         &mut self,
         path1: &Path,
         path2: &Path,
         constraints: &mut ConstraintSet,
         subtypes: &mut LifetimeSubtypeMap,
-    ) {
+    ) -> Result<(), ReflectError> {
         let (segment1, segment2) = (
             &path1.path[path1.path.len() - 1],
             &path2.path[path2.path.len() - 1],
         );
+        if segment1.ident != segment2.ident {
+            return Err(type_mismatch(
+                TypeNode::Path(path2.clone()),
+                TypeNode::Path(path1.clone()),
+            ));
+        }
         match (&segment1.args, &segment2.args) {
             (PathArguments::AngleBracketed(args1), PathArguments::AngleBracketed(args2))
                 if args1.args.args.len() == args2.args.args.len() =>
@@ -797,7 +1376,7 @@ impl TypeEqualitySets {
                     .args
                     .iter()
                     .zip(args2.args.args.iter())
-                    .for_each(|args| match args {
+                    .try_for_each(|args| match args {
                         (GenericArgument::Type(ty1), GenericArgument::Type(ty2)) => self
                             .insert_types_as_equal(
                                 ty1.0.clone(),
@@ -810,70 +1389,227 @@ impl TypeEqualitySets {
                             GenericArgument::Lifetime(lifetime2),
                         ) => {
                             subtypes.insert_as_equal(*lifetime1, *lifetime2);
+                            Ok(())
                         }
+                        // Matched by name below
+                        (GenericArgument::Binding(_), GenericArgument::Binding(_)) => Ok(()),
                         _ => unimplemented!("TypeEqualitySets::insert_inner_type_as_equal: Path"),
-                    })
+                    })?;
+                self.insert_bindings_as_equal(
+                    &args1.args.args,
+                    &args2.args.args,
+                    constraints,
+                    subtypes,
+                )
             }
-            (PathArguments::Parenthesized(args1), _) | (_, PathArguments::Parenthesized(args1)) => {
-                unimplemented!(
-                    "TypeEqualitySets::insert_inner_type_as_equal: ParenthesizedGenericArgument"
+            (PathArguments::AngleBracketed(args1), PathArguments::AngleBracketed(args2)) => {
+                let (lifetimes1, types1) = split_generic_arguments(&args1.args.args);
+                let (lifetimes2, types2) = split_generic_arguments(&args2.args.args);
+                for (lifetime1, lifetime2) in lifetimes1.into_iter().zip(lifetimes2) {
+                    subtypes.insert_as_equal(lifetime1, lifetime2);
+                }
+                types1.into_iter().zip(types2).try_for_each(|(ty1, ty2)| {
+                    self.insert_types_as_equal(ty1, ty2, constraints, subtypes)
+                })?;
+                self.insert_bindings_as_equal(
+                    &args1.args.args,
+                    &args2.args.args,
+                    constraints,
+                    subtypes,
+                )
+            }
+            (PathArguments::Parenthesized(args1), PathArguments::Parenthesized(args2))
+                if args1.inputs.len() == args2.inputs.len() =>
+            {
+                args1
+                    .inputs
+                    .iter()
+                    .zip(args2.inputs.iter())
+                    .try_for_each(|(ty1, ty2)| {
+                        self.insert_types_as_equal(
+                            ty1.0.clone(),
+                            ty2.0.clone(),
+                            constraints,
+                            subtypes,
+                        )
+                    })?;
+                self.insert_types_as_equal(
+                    args1.output_type().0,
+                    args2.output_type().0,
+                    constraints,
+                    subtypes,
                 )
             }
-            _ => (),
+            (PathArguments::Parenthesized(_), PathArguments::Parenthesized(_)) => Err(
+                type_mismatch(TypeNode::Path(path2.clone()), TypeNode::Path(path1.clone())),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Associated type bindings such as `Item = T` in `dyn Iterator<Item = T>`
+    /// may be written in any order, so each binding is matched with the
+    /// binding of the same associated type in the other arguments
+    fn insert_bindings_as_equal(
+        &mut self,
+        args1: &[GenericArgument],
+        args2: &[GenericArgument],
+        constraints: &mut ConstraintSet,
+        subtypes: &mut LifetimeSubtypeMap,
+    ) -> Result<(), ReflectError> {
+        for arg1 in args1 {
+            if let GenericArgument::Binding(binding1) = arg1 {
+                let binding2 = args2.iter().find_map(|arg2| match arg2 {
+                    GenericArgument::Binding(binding2) if binding2.ident == binding1.ident => {
+                        Some(binding2)
+                    }
+                    _ => None,
+                });
+                if let Some(binding2) = binding2 {
+                    self.insert_types_as_equal(
+                        binding1.ty.0.clone(),
+                        binding2.ty.0.clone(),
+                        constraints,
+                        subtypes,
+                    )?;
+                }
+            }
         }
+        Ok(())
     }
 }
 
-impl WipImpl {
-    pub(crate) fn compute_trait_bounds(mut self) -> CompleteImpl {
-        let mut constraints = ConstraintSet::new();
-        let mut type_equality_sets = TypeEqualitySets::new();
-        let mut subtypes = LifetimeSubtypeMap::new();
+/// The lifetime and type arguments of a path, each in order
+fn split_generic_arguments(args: &[GenericArgument]) -> (Vec<Lifetime>, Vec<TypeNode>) {
+    let mut lifetimes = Vec::new();
+    let mut types = Vec::new();
+    for arg in args {
+        match arg {
+            GenericArgument::Lifetime(lifetime) => lifetimes.push(*lifetime),
+            GenericArgument::Type(ty) => types.push(ty.0.clone()),
+            _ => {}
+        }
+    }
+    (lifetimes, types)
+}
 
-        let OriginalGenercs {
-            original_generic_params,
-            original_data_struct_args,
-            original_trait_args,
-        } = self.get_original_generics(&mut constraints);
+impl WipImpl {
+    /// Compute the trait bounds for several impls at once. The facts from all
+    /// the impls are gathered in one `InferenceSession`, so every impl in the
+    /// batch is inferred from the same equality sets and constraints.
+    pub(crate) fn compute_batch_trait_bounds(
+        impls: Vec<Self>,
+        per_impl_memo: bool,
+        known_impls: &TraitImplTable,
+    ) -> Result<Vec<CompleteImpl>, ReflectError> {
+        let mut session = InferenceSession::new();
+
+        let impls: Vec<_> = impls
+            .into_iter()
+            .map(|mut imp| {
+                let original_generics = imp.get_original_generics(&mut session.constraints);
+                let functions = imp.functions.replace(Vec::new());
+                let assoc_types = imp.assoc_types.borrow();
+                functions
+                    .iter()
+                    .try_for_each(|function| session.add_function(function, &assoc_types))?;
+                drop(assoc_types);
+                Ok((imp, original_generics, functions))
+            })
+            .collect::<Result<_, ReflectError>>()?;
 
-        let functions = self.functions.into_inner();
-        functions.iter().for_each(|function| {
-            function.compute_trait_bounds(&mut constraints, &mut type_equality_sets, &mut subtypes)
-        });
+        let InferenceSession {
+            constraints,
+            type_equality_sets,
+            mut subtypes,
+        } = session;
 
         subtypes.add_lifetime_bounds(&constraints);
         let mut transitive_closure = subtypes.transitive_closure();
+        let mut constraints = constraints;
         constraints.add_subtypes(&mut transitive_closure);
 
-        let (mut relevant_generic_params, mut concrete_maps_and_sets) = get_relevant_generic_params(
+        // Shared by all the impls, since they use the same equality sets
+        let mut concrete_maps_and_sets = ConcreteMapAndSets::new(type_equality_sets);
+
+        let impls = impls
+            .into_iter()
+            .map(|(imp, original_generics, functions)| {
+                if per_impl_memo {
+                    concrete_maps_and_sets.clear_memo();
+                }
+                imp.into_complete_impl(
+                    original_generics,
+                    functions,
+                    &constraints,
+                    known_impls,
+                    &mut concrete_maps_and_sets,
+                    &mut transitive_closure,
+                )
+            })
+            .collect();
+        concrete_maps_and_sets.check_depth()?;
+        Ok(impls)
+    }
+
+    fn into_complete_impl(
+        self,
+        original_generics: OriginalGenercs,
+        functions: Vec<WipFunction>,
+        constraints: &ConstraintSet,
+        known_impls: &TraitImplTable,
+        concrete_maps_and_sets: &mut ConcreteMapAndSets,
+        transitive_closure: &mut TransitiveClosure,
+    ) -> CompleteImpl {
+        let OriginalGenercs {
+            original_generic_params,
+            original_data_struct_args,
+            original_trait_args,
+        } = original_generics;
+
+        let mut relevant_generic_params = get_relevant_generic_params(
             &original_generic_params,
-            type_equality_sets,
-            &mut transitive_closure,
+            concrete_maps_and_sets,
+            transitive_closure,
         );
 
-        let constraints = constraints.filter_constraints(
+        let mut constraints = constraints.clone().filter_constraints(
             &relevant_generic_params,
-            &mut concrete_maps_and_sets,
-            &mut transitive_closure,
+            concrete_maps_and_sets,
+            transitive_closure,
         );
+        constraints.eliminate_trivial(known_impls);
 
         let data_struct_args = get_args(
             original_data_struct_args,
-            &mut concrete_maps_and_sets,
-            &mut transitive_closure,
+            concrete_maps_and_sets,
+            transitive_closure,
         );
 
         let trait_args = get_args(
             original_trait_args,
-            &mut concrete_maps_and_sets,
-            &mut transitive_closure,
+            concrete_maps_and_sets,
+            transitive_closure,
         );
 
         let functions: Vec<_> = functions
             .into_iter()
             .map(|function| {
-                function
-                    .make_concrete_function(&mut concrete_maps_and_sets, &mut transitive_closure)
+                function.make_concrete_function(
+                    &relevant_generic_params,
+                    concrete_maps_and_sets,
+                    transitive_closure,
+                )
+            })
+            .collect();
+
+        let assoc_types: Vec<_> = self
+            .assoc_types
+            .take()
+            .into_iter()
+            .map(|(name, mut ty)| {
+                ty.0.make_most_concrete(concrete_maps_and_sets, transitive_closure);
+                (name, ty)
             })
             .collect();
 
@@ -881,9 +1617,12 @@ impl WipImpl {
         relevant_generic_params.remove(&GenericParam::Lifetime(STATIC_LIFETIME));
 
         CompleteImpl {
+            unsafety: self.is_unsafe(),
             trait_ty: self.trait_ty,
             ty: self.ty,
             functions,
+            assoc_types,
+            cfgs: self.cfgs.into_inner(),
             result: Some(TraitInferenceResult {
                 constraints,
                 generic_params: relevant_generic_params,
@@ -898,16 +1637,40 @@ impl WipImpl {
         let mut original_data_struct_args = Vec::new();
         let mut original_trait_args = Vec::new();
 
-        // data structure generics
-        if let Type(TypeNode::DataStructure(data)) = &mut self.ty {
+        let mut declared_constraints = ConstraintSet::new();
+
+        // data structure generics, also when the impl is for a wrapper of
+        // the data structure like `&'a Foo<T>`
+        if let Some(TypeNode::DataStructure(data)) = self.ty.0.wrapped_data_structure_mut() {
             data.generics.constraints.drain(..).for_each(|constraint| {
-                constraints.insert(constraint);
+                declared_constraints.insert_canonical(constraint);
             });
             data.generics.params.iter().for_each(|&param| {
                 original_generic_params.push(param);
                 original_data_struct_args.push(param);
             })
         };
+        // the self param of a blanket impl, with its declared bounds
+        if let TypeNode::TypeParam(param) = self.ty.0 {
+            original_generic_params.push(GenericParam::Type(param));
+            if !self.self_bounds.is_empty() {
+                declared_constraints.insert_canonical(GenericConstraint::Type(PredicateType {
+                    lifetimes: Vec::new(),
+                    bounded_ty: self.ty.clone(),
+                    bounds: self.self_bounds.clone(),
+                }));
+            }
+        }
+        self.ty.0.any_node(&mut |node| {
+            if let TypeNode::Reference {
+                lifetime: Some(lifetime),
+                ..
+            } = node
+            {
+                original_generic_params.push(GenericParam::Lifetime(*lifetime));
+            }
+            false
+        });
 
         // trait generics
         if let Some((generics, path)) = self.trait_ty.as_ref().and_then(|trait_ty| {
@@ -919,7 +1682,7 @@ impl WipImpl {
             }
         }) {
             generics.constraints.iter().for_each(|constraint| {
-                constraints.insert(constraint.clone());
+                declared_constraints.insert_canonical(constraint.clone());
             });
             generics.params.iter().for_each(|&param| {
                 original_generic_params.push(param);
@@ -927,6 +1690,8 @@ impl WipImpl {
             });
         };
 
+        constraints.merge_declared(declared_constraints);
+
         OriginalGenercs {
             original_generic_params,
             original_data_struct_args,
@@ -951,15 +1716,36 @@ impl WipFunction {
         constraints: &mut ConstraintSet,
         type_equality_sets: &mut TypeEqualitySets,
         subtypes: &mut LifetimeSubtypeMap,
-    ) {
+        assoc_types: &[(Ident, Type)],
+    ) -> Result<(), ReflectError> {
         use Receiver::*;
         let mut supertype_map = SupertypeMap::new();
+        self.resolve_invoked_assoc_outputs(assoc_types);
 
-        INVOKES.with_borrow(|invokes| {
+        INVOKES.with_borrow(|invokes| -> Result<(), ReflectError> {
             for invoke in invokes[self.invokes.start.0..self.invokes.end.unwrap().0].iter() {
                 let parent = &invoke.function.parent;
                 let sig = &invoke.function.sig;
-                let args_iter = match sig.receiver {
+                if let Some(self_ty) = &invoke.qualified_self {
+                    add_self_trait_bound(parent.as_ref().unwrap(), self_ty.clone(), constraints);
+                }
+                // A `_` in a turbofish is left for Rust to infer
+                let type_params = sig
+                    .generics
+                    .params
+                    .iter()
+                    .filter_map(|param| param.type_param());
+                for (param, arg) in type_params.zip(&invoke.type_args) {
+                    if !arg.0.any_node(&mut |node| *node == TypeNode::Infer) {
+                        type_equality_sets.insert_types_as_equal(
+                            TypeNode::TypeParam(param),
+                            arg.0.clone(),
+                            constraints,
+                            subtypes,
+                        )?;
+                    }
+                }
+                let args_iter = match &sig.receiver {
                     NoSelf => {
                         assert_eq!(invoke.args.len(), sig.inputs.len());
                         invoke.args.iter()
@@ -968,68 +1754,109 @@ impl WipFunction {
                         assert_eq!(invoke.args.len(), sig.inputs.len() + 1);
                         let mut args_iter = invoke.args.iter();
                         let parent = parent.as_ref().unwrap();
-                        let first_type = args_iter.next().unwrap().node().get_type();
-
-                        match reciever {
-                            SelfByValue => match parent.parent_kind {
-                                ParentKind::Trait => {
-                                    if let TypeNode::TypeParam(_) = &first_type.0 {
-                                        add_self_trait_bound(parent, first_type, constraints)
-                                    }
-                                }
-                                ParentKind::Impl => type_equality_sets.insert_as_subtype_or_equal(
-                                    first_type.0,
-                                    TypeNode::Path(parent.path.clone()),
+                        let first = *args_iter.next().unwrap();
+                        let first_type = first.get_type();
+
+                        match parent.parent_kind {
+                            ParentKind::Trait if invoke.qualified_self.is_some() => {
+                                let self_ty = invoke.qualified_self.as_ref().unwrap();
+                                type_equality_sets.insert_value_type(
+                                    first,
+                                    reciever.self_type(self_ty.0.clone()).unwrap(),
                                     constraints,
                                     subtypes,
                                     &mut supertype_map,
-                                ),
-                            },
-                            SelfByReference { is_mut, lifetime } => match parent.parent_kind {
-                                ParentKind::Trait => {
-                                    let first_type = first_type.dereference();
-                                    if let TypeNode::TypeParam(_) = &first_type.0 {
-                                        add_self_trait_bound(parent, first_type, constraints)
+                                )?
+                            }
+                            ParentKind::Trait => {
+                                if let Some(self_ty) = reciever.find_self(&first_type.0) {
+                                    if let TypeNode::TypeParam(_) = &self_ty {
+                                        add_self_trait_bound(parent, Type(self_ty), constraints)
                                     }
                                 }
-                                ParentKind::Impl => type_equality_sets.insert_as_subtype_or_equal(
-                                    first_type.0,
-                                    TypeNode::Reference {
-                                        is_mut,
-                                        inner: Box::new(TypeNode::Path(parent.path.clone())),
-                                        lifetime,
-                                    },
-                                    constraints,
-                                    subtypes,
-                                    &mut supertype_map,
-                                ),
-                            },
-                            NoSelf => unreachable!(),
+                            }
+                            ParentKind::Impl => type_equality_sets.insert_value_type(
+                                first,
+                                reciever
+                                    .self_type(TypeNode::Path(parent.path.clone()))
+                                    .unwrap(),
+                                constraints,
+                                subtypes,
+                                &mut supertype_map,
+                            )?,
                         }
                         args_iter
                     }
                 };
 
-                sig.inputs.iter().zip(args_iter).for_each(|(ty, val)| {
-                    type_equality_sets.insert_as_subtype_or_equal(
-                        val.node().get_type().0,
+                sig.inputs.iter().zip(args_iter).try_for_each(|(ty, val)| {
+                    type_equality_sets.insert_value_type(
+                        *val,
                         ty.0.clone(),
                         constraints,
                         subtypes,
                         &mut supertype_map,
                     )
-                });
+                })?;
 
                 Self::add_constraints(&invoke.function, constraints, subtypes, &supertype_map);
             }
-        });
+            Ok(())
+        })?;
+
+        self.add_try_bounds(constraints)?;
+        self.unify_array_elements(constraints, type_equality_sets, subtypes)?;
+        self.unify_struct_literal_fields(constraints, type_equality_sets, subtypes)?;
+        self.unify_closure_args(constraints, type_equality_sets, subtypes)?;
 
         self.set_last_value_subtype_to_output(
             constraints,
             type_equality_sets,
             subtypes,
             &mut supertype_map,
-        );
+            assoc_types,
+        )
+    }
+
+    /// A method of the implemented trait called on the impl's own self type
+    /// returns the types the impl defines for `Self::Name` outputs
+    fn resolve_invoked_assoc_outputs(&self, assoc_types: &[(Ident, Type)]) {
+        let (trait_path, self_ty) = match (&self.f.parent, &self.self_ty) {
+            (Some(parent), Some(self_ty)) if !assoc_types.is_empty() => (&parent.path, self_ty),
+            _ => return,
+        };
+        let range = self.invokes.start.0..self.invokes.end.unwrap().0;
+        let resolved: Vec<usize> = INVOKES.with_borrow(|invokes| {
+            range
+                .filter(|&i| {
+                    let invoke = &invokes[i];
+                    let parent = match &invoke.function.parent {
+                        Some(parent) => parent,
+                        None => return false,
+                    };
+                    if parent.path != *trait_path {
+                        return false;
+                    }
+                    match &invoke.qualified_self {
+                        Some(qualified_self) => qualified_self == self_ty,
+                        None => invoke.args.first().is_some_and(|receiver| {
+                            invoke
+                                .function
+                                .sig
+                                .receiver
+                                .find_self(&receiver.node().get_type().0)
+                                .is_some_and(|ty| ty == self_ty.0)
+                        }),
+                    }
+                })
+                .collect()
+        });
+        INVOKES.with_borrow_mut(|invokes| {
+            for i in resolved {
+                let function = Rc::make_mut(&mut invokes[i].function);
+                function.sig.output.0 = function.sig.output.0.replace_assoc_type(assoc_types);
+            }
+        });
     }
 
     fn constraint_iterator(f: &Function) -> impl Iterator<Item = &GenericConstraint> {
@@ -1071,30 +1898,173 @@ impl WipFunction {
         });
     }
 
+    /// `value?` on a `Result<T, E>` in a function returning `Result<U, F>`
+    /// converts the error with `F: From<E>`. A `?` on a `Result` in a
+    /// function that does not return a `Result`, or on an `Option` in a
+    /// function that does not return an `Option`, is an error.
+    fn add_try_bounds(&self, constraints: &mut ConstraintSet) -> Result<(), ReflectError> {
+        let output = match &self.self_ty {
+            Some(self_ty) => Type(self.f.sig.output.0.replace_self(&self_ty.0)),
+            None => self.f.sig.output.clone(),
+        };
+        VALUES.with_borrow(|values| {
+            for node in &values[self.values.start.0..self.values.end.unwrap().0] {
+                if let ValueNode::Try(value) = node {
+                    let operand = value.get_type();
+                    let output_err = match (operand.result_types(), output.result_types()) {
+                        (Some((_, err)), Some((_, output_err))) => Some((err, output_err)),
+                        (Some(_), None) => return Err(try_mismatch(&operand, &output)),
+                        (None, _)
+                            if operand.option_type().is_some()
+                                && output.option_type().is_none() =>
+                        {
+                            return Err(try_mismatch(&operand, &output));
+                        }
+                        // An `Option` in a function returning an `Option`, or a
+                        // type not known to be either before the inference
+                        (None, _) => None,
+                    };
+                    if let Some((err, output_err)) = output_err {
+                        let mut from = Path::root()
+                            .get_simple_path("core")
+                            .get_simple_path("convert")
+                            .get_simple_path("From");
+                        from.path.last_mut().unwrap().args =
+                            PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+                                args: GenericArguments {
+                                    args: vec![GenericArgument::Type(err.clone())],
+                                },
+                            });
+                        constraints.insert(GenericConstraint::Type(PredicateType {
+                            lifetimes: Vec::new(),
+                            bounded_ty: output_err.clone(),
+                            bounds: vec![TypeParamBound::from_path(from)],
+                        }));
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// The elements of an array literal all have the same type
+    fn unify_array_elements(
+        &self,
+        constraints: &mut ConstraintSet,
+        type_equality_sets: &mut TypeEqualitySets,
+        subtypes: &mut LifetimeSubtypeMap,
+    ) -> Result<(), ReflectError> {
+        VALUES.with_borrow(|values| {
+            for node in &values[self.values.start.0..self.values.end.unwrap().0] {
+                if let ValueNode::Array(elems) = node {
+                    if let Some((first, rest)) = elems.split_first() {
+                        let first = first.get_type();
+                        rest.iter().try_for_each(|elem| {
+                            type_equality_sets.insert_types_as_equal(
+                                first.0.clone(),
+                                elem.get_type().0,
+                                constraints,
+                                subtypes,
+                            )
+                        })?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// The values of a struct literal have the types of the fields
+    fn unify_struct_literal_fields(
+        &self,
+        constraints: &mut ConstraintSet,
+        type_equality_sets: &mut TypeEqualitySets,
+        subtypes: &mut LifetimeSubtypeMap,
+    ) -> Result<(), ReflectError> {
+        VALUES.with_borrow(|values| {
+            for node in &values[self.values.start.0..self.values.end.unwrap().0] {
+                if let ValueNode::DataStructure { ty, data } = node {
+                    let field_types = ty.data();
+                    field_types.fields().zip(data.fields()).try_for_each(
+                        |((_, field_ty), (_, value))| {
+                            type_equality_sets
+                                .insert_types_as_equal(
+                                    field_ty.element.0.clone(),
+                                    value.element.get_type().0,
+                                    constraints,
+                                    subtypes,
+                                )
+                                .map_err(|err| err.at_value(value.element.span()))
+                        },
+                    )?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// The arguments of a call of a closure have the types of its parameters
+    fn unify_closure_args(
+        &self,
+        constraints: &mut ConstraintSet,
+        type_equality_sets: &mut TypeEqualitySets,
+        subtypes: &mut LifetimeSubtypeMap,
+    ) -> Result<(), ReflectError> {
+        VALUES.with_borrow(|values| {
+            for node in &values[self.values.start.0..self.values.end.unwrap().0] {
+                if let ValueNode::Call { closure, args } = node {
+                    let params = match &values[closure.0] {
+                        ValueNode::Closure { params, .. } => params,
+                        _ => unreachable!(),
+                    };
+                    params.iter().zip(args).try_for_each(|(param, arg)| {
+                        type_equality_sets
+                            .insert_types_as_equal(
+                                param.get_type().0,
+                                arg.get_type().0,
+                                constraints,
+                                subtypes,
+                            )
+                            .map_err(|err| err.at_value(arg.span()))
+                    })?;
+                }
+            }
+            Ok(())
+        })
+    }
+
     fn set_last_value_subtype_to_output(
         &self,
         constraints: &mut ConstraintSet,
         type_equality_sets: &mut TypeEqualitySets,
         subtypes: &mut LifetimeSubtypeMap,
         supertype_map: &mut SupertypeMap,
-    ) {
+        assoc_types: &[(Ident, Type)],
+    ) -> Result<(), ReflectError> {
         // The type of the outgoing value must be the same as the return value
         if self.values.end.unwrap().0 > self.values.start.0 {
-            let return_value_type =
-                VALUES.with_borrow(|values| values[self.values.end.unwrap().0 - 1].get_type());
+            let return_value = ValueRef(self.values.end.unwrap().0 - 1);
+            let output = match &self.self_ty {
+                Some(self_ty) => self.f.sig.output.0.replace_self(&self_ty.0),
+                None => self.f.sig.output.0.clone(),
+            }
+            .replace_assoc_type(assoc_types);
 
-            type_equality_sets.insert_as_subtype_or_equal(
-                return_value_type.0,
-                self.f.sig.output.0.clone(),
+            type_equality_sets.insert_value_type(
+                return_value,
+                output,
                 constraints,
                 subtypes,
                 supertype_map,
             )
+        } else {
+            Ok(())
         }
     }
 
     fn make_concrete_function(
         self,
+        relevant_generic_params: &BTreeSet<GenericParam>,
         concrete_maps_and_sets: &mut ConcreteMapAndSets,
         transitive_closure: &mut TransitiveClosure,
     ) -> CompleteFunction {
@@ -1127,6 +2097,9 @@ impl WipFunction {
         {
             lifetime.make_most_concrete(transitive_closure);
         }
+        if let Receiver::SelfByWrapper(ref mut ty) = f.sig.receiver {
+            ty.0.make_most_concrete(concrete_maps_and_sets, transitive_closure);
+        }
 
         for input in &mut f.sig.inputs {
             input
@@ -1138,6 +2111,39 @@ impl WipFunction {
             .0
             .make_most_concrete(concrete_maps_and_sets, transitive_closure);
 
+        let mut in_scope = relevant_generic_params.clone();
+        in_scope.extend(f.sig.generics.params.iter().cloned());
+
+        INVOKES.with_borrow_mut(|invokes| {
+            for invoke in &mut invokes[self.invokes.start.0..self.invokes.end.unwrap().0] {
+                if let Some(self_ty) = &mut invoke.qualified_self {
+                    self_ty
+                        .0
+                        .make_most_concrete(concrete_maps_and_sets, transitive_closure);
+                }
+                for arg in &mut invoke.type_args {
+                    if !arg.0.any_node(&mut |node| *node == TypeNode::Infer) {
+                        let param = arg.0.clone();
+                        arg.0
+                            .make_most_concrete(concrete_maps_and_sets, transitive_closure);
+                        // Nothing was inferred for the param, or only a param
+                        // that cannot be named here, so it is left for Rust
+                        // to infer
+                        if invoke.inferred_type_args
+                            && (arg.0 == param || !arg.0.is_nameable_in(&in_scope))
+                        {
+                            arg.0 = TypeNode::Infer;
+                        }
+                    }
+                }
+                if invoke.inferred_type_args
+                    && invoke.type_args.iter().all(|arg| arg.0 == TypeNode::Infer)
+                {
+                    invoke.type_args.clear();
+                }
+            }
+        });
+
         for constraint in &mut f.sig.generics.constraints {
             constraint.make_most_concrete(concrete_maps_and_sets, transitive_closure);
         }
@@ -1156,10 +2162,44 @@ impl WipFunction {
             invokes: invokes.unwrap(),
             macros: macros.unwrap(),
             ret: self.ret,
+            cfgs: self.cfgs.into_inner(),
+            attrs: self.attrs.into_inner(),
+            mut_args: self.mut_args.into_inner(),
         }
     }
 }
 
+fn inference_limit_exceeded(ty: TypeNode) -> ReflectError {
+    ReflectError::InferenceLimitExceeded { ty: Type(ty) }
+}
+
+fn try_mismatch(operand: &Type, output: &Type) -> ReflectError {
+    ReflectError::TryMismatch {
+        operand: operand.clone(),
+        output: output.clone(),
+    }
+}
+
+fn type_mismatch(expected: TypeNode, found: TypeNode) -> ReflectError {
+    ReflectError::TypeMismatch {
+        expected: Type(expected),
+        found: Type(found),
+        span: None,
+    }
+}
+
+/// Two trait bounds refer to the same trait if their paths are equal when
+/// the generic arguments are ignored, e.g. `Foo<T>` and `Foo<u32>`
+fn is_same_trait(path1: &Path, path2: &Path) -> bool {
+    path1.global == path2.global
+        && path1.path.len() == path2.path.len()
+        && path1
+            .path
+            .iter()
+            .zip(&path2.path)
+            .all(|(segment1, segment2)| segment1.ident == segment2.ident)
+}
+
 fn add_self_trait_bound(parent: &Rc<Parent>, first_type: Type, constraints: &mut ConstraintSet) {
     assert_eq!(parent.parent_kind, ParentKind::Trait);
 
@@ -1168,14 +2208,11 @@ fn add_self_trait_bound(parent: &Rc<Parent>, first_type: Type, constraints: &mut
     constraints.insert(GenericConstraint::Type(PredicateType {
         lifetimes: Vec::new(),
         bounded_ty: first_type,
-        bounds: vec![TypeParamBound::Trait(TraitBound {
-            lifetimes: Vec::new(),
-            path,
-        })],
+        bounds: vec![TypeParamBound::from_path(path)],
     }));
 }
 
-fn params_to_args(params: &[GenericParam]) -> PathArguments {
+pub(crate) fn params_to_args(params: &[GenericParam]) -> PathArguments {
     if params.is_empty() {
         return PathArguments::None;
     }
@@ -1202,17 +2239,11 @@ fn params_to_args(params: &[GenericParam]) -> PathArguments {
 /// We then return a set containing V, and U
 fn get_relevant_generic_params(
     original_generic_params: &[GenericParam],
-    type_equality_sets: TypeEqualitySets,
+    concrete_maps_and_sets: &mut ConcreteMapAndSets,
     transitive_closure: &mut TransitiveClosure,
-) -> (BTreeSet<GenericParam>, ConcreteMapAndSets) {
+) -> BTreeSet<GenericParam> {
     use TypeNode::*;
     let mut relevant_generic_params = BTreeSet::new();
-    let most_concrete_type_map = BTreeMap::new();
-
-    let mut concrete_maps_and_sets = ConcreteMapAndSets {
-        most_concrete_type_map,
-        type_equality_sets,
-    };
 
     for param in original_generic_params.iter() {
         match param {
@@ -1227,8 +2258,7 @@ fn get_relevant_generic_params(
                         .new_set(TypeParam(type_param))
                 });
 
-                let node =
-                    set_ref.make_most_concrete(&mut concrete_maps_and_sets, transitive_closure);
+                let node = set_ref.make_most_concrete(concrete_maps_and_sets, transitive_closure);
                 node.inner_params(
                     &mut concrete_maps_and_sets.type_equality_sets,
                     &mut relevant_generic_params,
@@ -1245,7 +2275,7 @@ fn get_relevant_generic_params(
         }
     }
 
-    (relevant_generic_params, concrete_maps_and_sets)
+    relevant_generic_params
 }
 
 fn get_args(
@@ -1300,13 +2330,40 @@ impl GenericConstraint {
         relevant_generic_params: &BTreeSet<GenericParam>,
         transitive_closure: &mut TransitiveClosure,
     ) -> bool {
+        let original = self.clone();
         self.make_most_concrete(concrete_maps_and_sets, transitive_closure);
+
+        // A self referential constraint such as `T: AsRef<T>` may be cut
+        // short by the loop guard in `TypeEqualitySetRef::make_most_concrete`,
+        // which leaves inferred placeholders behind. Such constraints are kept
+        // as they were written rather than half concretized.
+        if let (GenericConstraint::Type(original_ty), GenericConstraint::Type(pred_ty)) =
+            (&original, &*self)
+        {
+            if original_ty.is_self_referential() && pred_ty.contains_infer() {
+                *self = original;
+            }
+        }
+
         self.is_relevant(
             &concrete_maps_and_sets.type_equality_sets,
             &relevant_generic_params,
         )
     }
 
+    /// Whether a type predicate mentions one of the relevant type params
+    fn bounds_param(&self, relevant_generic_params: &BTreeSet<GenericParam>) -> bool {
+        match self {
+            GenericConstraint::Type(pred_ty) => pred_ty.any_node(&mut |node| match node {
+                TypeNode::TypeParam(param) => {
+                    relevant_generic_params.contains(&GenericParam::Type(*param))
+                }
+                _ => false,
+            }),
+            GenericConstraint::Lifetime(_) => false,
+        }
+    }
+
     fn is_relevant(
         &self,
         type_equality_sets: &TypeEqualitySets,
@@ -1340,6 +2397,56 @@ impl GenericConstraint {
 }
 
 impl PredicateType {
+    /// Whether the bounded type param is mentioned in its own bounds
+    fn is_self_referential(&self) -> bool {
+        match &self.bounded_ty.0 {
+            TypeNode::TypeParam(_) => self.bounds.iter().any(|bound| match bound {
+                TypeParamBound::Trait(bound) => {
+                    bound.path.any_node(&mut |node| *node == self.bounded_ty.0)
+                }
+                TypeParamBound::Lifetime(_) => false,
+            }),
+            _ => false,
+        }
+    }
+
+    fn contains_infer(&self) -> bool {
+        self.any_node(&mut |node| *node == TypeNode::Infer)
+    }
+
+    /// Whether `f` holds for any type in the bounded type or the bounds
+    pub(crate) fn any_node<F>(&self, f: &mut F) -> bool
+    where
+        F: FnMut(&TypeNode) -> bool,
+    {
+        self.bounded_ty.0.any_node(f)
+            || self.bounds.iter().any(|bound| match bound {
+                TypeParamBound::Trait(bound) => bound.path.any_node(f),
+                TypeParamBound::Lifetime(_) => false,
+            })
+    }
+
+    /// Whether `self` bounds the same type by at least the bounds of `other`
+    fn implies(&self, other: &PredicateType) -> bool {
+        self.lifetimes == other.lifetimes
+            && self.bounded_ty == other.bounded_ty
+            && other.bounds.iter().all(|bound| self.bounds.contains(bound))
+    }
+
+    fn unify_bound_lifetimes(
+        &self,
+        relevant_generic_params: &BTreeSet<GenericParam>,
+        transitive_closure: &mut TransitiveClosure,
+    ) {
+        self.bounds.iter().for_each(|bound| {
+            if let TypeParamBound::Trait(bound) = bound {
+                bound
+                    .path
+                    .unify_lifetime_args(relevant_generic_params, transitive_closure)
+            }
+        })
+    }
+
     fn is_relevant_for_constraint(
         &self,
         type_equality_sets: &TypeEqualitySets,
@@ -1373,6 +2480,24 @@ impl PredicateType {
 }
 
 impl TypeNode {
+    /// Whether every param mentioned by the type is in `in_scope`, so the
+    /// type can be written out in a turbofish
+    fn is_nameable_in(&mut self, in_scope: &BTreeSet<GenericParam>) -> bool {
+        let mut nameable = true;
+        self.walk_types_mut(&mut |node| {
+            if let TypeNode::TypeParam(param) = node {
+                nameable &= in_scope.contains(&GenericParam::Type(*param));
+            }
+            nameable
+        });
+        self.walk_lifetimes_mut(&mut |lifetime| {
+            nameable &= *lifetime == STATIC_LIFETIME
+                || *lifetime == ANONYMOUS_LIFETIME
+                || in_scope.contains(&GenericParam::Lifetime(*lifetime));
+        });
+        nameable
+    }
+
     fn is_relevant_for_constraint(
         &self,
         type_equality_sets: &TypeEqualitySets,
@@ -1387,10 +2512,58 @@ impl TypeNode {
                 lifetime, inner, ..
             } => inner.is_relevant_for_constraint(type_equality_sets, relevant_generic_params),
 
+            // The target type itself, e.g. `Self: Default` from a call to
+            // `Default::default()` returning `Self`
+            DataStructure(data) => data
+                .generics
+                .params
+                .iter()
+                .all(|param| relevant_generic_params.contains(param)),
+
+            // A projection like `T::Item` in `where T::Item: Clone`
+            Projection { self_ty, .. } => {
+                self_ty.is_relevant_for_constraint(type_equality_sets, relevant_generic_params)
+            }
+
             _ => false,
         }
     }
 
+    /// A type used as a generic argument is relevant as long as every
+    /// parameter and lifetime it mentions is relevant, so concrete arguments
+    /// such as `Borrow<'a, String>` are kept.
+    fn is_relevant_as_argument(
+        &self,
+        type_equality_sets: &TypeEqualitySets,
+        relevant_generic_params: &BTreeSet<GenericParam>,
+    ) -> bool {
+        use TypeNode::*;
+        match self {
+            Path(path) => {
+                path.is_relevant_for_constraint(type_equality_sets, relevant_generic_params)
+            }
+            PrimitiveStr | Never | Verbatim(_) => true,
+            Tuple(types) => types
+                .iter()
+                .all(|ty| ty.is_relevant_as_argument(type_equality_sets, relevant_generic_params)),
+            Reference {
+                lifetime, inner, ..
+            } => {
+                lifetime
+                    .iter()
+                    .all(|lifetime| lifetime.is_relevant_for_constraint(relevant_generic_params))
+                    && inner.is_relevant_as_argument(type_equality_sets, relevant_generic_params)
+            }
+            Slice(elem) | Array(elem, _) => {
+                elem.is_relevant_as_argument(type_equality_sets, relevant_generic_params)
+            }
+            BareFn(args) => args.inputs.iter().chain(&args.output).all(|ty| {
+                ty.0.is_relevant_as_argument(type_equality_sets, relevant_generic_params)
+            }),
+            _ => self.is_relevant_for_constraint(type_equality_sets, relevant_generic_params),
+        }
+    }
+
     fn make_most_concrete(
         &mut self,
         concrete_maps_and_sets: &mut ConcreteMapAndSets,
@@ -1419,6 +2592,10 @@ impl TypeNode {
                 node
             }
             (PrimitiveStr, _) | (_, PrimitiveStr) => PrimitiveStr,
+            (Never, Never) => Never,
+            (Verbatim(verbatim), _) | (_, Verbatim(verbatim)) => Verbatim(verbatim),
+            // Primitives are as concrete as a type can be
+            (Path(path), _) | (_, Path(path)) if path.is_primitive() => Path(path),
             (Path(path1), Path(path2)) => crate::Path::make_most_concrete_from_pair(
                 path1,
                 path2,
@@ -1434,7 +2611,7 @@ impl TypeNode {
                     .into_iter()
                     .zip(types2.into_iter())
                     .map(|(ty1, ty2)| {
-                        TypeNode::make_most_concrete_from_pair(
+                        TypeNode::make_most_concrete_from_inner_pair(
                             ty1,
                             ty2,
                             concrete_maps_and_sets,
@@ -1457,7 +2634,7 @@ impl TypeNode {
             ) => Reference {
                 is_mut: is_mut1 && is_mut2,
 
-                inner: Box::new(TypeNode::make_most_concrete_from_pair(
+                inner: Box::new(TypeNode::make_most_concrete_from_inner_pair(
                     *inner1,
                     *inner2,
                     concrete_maps_and_sets,
@@ -1468,11 +2645,36 @@ impl TypeNode {
                     .or(lifetime1)
                     .or(lifetime2),
             },
+            (Slice(elem1), Slice(elem2)) => {
+                Slice(Box::new(TypeNode::make_most_concrete_from_inner_pair(
+                    *elem1,
+                    *elem2,
+                    concrete_maps_and_sets,
+                    transitive_closure,
+                )))
+            }
+            // The lengths were checked to be the same when the arrays were
+            // inserted as equal
+            (Array(elem1, len), Array(elem2, _)) => Array(
+                Box::new(TypeNode::make_most_concrete_from_inner_pair(
+                    *elem1,
+                    *elem2,
+                    concrete_maps_and_sets,
+                    transitive_closure,
+                )),
+                len,
+            ),
+            // The inputs and outputs were inserted as equal, so either
+            // function type leads to the same concrete types
+            (mut node @ BareFn(_), BareFn(_)) => {
+                node.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure);
+                node
+            }
             (TraitObject(_), mut node) | (mut node, TraitObject(_)) => {
                 node.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure);
                 node
             }
-            (ImplTrait(_), mut node) | (mut node, ImplTrait(_)) => {
+            (ImplTrait(..), mut node) | (mut node, ImplTrait(..)) => {
                 node.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure);
                 node
             }
@@ -1488,6 +2690,35 @@ impl TypeNode {
         }
     }
 
+    /// Makes the most concrete `TypeNode` from the inner types of two equal
+    /// types, like the `T` and `U` of `Vec<T>` and `Vec<U>`. The inner types
+    /// were inserted as equal, so their set may hold a more concrete type than
+    /// either of them.
+    ///
+    /// `make_most_concrete_from_pair` keeps the lower of two type params, which
+    /// is right for the members of the set being made concrete, but a pair of
+    /// inner params must be looked up in their own set. Otherwise `Vec<T>`
+    /// and `Vec<U>` with `U = u32` would stay `Vec<T>`.
+    fn make_most_concrete_from_inner_pair(
+        ty1: TypeNode,
+        ty2: TypeNode,
+        concrete_maps_and_sets: &mut ConcreteMapAndSets,
+        transitive_closure: &mut TransitiveClosure,
+    ) -> Self {
+        match (ty1, ty2) {
+            (TypeNode::TypeParam(_), mut node @ TypeNode::TypeParam(_)) => {
+                node.make_most_concrete(concrete_maps_and_sets, transitive_closure);
+                node
+            }
+            (ty1, ty2) => TypeNode::make_most_concrete_from_pair(
+                ty1,
+                ty2,
+                concrete_maps_and_sets,
+                transitive_closure,
+            ),
+        }
+    }
+
     fn make_most_concrete_inner(
         &mut self,
         concrete_maps_and_sets: &mut ConcreteMapAndSets,
@@ -1507,11 +2738,60 @@ impl TypeNode {
                     lifetime.make_most_concrete(transitive_closure);
                 }
             }
+            Slice(elem) | Array(elem, _) => {
+                elem.make_most_concrete(concrete_maps_and_sets, transitive_closure)
+            }
+            BareFn(args) => args.types_mut().for_each(|ty| {
+                ty.0.make_most_concrete(concrete_maps_and_sets, transitive_closure)
+            }),
             Path(path) => path.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure),
+            TraitObject(bounds) => bounds.iter_mut().for_each(|bound| {
+                bound.make_most_concrete_inner(concrete_maps_and_sets, transitive_closure)
+            }),
+            Projection { self_ty, .. } => {
+                self_ty.make_most_concrete(concrete_maps_and_sets, transitive_closure);
+                // A more concrete self type may match a registered projection
+                let projection = std::mem::replace(self, Infer);
+                *self = projection.resolve_projections();
+            }
             node => {}
         }
     }
 
+    /// Whether `f` holds for this node or any of the types nested inside it
+    pub(crate) fn any_node<F>(&self, f: &mut F) -> bool
+    where
+        F: FnMut(&TypeNode) -> bool,
+    {
+        use TypeNode::*;
+        f(self)
+            || match self {
+                Tuple(types) => types.iter().any(|ty| ty.any_node(f)),
+                Reference { inner, .. } => inner.any_node(f),
+                Dereference(inner) | Slice(inner) | Array(inner, _) => inner.any_node(f),
+                BareFn(args) => args
+                    .inputs
+                    .iter()
+                    .chain(&args.output)
+                    .any(|ty| ty.0.any_node(f)),
+                TraitObject(bounds) | ImplTrait(bounds, _) => {
+                    bounds.iter().any(|bound| match bound {
+                        TypeParamBound::Trait(bound) => bound.path.any_node(f),
+                        TypeParamBound::Lifetime(_) => false,
+                    })
+                }
+                Path(path) => path.any_node(f),
+                Projection {
+                    self_ty,
+                    trait_path,
+                    ..
+                } => {
+                    self_ty.any_node(f) || trait_path.as_ref().is_some_and(|path| path.any_node(f))
+                }
+                _ => false,
+            }
+    }
+
     fn inner_params(
         &self,
         type_equality_sets: &mut TypeEqualitySets,
@@ -1524,9 +2804,14 @@ impl TypeNode {
                     ty.inner_params(type_equality_sets, relevant_generic_params)
                 }
             }
-            Reference { inner, .. } => {
+            Reference { inner, .. } | Slice(inner) | Array(inner, _) => {
                 inner.inner_params(type_equality_sets, relevant_generic_params)
             }
+            BareFn(args) => {
+                for ty in args.inputs.iter().chain(&args.output) {
+                    ty.0.inner_params(type_equality_sets, relevant_generic_params)
+                }
+            }
             Path(path) => {
                 path.inner_params(type_equality_sets, relevant_generic_params);
             }
@@ -1610,6 +2895,40 @@ impl TypeParamBound {
 }
 
 impl Path {
+    fn any_node<F>(&self, f: &mut F) -> bool
+    where
+        F: FnMut(&TypeNode) -> bool,
+    {
+        self.path.iter().any(|segment| match &segment.args {
+            PathArguments::None => false,
+            PathArguments::AngleBracketed(args) => args.args.args.iter().any(|arg| match arg {
+                GenericArgument::Type(ty) => ty.0.any_node(f),
+                GenericArgument::Binding(binding) => binding.ty.0.any_node(f),
+                _ => false,
+            }),
+            PathArguments::Parenthesized(args) => {
+                args.inputs.iter().any(|ty| ty.0.any_node(f))
+                    || args.output.iter().any(|ty| ty.0.any_node(f))
+            }
+        })
+    }
+
+    fn unify_lifetime_args(
+        &self,
+        relevant_generic_params: &BTreeSet<GenericParam>,
+        transitive_closure: &mut TransitiveClosure,
+    ) {
+        for segment in &self.path {
+            if let PathArguments::AngleBracketed(args) = &segment.args {
+                for arg in &args.args.args {
+                    if let GenericArgument::Lifetime(lifetime) = arg {
+                        transitive_closure.unify_with_relevant(*lifetime, relevant_generic_params)
+                    }
+                }
+            }
+        }
+    }
+
     fn is_relevant_for_constraint(
         &self,
         type_equality_sets: &TypeEqualitySets,
@@ -1620,18 +2939,25 @@ impl Path {
 
             PathArguments::AngleBracketed(args) => args.args.args.iter().all(|arg| match arg {
                 GenericArgument::Type(ty) => {
-                    ty.0.is_relevant_for_constraint(type_equality_sets, relevant_generic_params)
+                    ty.0.is_relevant_as_argument(type_equality_sets, relevant_generic_params)
                 }
 
                 GenericArgument::Lifetime(lifetime) => {
                     lifetime.is_relevant_for_constraint(relevant_generic_params)
                 }
 
+                GenericArgument::Binding(binding) => binding
+                    .ty
+                    .0
+                    .is_relevant_as_argument(type_equality_sets, relevant_generic_params),
+
                 _ => unimplemented!("is_relevant_for_constraint: PathArguments::AngleBracketed"),
             }),
 
-            PathArguments::Parenthesized(_) => {
-                unimplemented!("is_relevant_for_constraint: PathArguments::Parenthesized")
+            PathArguments::Parenthesized(args) => {
+                args.inputs.iter().chain(&args.output).all(|ty| {
+                    ty.0.is_relevant_as_argument(type_equality_sets, relevant_generic_params)
+                })
             }
         })
     }
@@ -1653,12 +2979,18 @@ impl Path {
                             GenericArgument::Lifetime(lifetime) => {
                                 relevant_generic_params.insert(GenericParam::Lifetime(*lifetime));
                             }
+                            GenericArgument::Binding(binding) => binding
+                                .ty
+                                .0
+                                .inner_params(type_equality_sets, relevant_generic_params),
                             _ => unimplemented!(),
                         }
                     }
                 }
-                PathArguments::Parenthesized(_) => {
-                    unimplemented!("Path::inner_param_set_ref: Parenthesized")
+                PathArguments::Parenthesized(args) => {
+                    for ty in args.inputs.iter().chain(&args.output) {
+                        ty.0.inner_params(type_equality_sets, relevant_generic_params)
+                    }
                 }
             }
         }
@@ -1682,12 +3014,16 @@ impl Path {
                     GenericArgument::Lifetime(lifetime) => {
                         lifetime.make_most_concrete(transitive_closure)
                     }
+                    GenericArgument::Binding(binding) => binding
+                        .ty
+                        .0
+                        .make_most_concrete(concrete_maps_and_sets, transitive_closure),
                     _ => unimplemented!(),
                 });
             }
-            PathArguments::Parenthesized(_) => {
-                unimplemented!("Path::make_most_concrete_inner: Parenthesized")
-            }
+            PathArguments::Parenthesized(args) => args.types_mut().for_each(|ty| {
+                ty.0.make_most_concrete(concrete_maps_and_sets, transitive_closure)
+            }),
         };
     }
 
@@ -1756,7 +3092,7 @@ impl Path {
                             .map(|arg_pair| match arg_pair {
                                 (GenericArgument::Type(ty1), GenericArgument::Type(ty2)) => {
                                     GenericArgument::Type(Type(
-                                        TypeNode::make_most_concrete_from_pair(
+                                        TypeNode::make_most_concrete_from_inner_pair(
                                             ty1.clone().0,
                                             ty2.clone().0,
                                             concrete_maps_and_sets,
@@ -1784,8 +3120,12 @@ impl Path {
                     }
                 }
             }
-            (PathArguments::Parenthesized(args1), PathArguments::Parenthesized(args2)) => {
-                unimplemented!("Path::make_most_concrete_from_pair: Parenthesized")
+            // The inputs and outputs were inserted as equal, so either
+            // `Fn` bound leads to the same concrete types
+            (PathArguments::Parenthesized(_), PathArguments::Parenthesized(_)) => {
+                let mut node = TypeNode::Path(path1);
+                node.make_most_concrete(concrete_maps_and_sets, transitive_closure);
+                node
             }
             _ => panic!("Path::make_most_concrete_from_pair: incompatible types"),
         }
@@ -1,17 +1,82 @@
+use crate::print::with_param_names;
+use crate::trait_inference::params_to_args;
 use crate::{
-    generics, Data, GenericParam, Generics, Ident, Lifetime, ParamMap, Path, Print, Struct,
-    SynParamMap, TupleStruct, TypeParam, TypeParamBound,
+    generics, AngleBracketedGenericArguments, Data, GenericArgument, GenericArguments,
+    GenericParam, Generics, Ident, Lifetime, ParamMap, ParenthesizedGenericArguments, Path,
+    PathArguments, PathSegment, Print, ReflectError, Struct, SynParamMap, TupleStruct, Turbofish,
+    TypeParam, TypeParamBound, ANONYMOUS_LIFETIME, CODEGEN_OPTIONS, COPY_TYPES, DROP_FREE_TYPES,
+    STATIC_LIFETIME,
 };
+use fxhash::FxHashMap;
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use ref_cast::RefCast;
+use ref_cast::{ref_cast_custom, RefCast, RefCastCustom};
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use syn::visit::{self, Visit};
 use syn::TypePath;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, RefCastCustom)]
 #[repr(C)]
 pub struct Type(pub(crate) TypeNode);
 
+/// A type erased to a trait object by `Type::erase_to_trait_object`. The type
+/// it was erased from is kept, e.g. to generate a downcast back to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErasedType {
+    trait_object: Type,
+    original: Type,
+}
+
+impl ErasedType {
+    /// The trait object `dyn bounds`
+    pub fn trait_object(&self) -> &Type {
+        &self.trait_object
+    }
+
+    /// The type that was erased
+    pub fn original(&self) -> &Type {
+        &self.original
+    }
+}
+
+/// Options for the generated code. Set with
+/// `Execution::set_codegen_options`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodegenOptions {
+    /// Name the types of the standard library through `::core` and `::alloc`
+    /// instead of `::std`, for `no_std` crates
+    pub prefer_core: bool,
+    /// Keep the lifetimes that were elided in a reflected signature elided
+    /// in the generated one, e.g. `fn get(&self) -> &str` instead of
+    /// `fn get<'__a1>(&'__a1 self) -> &'__a1 str`
+    pub preserve_elided_lifetimes: bool,
+    /// Where the bounds of the generic params of an impl are written
+    pub where_clause_style: WhereClauseStyle,
+}
+
+/// Where the bounds of the generic params of an impl are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WhereClauseStyle {
+    /// All of them in the `where` clause: `impl<T> Trait for Foo<T> where
+    /// T: Clone`
+    #[default]
+    WhereClause,
+    /// The bounds on a param next to the param: `impl<T: Clone> Trait for
+    /// Foo<T>`. Other predicates, like `Vec<T>: Debug` or one with a
+    /// `for<'a>`, are still written in the `where` clause.
+    Inline,
+}
+
+/// The names of the primitive types, which are represented as single segment
+/// paths, except for `str` which has its own `TypeNode::PrimitiveStr`
+pub(crate) const PRIMITIVE_TYPES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+    "i128", "isize", "f32", "f64",
+];
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum TypeNode {
     Infer,
@@ -24,10 +89,81 @@ pub(crate) enum TypeNode {
     },
     Dereference(Box<TypeNode>),
     TraitObject(Vec<TypeParamBound>),
-    ImplTrait(Vec<TypeParamBound>),
+    /// `impl Trait`, with the generic params of a `+ use<'a, T>`
+    /// precise-capturing bound if there is one
+    ImplTrait(Vec<TypeParamBound>, Option<Vec<GenericParam>>),
     DataStructure(Box<DataStructure>),
     Path(Path),
     TypeParam(TypeParam),
+    /// An associated type of a trait impl: `<Vec<T> as IntoIterator>::Item`.
+    /// The trait is `None` for the shorthand `T::Item` of a type param, where
+    /// it follows from the bounds of the param.
+    Projection {
+        self_ty: Box<TypeNode>,
+        trait_path: Option<Box<Path>>,
+        assoc: Ident,
+    },
+    /// A slice `[T]`
+    Slice(Box<TypeNode>),
+    /// An array `[T; N]`. The length is kept as the tokens of its expression,
+    /// e.g. `4` or `SIZE * 2`, and two arrays only have the same length if
+    /// these tokens are the same.
+    Array(Box<TypeNode>, Verbatim),
+    /// Tokens that do not parse as a structured type, kept as they are and
+    /// only equal to the same tokens
+    Verbatim(Verbatim),
+    /// A function pointer `fn(A, B) -> C`
+    BareFn(Box<ParenthesizedGenericArguments>),
+    /// The never type `!` of a diverging expression, which coerces to any
+    /// other type
+    Never,
+}
+
+/// Only a projection of a single associated type, like `<T as Trait>::Assoc`
+/// or `<T>::Assoc`, can be reflected. A longer one such as
+/// `<T as Trait>::A::B` in the input is reported instead of converted.
+pub(crate) fn check_projections(input: &syn::DeriveInput) -> Result<(), ReflectError> {
+    struct CheckProjections(Option<syn::Error>);
+
+    impl<'ast> Visit<'ast> for CheckProjections {
+        fn visit_type_path(&mut self, ty: &'ast TypePath) {
+            if let Some(qself) = &ty.qself {
+                if ty.path.segments.len() != qself.position + 1 && self.0.is_none() {
+                    self.0 = Some(syn::Error::new_spanned(
+                        ty,
+                        "only projections of a single associated type, like \
+                         `<T as Trait>::Assoc`, are supported",
+                    ));
+                }
+            }
+            visit::visit_type_path(self, ty);
+        }
+    }
+
+    let mut check = CheckProjections(None);
+    check.visit_derive_input(input);
+    match check.0 {
+        Some(error) => Err(ReflectError::UnsupportedProjection { error }),
+        None => Ok(()),
+    }
+}
+
+/// Compared by the string of its tokens, as `TokenStream` has no equality
+#[derive(Debug, Clone)]
+pub(crate) struct Verbatim(pub(crate) TokenStream);
+
+impl PartialEq for Verbatim {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Eq for Verbatim {}
+
+impl Hash for Verbatim {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_string().hash(state);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -35,6 +171,10 @@ pub(crate) struct DataStructure {
     pub name: Ident,
     pub generics: Generics,
     pub data: Data<Type>,
+    /// Whether the type is `#[non_exhaustive]`. Such a type from another
+    /// crate can not be built with a struct literal, and a `match` on it
+    /// needs a wildcard arm.
+    pub non_exhaustive: bool,
 }
 
 impl Type {
@@ -52,6 +192,226 @@ impl Type {
         Type(TypeNode::PrimitiveStr)
     }
 
+    /// Returns the never type `!`
+    pub fn never() -> Self {
+        Type(TypeNode::Never)
+    }
+
+    /// Whether this is the never type `!`, the type of a diverging
+    /// expression. Only the type itself is checked, so `Option<!>` is not.
+    pub fn is_never(&self) -> bool {
+        self.0 == TypeNode::Never
+    }
+
+    /// The type that a value of this type has where `target` is expected:
+    /// `target` if this is the never type `!`, which coerces to anything, and
+    /// otherwise this type itself
+    pub fn coerce_to(&self, target: &Type) -> Type {
+        if self.is_never() {
+            target.clone()
+        } else {
+            self.clone()
+        }
+    }
+
+    /// Returns the slice type `[self]`
+    pub fn slice(&self) -> Self {
+        Type(TypeNode::Slice(Box::new(self.0.clone())))
+    }
+
+    /// Returns the array type `[self; len]`, where `len` is the expression
+    /// of the length, e.g. `"4"` or `"SIZE * 2"`
+    pub fn array(&self, len: &str) -> Self {
+        let len: syn::Expr = syn::parse_str(len).expect("Type::array: Not an expression");
+        Type(TypeNode::Array(
+            Box::new(self.0.clone()),
+            Verbatim(len.into_token_stream()),
+        ))
+    }
+
+    /// Returns the type `::core::result::Result<ok, err>`
+    pub fn result(ok: &Self, err: &Self) -> Self {
+        library_type("core", "result", "Result", &[ok, err])
+    }
+
+    /// Returns the type `::std::option::Option<some>`, or
+    /// `::core::option::Option<some>` with `CodegenOptions::prefer_core`
+    pub fn option(some: &Self) -> Self {
+        library_type(std_or("core"), "option", "Option", &[some])
+    }
+
+    /// Returns the type `::std::vec::Vec<elem>`, or `::alloc::vec::Vec<elem>`
+    /// with `CodegenOptions::prefer_core`
+    pub fn vec(elem: &Self) -> Self {
+        library_type(std_or("alloc"), "vec", "Vec", &[elem])
+    }
+
+    /// Returns the `Ok` and `Err` types of a `Result<Ok, Err>`, or `None` if
+    /// the type is not a `Result` with two type arguments.
+    pub fn result_types(&self) -> Option<(&Self, &Self)> {
+        let segment = match &self.0 {
+            TypeNode::Path(path) => path.path.last()?,
+            _ => return None,
+        };
+        if segment.ident != Ident::new("Result") {
+            return None;
+        }
+        match &segment.args {
+            PathArguments::AngleBracketed(args) => match args.args.args.as_slice() {
+                [GenericArgument::Type(ok), GenericArgument::Type(err)] => Some((ok, err)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the type argument of an `Option<T>`, or `None` if the type is
+    /// not an `Option` with one type argument.
+    pub fn option_type(&self) -> Option<&Self> {
+        let segment = match &self.0 {
+            TypeNode::Path(path) => path.path.last()?,
+            _ => return None,
+        };
+        if segment.ident != Ident::new("Option") {
+            return None;
+        }
+        match &segment.args {
+            PathArguments::AngleBracketed(args) => match args.args.args.as_slice() {
+                [GenericArgument::Type(some)] => Some(some),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the input types and the output type of a function pointer
+    /// `fn(A, B) -> C`, or `None` if the type is not one. The output is
+    /// `None` for a function written without `->`.
+    pub fn as_bare_fn(&self) -> Option<(Vec<&Self>, Option<&Self>)> {
+        match &self.0 {
+            TypeNode::BareFn(args) => Some((args.inputs.iter().collect(), args.output.as_ref())),
+            _ => None,
+        }
+    }
+
+    /// Whether this is one of the primitive types, like `u32`, `bool` or `str`
+    pub fn is_primitive(&self) -> bool {
+        match &self.0 {
+            TypeNode::PrimitiveStr => true,
+            TypeNode::Path(path) => path.is_primitive(),
+            _ => false,
+        }
+    }
+
+    /// Whether this is `str`, possibly behind references like `&str` or
+    /// `&&'a str`
+    pub fn is_str(&self) -> bool {
+        let mut node = &self.0;
+        while let TypeNode::Reference { inner, .. } = node {
+            node = inner;
+        }
+        match node {
+            TypeNode::PrimitiveStr => true,
+            TypeNode::Path(path) => path.is_primitive() && path.path[0].ident == Ident::new("str"),
+            _ => false,
+        }
+    }
+
+    /// Whether this is a path to `String` without generic arguments, like
+    /// `String` or `::std::string::String`. A reference `&String` is not.
+    pub fn is_string(&self) -> bool {
+        match &self.0 {
+            TypeNode::Path(path) => path.path.last().is_some_and(|segment| {
+                segment.ident == Ident::new("String") && segment.args == PathArguments::None
+            }),
+            _ => false,
+        }
+    }
+
+    /// A best-effort guess whether the type is not `Sized`, without solving
+    /// any trait bounds: true for `str`, slices, bare trait objects and type
+    /// params that `generics` bounds by `?Sized`. A false result does not
+    /// prove that the type is `Sized`, e.g. a path may name a type alias of
+    /// `str`.
+    pub fn is_definitely_unsized(&self, generics: &Generics) -> bool {
+        match &self.0 {
+            TypeNode::PrimitiveStr | TypeNode::Slice(_) | TypeNode::TraitObject(_) => true,
+            TypeNode::Path(path) => path.is_primitive() && path.path[0].ident == Ident::new("str"),
+            TypeNode::TypeParam(_) => generics.relaxes_sized(self),
+            _ => false,
+        }
+    }
+
+    /// Whether the type is known to be `Copy`, so that generated code can copy
+    /// it instead of calling `.clone()`: true for primitives other than `str`,
+    /// shared references, tuples of known `Copy` types and types registered
+    /// with `Execution::register_copy_type`. A false result does not prove
+    /// that the type is not `Copy`.
+    pub fn is_known_copy(&self) -> bool {
+        self.0.is_known_copy()
+    }
+
+    /// Whether the type may implement `Drop`, in which case generated code
+    /// cannot move out of its fields and has to clone them or go through
+    /// `ManuallyDrop` instead. False for primitives, references, tuples,
+    /// arrays, slices, function pointers and types registered with
+    /// `Execution::register_drop_free_type`. Any other type, including the
+    /// target type of the derive, is assumed to implement `Drop`.
+    pub fn may_impl_drop(&self) -> bool {
+        self.0.may_impl_drop()
+    }
+
+    /// The distinct type params mentioned anywhere in the type, in the order
+    /// they first appear, e.g. `[U, T]` for `Result<(U, &T), Vec<U>>`. These
+    /// are the generics that an item using the type needs in scope.
+    pub fn type_params(&self) -> Vec<Type> {
+        let mut params = Vec::new();
+        self.0.any_node(&mut |node| {
+            match node {
+                TypeNode::TypeParam(param) => params.push(*param),
+                TypeNode::DataStructure(data) => params.extend(
+                    data.generics
+                        .params
+                        .iter()
+                        .filter_map(|param| param.type_param()),
+                ),
+                _ => {}
+            }
+            false
+        });
+        let mut seen = BTreeSet::new();
+        params
+            .into_iter()
+            .filter(|param| seen.insert(*param))
+            .map(|param| Type(TypeNode::TypeParam(param)))
+            .collect()
+    }
+
+    /// Whether the type mentions a type param or has generic arguments
+    /// anywhere, e.g. `T`, `Vec<u8>` or `&Wrapper<'a>`. This is about the
+    /// shape of the type, not about whether it is fully known: `Vec<u8>` is
+    /// generic although it has no params left, see `type_params` for those.
+    pub fn is_generic(&self) -> bool {
+        self.0.any_node(&mut |node| match node {
+            TypeNode::TypeParam(_) => true,
+            TypeNode::Path(path) => path.path.iter().any(|segment| match &segment.args {
+                PathArguments::None => false,
+                PathArguments::AngleBracketed(args) => !args.args.args.is_empty(),
+                PathArguments::Parenthesized(_) => true,
+            }),
+            TypeNode::DataStructure(data) => !data.generics.params.is_empty(),
+            _ => false,
+        })
+    }
+
+    /// Whether this is a struct or enum marked `#[non_exhaustive]`
+    pub fn is_non_exhaustive(&self) -> bool {
+        match &self.0 {
+            TypeNode::DataStructure(data) => data.non_exhaustive,
+            _ => false,
+        }
+    }
+
     pub fn reference(&self) -> Self {
         Type(TypeNode::Reference {
             is_mut: false,
@@ -88,6 +448,19 @@ impl Type {
         })
     }
 
+    #[ref_cast_custom]
+    fn from_node_ref(node: &TypeNode) -> &Self;
+
+    /// The type behind a `&` or `&mut`, or `None` if the type is not a
+    /// reference. Borrows from `self` instead of cloning the way
+    /// `dereference` does, for when the target only needs to be inspected.
+    pub fn as_reference_inner(&self) -> Option<&Self> {
+        match &self.0 {
+            TypeNode::Reference { inner, .. } => Some(Type::from_node_ref(inner)),
+            _ => None,
+        }
+    }
+
     pub fn dereference(&self) -> Self {
         match &self.0 {
             TypeNode::Reference { inner, .. } => Type((**inner).clone()),
@@ -95,6 +468,17 @@ impl Type {
         }
     }
 
+    /// Removes one level of reference, returning the referenced type and
+    /// whether the reference was `&mut`, or `None` if the type is not a
+    /// reference. Unlike `dereference`, this keeps the kind of borrow, so
+    /// that it can be recreated around another type.
+    pub fn strip_one_reference(&self) -> Option<(Self, bool)> {
+        match &self.0 {
+            TypeNode::Reference { is_mut, inner, .. } => Some((Type((**inner).clone()), *is_mut)),
+            _ => None,
+        }
+    }
+
     pub fn data(&self) -> Data<Self> {
         match &self.0 {
             TypeNode::DataStructure(data) => data.data.clone().map(|field| field.element),
@@ -128,22 +512,145 @@ impl Type {
         }
     }
 
+    /// Returns all the element types of a `Tuple`, looking through any
+    /// references to it, or `None` if the type is not a tuple.
+    ///
+    /// The elements are returned as an iterator rather than a `&[Type]`,
+    /// since a tuple holds its elements as bare nodes that can only be
+    /// borrowed as a `Type` one at a time.
+    ///
+    /// ```
+    /// use reflect::runtime::RuntimeType;
+    /// use reflect::{Path, SynParamMap, Type};
+    ///
+    /// let mut param_map = SynParamMap::new();
+    /// let numbers: Vec<Type> = ["u8", "u16", "u32"]
+    ///     .iter()
+    ///     .map(|name| Path::path_from_str(name, &mut param_map).SELF())
+    ///     .collect();
+    /// let tuple = Type::tuple(&numbers).reference();
+    /// assert!(tuple.tuple_elements().unwrap().eq(&numbers));
+    ///
+    /// // The total width in bits, from the names of the elements
+    /// let bits: u32 = tuple
+    ///     .tuple_elements()
+    ///     .unwrap()
+    ///     .map(|element| element.display(&param_map)[1..].parse::<u32>().unwrap())
+    ///     .sum();
+    /// assert_eq!(bits, 56);
+    /// assert!(Type::primitive_str().tuple_elements().is_none());
+    /// ```
+    pub fn tuple_elements(&self) -> Option<impl ExactSizeIterator<Item = &Type>> {
+        let mut node = &self.0;
+        while let TypeNode::Reference { inner, .. } = node {
+            node = inner;
+        }
+        match node {
+            TypeNode::Tuple(types) => Some(types.iter().map(Type::from_node_ref)),
+            _ => None,
+        }
+    }
+
+    /// Each string may contain a single bound or several bounds joined by
+    /// `+`, so `&["Read + Write"]` and `&["Read", "Write"]` are equivalent.
     pub fn get_trait_object(type_param_bounds: &[&str], param_map: &mut SynParamMap) -> Self {
         Type(TypeNode::TraitObject(
             type_param_bounds
                 .iter()
-                .map(|bound| TypeParamBound::get_type_param_bound(bound, param_map))
+                .flat_map(|bounds| TypeParamBound::get_type_param_bounds(bounds, param_map))
                 .collect(),
         ))
     }
 
+    /// Returns the trait object `dyn bounds` that the type can be erased to,
+    /// e.g. to store it as a `Box<dyn Display>`, along with the type itself.
+    /// The bounds are given as for
+    /// `get_trait_object`. The lifetimes the type borrows with, like the `'a`
+    /// of `&'a str`, are added as bounds, since a trait object is otherwise
+    /// assumed to be `'static`.
+    ///
+    /// Panics if the type is definitely unsized, as it can not be coerced to
+    /// a trait object.
+    pub fn erase_to_trait_object(
+        &self,
+        bounds: &[&str],
+        param_map: &mut SynParamMap,
+    ) -> ErasedType {
+        assert!(
+            !self.is_definitely_unsized(&Generics::default()),
+            "Type::erase_to_trait_object: Unsized type"
+        );
+        let mut lifetimes = Vec::new();
+        self.0.any_node(&mut |node| {
+            match node {
+                TypeNode::Reference {
+                    lifetime: Some(lifetime),
+                    ..
+                } => lifetimes.push(*lifetime),
+                TypeNode::DataStructure(data) => lifetimes.extend(
+                    data.generics
+                        .params
+                        .iter()
+                        .filter_map(|param| param.lifetime()),
+                ),
+                TypeNode::Path(path) => lifetimes.extend(
+                    path.generic_arguments()
+                        .iter()
+                        .filter_map(GenericArgument::as_lifetime),
+                ),
+                _ => {}
+            }
+            false
+        });
+        let mut trait_object = Self::get_trait_object(bounds, param_map);
+        if let TypeNode::TraitObject(bounds) = &mut trait_object.0 {
+            let mut seen = BTreeSet::new();
+            bounds.extend(
+                lifetimes
+                    .into_iter()
+                    .filter(|&lifetime| {
+                        lifetime != STATIC_LIFETIME
+                            && lifetime != ANONYMOUS_LIFETIME
+                            && seen.insert(lifetime)
+                    })
+                    .map(TypeParamBound::Lifetime),
+            );
+        }
+        ErasedType {
+            trait_object,
+            original: self.clone(),
+        }
+    }
+
+    /// Returns the associated type projection `<self_ty as trait_path>::assoc`
+    pub fn projection(self_ty: &Self, trait_path: Path, assoc: &str) -> Self {
+        Type(TypeNode::Projection {
+            self_ty: Box::new(self_ty.0.clone()),
+            trait_path: Some(Box::new(trait_path)),
+            assoc: Ident::new(assoc),
+        })
+    }
+
+    /// Returns the type `::std::boxed::Box<dyn bounds>`, where the bounds
+    /// are given as for `get_trait_object`. The box is `::alloc::boxed::Box`
+    /// with `CodegenOptions::prefer_core`.
+    pub fn boxed_dyn(type_param_bounds: &[&str], param_map: &mut SynParamMap) -> Self {
+        let trait_object = Self::get_trait_object(type_param_bounds, param_map);
+        library_type(std_or("alloc"), "boxed", "Box", &[&trait_object])
+    }
+
+    /// Returns the type `impl bounds`. A bound of the form `use<'a, T>`
+    /// lists the generic params that the type captures.
     pub fn get_impl_trait(type_param_bounds: &[&str], param_map: &mut SynParamMap) -> Self {
-        Type(TypeNode::ImplTrait(
-            type_param_bounds
-                .iter()
-                .map(|bound| TypeParamBound::get_type_param_bound(bound, param_map))
-                .collect(),
-        ))
+        let mut captures = None;
+        let mut bounds = Vec::new();
+        for bound in type_param_bounds {
+            match captured_params(bound, param_map) {
+                Some(params) => captures = Some(params),
+                None => bounds.push(TypeParamBound::get_type_param_bound(bound, param_map)),
+            }
+        }
+        Type(TypeNode::ImplTrait(bounds, captures))
     }
 
     pub fn type_param_from_str(type_param: &str, param_map: &mut SynParamMap) -> Self {
@@ -158,13 +665,318 @@ impl Type {
         }
     }
 
+    /// The type with its type params replaced by `_` and its lifetimes other
+    /// than `'static` by `'_`
+    pub(crate) fn without_generated_names(&self) -> Self {
+        fn anonymous(lifetime: &mut Lifetime) {
+            if *lifetime != STATIC_LIFETIME {
+                *lifetime = ANONYMOUS_LIFETIME;
+            }
+        }
+        fn anonymous_bounds(bounds: &mut [TypeParamBound]) {
+            for bound in bounds {
+                if let TypeParamBound::Lifetime(lifetime) = bound {
+                    anonymous(lifetime);
+                }
+            }
+        }
+
+        let mut node = self.0.clone();
+        node.walk_types_mut(&mut |node| {
+            match node {
+                TypeNode::TypeParam(_) => *node = TypeNode::Infer,
+                TypeNode::Reference {
+                    lifetime: Some(lifetime),
+                    ..
+                } => anonymous(lifetime),
+                TypeNode::TraitObject(bounds) | TypeNode::ImplTrait(bounds, _) => {
+                    anonymous_bounds(bounds)
+                }
+                TypeNode::Path(path) => {
+                    for segment in &mut path.path {
+                        if let PathArguments::AngleBracketed(args) = &mut segment.args {
+                            for arg in &mut args.args.args {
+                                match arg {
+                                    GenericArgument::Lifetime(lifetime) => anonymous(lifetime),
+                                    GenericArgument::Constraint(constraint) => {
+                                        anonymous_bounds(&mut constraint.bounds)
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            true
+        });
+        Type(node)
+    }
+
+    /// Render the type using the names its type params and lifetimes have in
+    /// `param_map`, e.g. `Wrapper < T >` instead of `Wrapper < __T3 >`.
+    /// Params that are not in `param_map` keep their generated names.
+    pub fn display(&self, param_map: &SynParamMap) -> String {
+        let names = param_names(param_map);
+        with_param_names(names, || {
+            Print::ref_cast(self).to_token_stream().to_string()
+        })
+    }
+
+    /// Render the type as it has to be written in expression position, with a
+    /// turbofish before the generic args of a path, e.g. `Vec :: < u32 >` for
+    /// `Vec<u32>`, so it can be spliced in front of `::new()`. Types that are
+    /// not paths are rendered as usual.
+    pub fn to_turbofish_string(&self) -> String {
+        match &self.0 {
+            TypeNode::Path(path) => Turbofish::ref_cast(path).to_token_stream().to_string(),
+            _ => Print::ref_cast(self).to_token_stream().to_string(),
+        }
+    }
+
+    /// Returns the type with all of its lifetimes removed, leaving them to be
+    /// inferred by the compiler, e.g. `&str` instead of `&'a str`.
+    pub fn elide_lifetimes(&self) -> Self {
+        let mut node = self.0.clone();
+        node.elide_lifetimes();
+        Type(node)
+    }
+
+    /// Returns the type with every `_` inside of it replaced by `ty`, e.g.
+    /// `Vec<_>` becomes `Vec<u32>` for `ty` = `u32`. This seeds types that
+    /// are partially known before the trait inference runs.
+    pub fn replace_infer(&self, ty: &Type) -> Self {
+        let mut node = self.0.clone();
+        node.replace_infer(&ty.0);
+        Type(node)
+    }
+
+    /// Returns the type with the type params that are keys of `map` replaced
+    /// by their values, e.g. `(T, Vec<U>)` becomes `(u32, Vec<String>)` for
+    /// `{T: u32, U: String}`. Params that are not in `map` are left as they
+    /// are.
+    ///
+    /// Panics if a key of `map` is not a type param.
+    pub fn substitute(&self, map: &HashMap<Type, Type>) -> Self {
+        let bindings = map
+            .iter()
+            .map(|(param, ty)| match param.0 {
+                TypeNode::TypeParam(param) => (param, ty.0.clone()),
+                _ => panic!("Type::substitute: Not a type param"),
+            })
+            .collect();
+        let mut node = self.0.clone();
+        node.substitute(&bindings);
+        Type(node)
+    }
+
+    /// Returns the type with `f` applied to every path inside of it, e.g. to
+    /// rewrite `crate::Foo` to `other_crate::Foo`. The generic arguments of
+    /// a path are mapped before the path itself.
+    pub fn map_paths<F>(&self, f: F) -> Self
+    where
+        F: Fn(&Path) -> Path,
+    {
+        let mut node = self.0.clone();
+        node.walk_paths_mut(&mut |path| *path = f(path));
+        Type(node)
+    }
+
+    /// Calls `f` on every path inside of the type, allowing it to change the
+    /// path in place, e.g. to rename the crate of every path without building
+    /// a new type. Paths are visited in the same order as by `map_paths`.
+    pub fn walk_paths_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Path),
+    {
+        self.0.walk_paths_mut(&mut f);
+    }
+
+    /// Hashes the structure of the type, using the names its type params and
+    /// lifetimes have in `param_map`.
+    ///
+    /// The derived `Hash` hashes the interned indices of type params and
+    /// lifetimes, so the same type built in two different sessions hashes
+    /// differently. This hash renumbers the params in the order in which they
+    /// first appear and hashes the renumbered type together with the names of
+    /// the params, so it only depends on the shape of the type, which makes
+    /// it suitable as a cache key across sessions. Params that are not in
+    /// `param_map` are hashed by their position alone. A data structure is
+    /// hashed by its name and its generic args, like the path naming it.
+    pub fn structural_hash<H: Hasher>(&self, param_map: &SynParamMap, state: &mut H) {
+        let names = param_names(param_map);
+        let mut node = self.0.clone();
+
+        // The fields and generics of a data structure hold interned params
+        // of their own, so it is hashed as the path `Name<args>` instead
+        node.walk_types_mut(&mut |node| {
+            if let TypeNode::DataStructure(data) = node {
+                *node = TypeNode::Path(Path {
+                    global: false,
+                    path: vec![PathSegment {
+                        ident: data.name.clone(),
+                        args: params_to_args(&data.generics.params),
+                    }],
+                });
+            }
+            true
+        });
+
+        let mut type_params = Vec::new();
+        let mut normalize_type_param = |param: &mut TypeParam| {
+            let position = match type_params.iter().position(|seen| seen == param) {
+                Some(position) => position,
+                None => {
+                    type_params.push(*param);
+                    type_params.len() - 1
+                }
+            };
+            *param = TypeParam(position);
+        };
+        node.walk_types_mut(&mut |node| {
+            match node {
+                TypeNode::TypeParam(param) => normalize_type_param(param),
+                TypeNode::ImplTrait(_, Some(captures)) => {
+                    for capture in captures {
+                        if let GenericParam::Type(param) = capture {
+                            normalize_type_param(param);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            true
+        });
+
+        // The renumbered lifetimes start at 1 to keep clear of `'static`
+        let mut lifetimes = Vec::new();
+        node.walk_lifetimes_mut(&mut |lifetime| {
+            if *lifetime == STATIC_LIFETIME || *lifetime == ANONYMOUS_LIFETIME {
+                return;
+            }
+            let position = match lifetimes.iter().position(|seen| seen == lifetime) {
+                Some(position) => position,
+                None => {
+                    lifetimes.push(*lifetime);
+                    lifetimes.len() - 1
+                }
+            };
+            *lifetime = Lifetime(position + 1);
+        });
+
+        node.hash(state);
+        for param in type_params.into_iter().map(GenericParam::Type) {
+            names.get(&param).hash(state);
+        }
+        for param in lifetimes.into_iter().map(GenericParam::Lifetime) {
+            names.get(&param).hash(state);
+        }
+    }
+
+    /// Render the structure of the type as an indented tree with one node
+    /// per line, e.g. to see which part of a nested type the inference made
+    /// concrete. Type params and lifetimes are shown by the names they have
+    /// in `param_map`, and by their generated names, like `__T3` and
+    /// `'__a1`, if they are not in it.
+    pub fn debug_tree(&self, param_map: &SynParamMap) -> String {
+        with_param_names(param_names(param_map), || self.tree())
+    }
+
+    fn tree(&self) -> String {
+        let mut out = String::new();
+        self.0.write_tree(0, &mut out);
+        out
+    }
+
+    /// Describes how the type differs from `other`, with both types printed
+    /// and rendered as a `debug_tree` with generated param names, or `None`
+    /// if they are equal. This is more readable than the derived `Debug` in a
+    /// failing test.
+    pub fn pretty_diff(&self, other: &Type) -> Option<String> {
+        if self == other {
+            return None;
+        }
+        Some(format!(
+            "types are not equal\n  left: {}\n right: {}\nleft tree:\n{}right tree:\n{}",
+            Print::ref_cast(self).to_token_stream(),
+            Print::ref_cast(other).to_token_stream(),
+            self.tree(),
+            other.tree(),
+        ))
+    }
+
+    /// Asserts that the type equals `other`, panicking with the
+    /// `pretty_diff` of the two if it does not
+    #[track_caller]
+    pub fn assert_eq_pretty(&self, other: &Type) {
+        if let Some(diff) = self.pretty_diff(other) {
+            panic!("{}", diff);
+        }
+    }
+
     pub(crate) fn syn_to_type(ty: syn::Type, param_map: &mut SynParamMap) -> Self {
         match ty {
             syn::Type::Path(TypePath {
-                //FIXME: add qself to Path
-                qself: None,
-                path,
+                qself: Some(qself),
+                mut path,
             }) => {
+                let self_ty = Self::syn_to_type(*qself.ty, param_map).0;
+                let assoc = path
+                    .segments
+                    .pop()
+                    .expect("syn_to_type: Missing associated type")
+                    .into_value();
+                assert_eq!(
+                    path.segments.len(),
+                    qself.position,
+                    "syn_to_type: Only projections of a single associated type are supported, \
+                     see check_projections"
+                );
+                // `<T>::Assoc` names no trait
+                let trait_path = if qself.position == 0 {
+                    None
+                } else {
+                    let trait_path = syn::Path {
+                        leading_colon: path.leading_colon,
+                        segments: path
+                            .segments
+                            .into_pairs()
+                            .map(|pair| pair.into_value())
+                            .collect(),
+                    };
+                    Some(Box::new(Path::syn_to_path(trait_path, param_map)))
+                };
+                Type(TypeNode::Projection {
+                    self_ty: Box::new(self_ty),
+                    trait_path,
+                    assoc: Ident::from(assoc.ident),
+                })
+            }
+
+            // The shorthand projection `T::Item` of a type param
+            syn::Type::Path(TypePath { qself: None, path })
+                if path.leading_colon.is_none()
+                    && path.segments.len() == 2
+                    && path.segments[0].arguments.is_empty()
+                    && path.segments[1].arguments.is_empty()
+                    && param_map
+                        .get(&path.segments[0].ident.to_string())
+                        .and_then(|param| param.type_param())
+                        .is_some() =>
+            {
+                let param = param_map
+                    .get(&path.segments[0].ident.to_string())
+                    .and_then(|param| param.type_param())
+                    .unwrap();
+                Type(TypeNode::Projection {
+                    self_ty: Box::new(TypeNode::TypeParam(param)),
+                    trait_path: None,
+                    assoc: Ident::from(path.segments[1].ident.clone()),
+                })
+            }
+
+            syn::Type::Path(TypePath { qself: None, path }) => {
                 if let Some(ident) = path.get_ident() {
                     if let Some(&param) = param_map.get(&ident.to_string()) {
                         return Type(TypeNode::TypeParam(
@@ -194,6 +1006,13 @@ impl Type {
                 generics::syn_to_type_param_bounds(type_trait_object.bounds, param_map).collect(),
             )),
 
+            // Nested in a bound, like the `impl Display` of
+            // `impl Iterator<Item = impl Display>`
+            syn::Type::ImplTrait(type_impl_trait) => Type(TypeNode::ImplTrait(
+                generics::syn_to_type_param_bounds(type_impl_trait.bounds, param_map).collect(),
+                None,
+            )),
+
             syn::Type::Tuple(type_tuple) => {
                 if type_tuple.elems.is_empty() {
                     Type::unit()
@@ -211,6 +1030,47 @@ impl Type {
                     ))
                 }
             }
+
+            syn::Type::Infer(_) => Type(TypeNode::Infer),
+
+            syn::Type::Never(_) => Type(TypeNode::Never),
+
+            syn::Type::Slice(slice) => Type(TypeNode::Slice(Box::new(
+                Self::syn_to_type(*slice.elem, param_map).0,
+            ))),
+
+            syn::Type::Array(array) => Type(TypeNode::Array(
+                Box::new(Self::syn_to_type(*array.elem, param_map).0),
+                Verbatim(array.len.into_token_stream()),
+            )),
+
+            syn::Type::Verbatim(tokens) => Type(TypeNode::Verbatim(Verbatim(tokens))),
+
+            // Only safe Rust functions without higher ranked lifetimes are
+            // structured, the others are kept as they are
+            syn::Type::BareFn(bare_fn)
+                if bare_fn.lifetimes.is_none()
+                    && bare_fn.unsafety.is_none()
+                    && bare_fn.abi.is_none()
+                    && bare_fn.variadic.is_none() =>
+            {
+                Type(TypeNode::BareFn(Box::new(ParenthesizedGenericArguments {
+                    inputs: bare_fn
+                        .inputs
+                        .into_iter()
+                        .map(|arg| Self::syn_to_type(arg.ty, param_map))
+                        .collect(),
+                    output: match bare_fn.output {
+                        syn::ReturnType::Default => None,
+                        syn::ReturnType::Type(_, ty) => Some(Self::syn_to_type(*ty, param_map)),
+                    },
+                })))
+            }
+
+            syn::Type::BareFn(bare_fn) => {
+                Type(TypeNode::Verbatim(Verbatim(bare_fn.into_token_stream())))
+            }
+
             _ => unimplemented!("Type::syn_to_type"),
         }
     }
@@ -220,7 +1080,248 @@ impl Type {
     }
 }
 
+/// `std`, or `krate` if the generated code should not depend on `std`
+fn std_or(krate: &'static str) -> &'static str {
+    if CODEGEN_OPTIONS.with(Cell::get).prefer_core {
+        krate
+    } else {
+        "std"
+    }
+}
+
+/// The type `::krate::module::name<args>` from one of the standard crates
+fn library_type(krate: &str, module: &str, name: &str, args: &[&Type]) -> Type {
+    let mut path = Path::root()
+        .get_simple_path(krate)
+        .get_simple_path(module)
+        .get_simple_path(name);
+    path.path.last_mut().unwrap().args =
+        PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+            args: GenericArguments {
+                args: args
+                    .iter()
+                    .map(|&arg| GenericArgument::Type(arg.clone()))
+                    .collect(),
+            },
+        });
+    Type(TypeNode::Path(path))
+}
+
+/// Maps the params in `param_map` to the names they have there, without the
+/// apostrophe of lifetimes
+pub(crate) fn param_names(param_map: &SynParamMap) -> FxHashMap<GenericParam, String> {
+    param_map
+        .map
+        .iter()
+        .filter_map(|(name, param)| match param {
+            GenericParam::Type(_) => Some((*param, name.clone())),
+            GenericParam::Lifetime(lifetime)
+                if *lifetime != STATIC_LIFETIME && *lifetime != ANONYMOUS_LIFETIME =>
+            {
+                Some((*param, name.trim_start_matches('\'').to_owned()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses a precise-capturing bound `use<'a, T>` into the params it lists,
+/// or returns `None` if the bound is not one
+fn captured_params(bound: &str, param_map: &SynParamMap) -> Option<Vec<GenericParam>> {
+    let params = bound
+        .trim()
+        .strip_prefix("use")?
+        .trim_start()
+        .strip_prefix('<')?
+        .strip_suffix('>')?;
+    Some(
+        params
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                *param_map
+                    .get(param)
+                    .expect("Type::get_impl_trait: use<..> lists an unknown param")
+            })
+            .collect(),
+    )
+}
+
 impl TypeNode {
+    fn write_tree(&self, depth: usize, out: &mut String) {
+        let line = |depth: usize, out: &mut String, text: String| {
+            out.push_str(&"    ".repeat(depth));
+            out.push_str(&text);
+            out.push('\n');
+        };
+        let tokens = |tokens: &dyn ToTokens| tokens.to_token_stream().to_string();
+        match self {
+            TypeNode::Infer => line(depth, out, "Infer".to_owned()),
+            TypeNode::Tuple(types) => {
+                line(depth, out, "Tuple".to_owned());
+                for ty in types {
+                    ty.write_tree(depth + 1, out);
+                }
+            }
+            TypeNode::PrimitiveStr => line(depth, out, "PrimitiveStr".to_owned()),
+            TypeNode::Never => line(depth, out, "Never".to_owned()),
+            TypeNode::BareFn(args) => {
+                line(depth, out, "BareFn".to_owned());
+                for input in &args.inputs {
+                    input.0.write_tree(depth + 1, out);
+                }
+                if let Some(output) = &args.output {
+                    line(depth + 1, out, "Output".to_owned());
+                    output.0.write_tree(depth + 2, out);
+                }
+            }
+            TypeNode::Reference {
+                is_mut,
+                lifetime,
+                inner,
+            } => {
+                let mut text = "Reference".to_owned();
+                if let Some(lifetime) = lifetime {
+                    text += &format!(" {}", tokens(Print::ref_cast(lifetime)));
+                }
+                if *is_mut {
+                    text += " mut";
+                }
+                line(depth, out, text);
+                inner.write_tree(depth + 1, out);
+            }
+            TypeNode::Dereference(inner) => {
+                line(depth, out, "Dereference".to_owned());
+                inner.write_tree(depth + 1, out);
+            }
+            TypeNode::TraitObject(bounds) | TypeNode::ImplTrait(bounds, _) => {
+                let kind = match self {
+                    TypeNode::TraitObject(_) => "TraitObject",
+                    _ => "ImplTrait",
+                };
+                line(depth, out, kind.to_owned());
+                for bound in bounds {
+                    line(
+                        depth + 1,
+                        out,
+                        format!("Bound {}", tokens(Print::ref_cast(bound))),
+                    );
+                }
+                if let TypeNode::ImplTrait(_, Some(captures)) = self {
+                    let captures = captures.iter().map(Print::ref_cast);
+                    line(depth + 1, out, format!("Use {}", quote!(#(#captures),*)));
+                }
+            }
+            TypeNode::DataStructure(data) => {
+                line(depth, out, format!("DataStructure {}", data.name));
+                for param in &data.generics.params {
+                    line(
+                        depth + 1,
+                        out,
+                        format!("Param {}", tokens(Print::ref_cast(param))),
+                    );
+                }
+            }
+            TypeNode::Path(path) => {
+                let segments: Vec<_> = path
+                    .path
+                    .iter()
+                    .map(|segment| segment.ident.to_string())
+                    .collect();
+                let leading = if path.global { "::" } else { "" };
+                line(
+                    depth,
+                    out,
+                    format!("Path {}{}", leading, segments.join("::")),
+                );
+                for segment in &path.path {
+                    match &segment.args {
+                        PathArguments::None => {}
+                        PathArguments::AngleBracketed(args) => {
+                            for arg in &args.args.args {
+                                match arg {
+                                    GenericArgument::Type(ty) => ty.0.write_tree(depth + 1, out),
+                                    arg => line(
+                                        depth + 1,
+                                        out,
+                                        format!("Argument {}", tokens(Print::ref_cast(arg))),
+                                    ),
+                                }
+                            }
+                        }
+                        PathArguments::Parenthesized(args) => {
+                            line(depth + 1, out, "Inputs".to_owned());
+                            for input in &args.inputs {
+                                input.0.write_tree(depth + 2, out);
+                            }
+                            line(depth + 1, out, "Output".to_owned());
+                            args.output_type().0.write_tree(depth + 2, out);
+                        }
+                    }
+                }
+            }
+            TypeNode::TypeParam(param) => line(
+                depth,
+                out,
+                format!("TypeParam {}", tokens(Print::ref_cast(param))),
+            ),
+            TypeNode::Projection {
+                self_ty,
+                trait_path,
+                assoc,
+            } => {
+                line(depth, out, format!("Projection {}", assoc));
+                self_ty.write_tree(depth + 1, out);
+                if let Some(trait_path) = trait_path {
+                    TypeNode::Path((**trait_path).clone()).write_tree(depth + 1, out);
+                }
+            }
+            TypeNode::Slice(elem) => {
+                line(depth, out, "Slice".to_owned());
+                elem.write_tree(depth + 1, out);
+            }
+            TypeNode::Array(elem, len) => {
+                line(depth, out, format!("Array {}", len.0));
+                elem.write_tree(depth + 1, out);
+            }
+            TypeNode::Verbatim(verbatim) => {
+                line(depth, out, format!("Verbatim {}", verbatim.0));
+            }
+        }
+    }
+
+    /// The data structure node that the type is or wraps, like `Foo<T>` in
+    /// `&Foo<T>` or `Box<Foo<T>>`
+    pub(crate) fn wrapped_data_structure(&self) -> Option<&TypeNode> {
+        match self {
+            TypeNode::DataStructure(_) => Some(self),
+            TypeNode::Reference { inner, .. } => inner.wrapped_data_structure(),
+            TypeNode::Path(path) => path
+                .generic_arguments()
+                .iter()
+                .find_map(|arg| arg.as_type()?.0.wrapped_data_structure()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn wrapped_data_structure_mut(&mut self) -> Option<&mut TypeNode> {
+        match self {
+            TypeNode::DataStructure(_) => Some(self),
+            TypeNode::Reference { inner, .. } => inner.wrapped_data_structure_mut(),
+            TypeNode::Path(path) => match &mut path.path.last_mut()?.args {
+                PathArguments::AngleBracketed(args) => {
+                    args.args.args.iter_mut().find_map(|arg| match arg {
+                        GenericArgument::Type(ty) => ty.0.wrapped_data_structure_mut(),
+                        _ => None,
+                    })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub(crate) fn get_name(&self) -> String {
         match self {
             //FIXME: Add more TypeNode branches
@@ -229,8 +1330,11 @@ impl TypeNode {
                 quote!((#(#types),*)).to_string()
             }
             TypeNode::PrimitiveStr => String::from("str"),
+            TypeNode::Never => String::from("!"),
+            TypeNode::Verbatim(verbatim) => verbatim.0.to_string(),
             TypeNode::DataStructure(data) => data.name.to_string(),
             TypeNode::Reference { inner, .. } => (&**inner).get_name(),
+            TypeNode::Array(..) => Print::ref_cast(self).to_token_stream().to_string(),
             TypeNode::Path(path) => {
                 let mut tokens = TokenStream::new();
                 Print::ref_cast(path).to_tokens(&mut tokens);
@@ -246,11 +1350,38 @@ impl TypeNode {
         }
     }
 
+    fn is_known_copy(&self) -> bool {
+        match self {
+            TypeNode::Reference { is_mut, .. } => !is_mut,
+            TypeNode::Tuple(types) => types.iter().all(TypeNode::is_known_copy),
+            TypeNode::Array(elem, _) => elem.is_known_copy(),
+            TypeNode::Path(path) if path.is_primitive() => path.path[0].ident != Ident::new("str"),
+            _ => COPY_TYPES.with(|types| types.borrow().contains(self)),
+        }
+    }
+
+    fn may_impl_drop(&self) -> bool {
+        match self {
+            // A `Drop` impl can only be written for a struct, enum or union
+            TypeNode::Reference { .. }
+            | TypeNode::Tuple(_)
+            | TypeNode::Array(..)
+            | TypeNode::Slice(_)
+            | TypeNode::BareFn(_)
+            | TypeNode::PrimitiveStr
+            | TypeNode::Never => false,
+            TypeNode::Path(path) if path.is_primitive() => false,
+            _ => !DROP_FREE_TYPES.with(|types| types.borrow().contains(self)),
+        }
+    }
+
     pub(crate) fn clone_with_fresh_generics(&self, param_map: &ParamMap) -> Self {
         use super::TypeNode::*;
         match self {
             Infer => Infer,
 
+            BareFn(args) => BareFn(Box::new(args.clone_with_fresh_generics(param_map))),
+
             Tuple(types) => Tuple(
                 types
                     .iter()
@@ -259,6 +1390,9 @@ impl TypeNode {
             ),
 
             PrimitiveStr => PrimitiveStr,
+            Never => Never,
+
+            Verbatim(verbatim) => Verbatim(verbatim.clone()),
 
             Reference {
                 is_mut,
@@ -275,6 +1409,13 @@ impl TypeNode {
                 Dereference(Box::new(dereference.clone_with_fresh_generics(param_map)))
             }
 
+            Slice(elem) => Slice(Box::new(elem.clone_with_fresh_generics(param_map))),
+
+            Array(elem, len) => Array(
+                Box::new(elem.clone_with_fresh_generics(param_map)),
+                len.clone(),
+            ),
+
             TraitObject(bounds) => TraitObject(
                 bounds
                     .iter()
@@ -282,11 +1423,17 @@ impl TypeNode {
                     .collect(),
             ),
 
-            ImplTrait(bounds) => ImplTrait(
+            ImplTrait(bounds, captures) => ImplTrait(
                 bounds
                     .iter()
                     .map(|bound| bound.clone_with_fresh_generics(param_map))
                     .collect(),
+                captures.as_ref().map(|params| {
+                    params
+                        .iter()
+                        .map(|param| *param_map.get(param).unwrap())
+                        .collect()
+                }),
             ),
 
             DataStructure { .. } => {
@@ -301,6 +1448,203 @@ impl TypeNode {
                     .and_then(|param| param.type_param())
                     .unwrap(),
             ),
+
+            Projection {
+                self_ty,
+                trait_path,
+                assoc,
+            } => Projection {
+                self_ty: Box::new(self_ty.clone_with_fresh_generics(param_map)),
+                trait_path: trait_path
+                    .as_ref()
+                    .map(|path| Box::new(path.clone_with_fresh_generics(param_map))),
+                assoc: assoc.clone(),
+            },
+        }
+    }
+
+    pub(crate) fn elide_lifetimes(&mut self) {
+        use TypeNode::*;
+        match self {
+            Tuple(types) => types.iter_mut().for_each(TypeNode::elide_lifetimes),
+            Reference {
+                lifetime, inner, ..
+            } => {
+                *lifetime = None;
+                inner.elide_lifetimes();
+            }
+            Dereference(inner) | Slice(inner) | Array(inner, _) => inner.elide_lifetimes(),
+            BareFn(args) => args.types_mut().for_each(|ty| ty.0.elide_lifetimes()),
+            TraitObject(bounds) | ImplTrait(bounds, _) => {
+                bounds.retain(|bound| match bound {
+                    TypeParamBound::Trait(_) => true,
+                    TypeParamBound::Lifetime(_) => false,
+                });
+                bounds.iter_mut().for_each(TypeParamBound::elide_lifetimes);
+                if let ImplTrait(_, Some(captures)) = self {
+                    captures.retain(|param| param.type_param().is_some());
+                }
+            }
+            Path(path) => path.elide_lifetimes(),
+            Projection {
+                self_ty,
+                trait_path,
+                ..
+            } => {
+                self_ty.elide_lifetimes();
+                if let Some(trait_path) = trait_path {
+                    trait_path.elide_lifetimes();
+                }
+            }
+            Infer | PrimitiveStr | Never | Verbatim(_) | DataStructure { .. } | TypeParam(_) => {}
+        }
+    }
+
+    pub(crate) fn replace_infer(&mut self, ty: &TypeNode) {
+        self.walk_types_mut(&mut |node| match node {
+            TypeNode::Infer => {
+                *node = ty.clone();
+                false
+            }
+            _ => true,
+        });
+    }
+
+    /// Replaces every type param that has a type in `bindings` with that type
+    pub(crate) fn substitute(&mut self, bindings: &BTreeMap<TypeParam, TypeNode>) {
+        self.walk_types_mut(&mut |node| match node {
+            TypeNode::TypeParam(param) => {
+                if let Some(ty) = bindings.get(param) {
+                    *node = ty.clone();
+                }
+                false
+            }
+            _ => true,
+        });
+    }
+
+    /// Replaces every `impl Trait` in the type with the type returned by `f`
+    /// for its bounds. An `impl Trait` nested in the bounds of another, like
+    /// `impl Iterator<Item = impl Display>`, is replaced first.
+    pub(crate) fn replace_impl_trait(
+        &mut self,
+        f: &mut dyn FnMut(Vec<TypeParamBound>) -> TypeNode,
+    ) {
+        self.walk_types_mut(&mut |node| match node {
+            TypeNode::ImplTrait(bounds, _) => {
+                let mut bounds = std::mem::take(bounds);
+                for bound in &mut bounds {
+                    bound.walk_types_mut(&mut |inner| {
+                        inner.replace_impl_trait(f);
+                        false
+                    });
+                }
+                *node = f(bounds);
+                false
+            }
+            _ => true,
+        });
+    }
+
+    /// Calls `f` on this node and then on every type nested in it, including
+    /// the ones in generic arguments, associated type bindings and trait
+    /// bounds. The nodes inside of a node are skipped if `f` returns false
+    /// for it, like when `f` replaced the node.
+    pub(crate) fn walk_types_mut(&mut self, f: &mut dyn FnMut(&mut TypeNode) -> bool) {
+        use TypeNode::*;
+        if !f(self) {
+            return;
+        }
+        match self {
+            Tuple(types) => types.iter_mut().for_each(|ty| ty.walk_types_mut(f)),
+            Reference { inner, .. } | Dereference(inner) | Slice(inner) | Array(inner, _) => {
+                inner.walk_types_mut(f)
+            }
+            BareFn(args) => args.types_mut().for_each(|ty| ty.0.walk_types_mut(f)),
+            TraitObject(bounds) | ImplTrait(bounds, _) => {
+                bounds.iter_mut().for_each(|bound| bound.walk_types_mut(f))
+            }
+            Path(path) => path.walk_types_mut(f),
+            Projection {
+                self_ty,
+                trait_path,
+                ..
+            } => {
+                self_ty.walk_types_mut(f);
+                if let Some(trait_path) = trait_path {
+                    trait_path.walk_types_mut(f);
+                }
+            }
+            Infer | PrimitiveStr | Never | Verbatim(_) | DataStructure { .. } | TypeParam(_) => {}
+        }
+    }
+
+    /// Calls `f` on every lifetime in the type, including the captures of an
+    /// `impl Trait` and the lifetimes bound by a `for<'a>`
+    pub(crate) fn walk_lifetimes_mut(&mut self, f: &mut dyn FnMut(&mut Lifetime)) {
+        use TypeNode::*;
+        match self {
+            Tuple(types) => types.iter_mut().for_each(|ty| ty.walk_lifetimes_mut(f)),
+            Reference {
+                lifetime, inner, ..
+            } => {
+                if let Some(lifetime) = lifetime {
+                    f(lifetime);
+                }
+                inner.walk_lifetimes_mut(f);
+            }
+            Dereference(inner) | Slice(inner) | Array(inner, _) => inner.walk_lifetimes_mut(f),
+            BareFn(args) => args.types_mut().for_each(|ty| ty.0.walk_lifetimes_mut(f)),
+            TraitObject(bounds) | ImplTrait(bounds, _) => {
+                bounds
+                    .iter_mut()
+                    .for_each(|bound| bound.walk_lifetimes_mut(f));
+                if let ImplTrait(_, Some(captures)) = self {
+                    for capture in captures {
+                        if let GenericParam::Lifetime(lifetime) = capture {
+                            f(lifetime);
+                        }
+                    }
+                }
+            }
+            Path(path) => path.walk_lifetimes_mut(f),
+            Projection {
+                self_ty,
+                trait_path,
+                ..
+            } => {
+                self_ty.walk_lifetimes_mut(f);
+                if let Some(trait_path) = trait_path {
+                    trait_path.walk_lifetimes_mut(f);
+                }
+            }
+            Infer | PrimitiveStr | Never | Verbatim(_) | DataStructure { .. } | TypeParam(_) => {}
+        }
+    }
+
+    pub(crate) fn walk_paths_mut(&mut self, f: &mut dyn FnMut(&mut Path)) {
+        use TypeNode::*;
+        match self {
+            Tuple(types) => types.iter_mut().for_each(|ty| ty.walk_paths_mut(f)),
+            Reference { inner, .. } | Dereference(inner) | Slice(inner) | Array(inner, _) => {
+                inner.walk_paths_mut(f)
+            }
+            BareFn(args) => args.types_mut().for_each(|ty| ty.0.walk_paths_mut(f)),
+            TraitObject(bounds) | ImplTrait(bounds, _) => {
+                bounds.iter_mut().for_each(|bound| bound.walk_paths_mut(f))
+            }
+            Path(path) => path.walk_paths_mut(f),
+            Projection {
+                self_ty,
+                trait_path,
+                ..
+            } => {
+                self_ty.walk_paths_mut(f);
+                if let Some(trait_path) = trait_path {
+                    trait_path.walk_paths_mut(f);
+                }
+            }
+            Infer | PrimitiveStr | Never | Verbatim(_) | DataStructure { .. } | TypeParam(_) => {}
         }
     }
 }
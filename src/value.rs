@@ -16,6 +16,16 @@ impl Value {
         }
     }
 
+    /// An array literal `[a, b, c]` of the values. Passed by reference it
+    /// can stand in for a `&[T]` argument, and the trait inference makes the
+    /// types of the values equal to each other and to `T`.
+    pub fn array(values: &[Self]) -> Self {
+        let node = ValueNode::Array(values.iter().map(|v| v.index).collect());
+        Value {
+            index: VALUES.index_push(node),
+        }
+    }
+
     pub fn reference(&self) -> Self {
         let node = ValueNode::Reference {
             is_mut: false,
@@ -48,6 +58,28 @@ impl Value {
         }
     }
 
+    /// Applies the `?` operator to a `Result` or an `Option`, giving the `Ok`
+    /// or `Some` value. The error of a `Result` is converted with `From` to
+    /// the error type of the function being made, and the trait inference
+    /// adds the `From` bound this needs.
+    pub fn try_unwrap(&self) -> Self {
+        let ty = self.get_type();
+        assert!(
+            ty.result_types().is_some() || ty.option_type().is_some(),
+            "Value::try_unwrap: not a Result or an Option"
+        );
+        let node = ValueNode::Try(self.index);
+        Value {
+            index: VALUES.index_push(node),
+        }
+    }
+
+    /// The type of the value, as far as it is known before the trait
+    /// inference
+    pub fn get_type(&self) -> Type {
+        self.index.get_type()
+    }
+
     pub fn get_type_name(&self) -> Self {
         let node = self.node().get_type_name();
         Value {
@@ -56,25 +88,36 @@ impl Value {
     }
 
     pub fn data(&self) -> Data<Self> {
+        let mut data = self.data_fields();
+        // The variants of an enum are matched on this value
+        if let Data::Enum(data) = &mut data {
+            data.scrutinee = Some(self.index);
+        }
+        data
+    }
+
+    fn data_fields(&self) -> Data<Self> {
         use crate::ValueNode::*;
         match self.node() {
             DataStructure { data, .. } => data.map(|value_ref| Value {
                 index: value_ref.element,
             }),
-            Reference { is_mut, value } if !is_mut => {
-                Value { index: value }.data().map(|v| v.element.reference())
-            }
+            Reference { is_mut, value } if !is_mut => Value { index: value }
+                .data_fields()
+                .map(|v| v.element.reference()),
 
             Reference { is_mut, value } if is_mut => Value { index: value }
-                .data()
+                .data_fields()
                 .map(|v| v.element.reference_mut()),
 
-            // FIXME generate match and propagate the binding
-            Binding { name, ty } => ty.data().map(|field| {
+            // The fields of an enum are accessed through the bindings of the
+            // arms of `Enum::match_variant` instead
+            Binding { ty, .. } | PatternBinding { ty } => ty.data().map(|field| {
                 let node = ValueNode::Destructure {
                     parent: self.index,
                     accessor: field.accessor.clone(),
                     ty: field.element,
+                    span: Some(field.span),
                 };
                 Value {
                     index: VALUES.index_push(node),
@@ -101,6 +144,7 @@ impl Value {
                     parent: self.index,
                     accessor: Accessor::Index(index),
                     ty: Type(types[index].clone()),
+                    span: None,
                 };
                 Value {
                     index: VALUES.index_push(node),
@@ -115,6 +159,7 @@ impl Value {
                     parent: self.index,
                     accessor: field.accessor.clone(),
                     ty: field.element.get_type(),
+                    span: field.element.span(),
                 };
                 Value {
                     index: VALUES.index_push(node),
@@ -130,6 +175,7 @@ impl Value {
                         parent: self.index,
                         accessor: field.accessor.clone(),
                         ty: field.element.clone(),
+                        span: Some(field.span),
                     };
                     Value {
                         index: VALUES.index_push(node),
@@ -143,6 +189,7 @@ impl Value {
                     parent: self.index,
                     accessor: Accessor::Index(index),
                     ty: Type(TypeNode::Infer),
+                    span: None,
                 };
                 Value {
                     index: VALUES.index_push(node),
@@ -1,10 +1,13 @@
 use crate::{
-    Function, GlobalBorrow, GlobalPush, Ident, InvokeRef, MacroInvokeRef, Parent, Path,
-    RuntimeFunction, Type, TypeNode, Value, ValueNode, ValueRef, INVOKES, MACROS, VALUES,
+    Data, Function, GlobalBorrow, GlobalPush, Ident, InvokeRef, MacroInvokeRef, Parent, Path,
+    ReflectError, RuntimeFunction, RuntimeType, Type, TypeNode, TypeParamBound, Value, ValueNode,
+    ValueRef, INVOKES, MACROS, VALUES,
 };
 use std::cell::RefCell;
 use std::ops::Range;
 use std::rc::Rc;
+use syn::parse::Parser;
+use syn::{Attribute, Meta};
 
 #[derive(Debug, Clone)]
 pub struct MakeImpl<'a> {
@@ -16,6 +19,11 @@ pub(crate) struct WipImpl {
     pub(crate) trait_ty: Option<Rc<Parent>>,
     pub(crate) ty: Type,
     pub(crate) functions: RefCell<Vec<WipFunction>>,
+    pub(crate) assoc_types: RefCell<Vec<(Ident, Type)>>,
+    /// The bounds on the generic self type of a blanket impl
+    pub(crate) self_bounds: Vec<TypeParamBound>,
+    /// The predicates of the `#[cfg(..)]` attributes on the impl
+    pub(crate) cfgs: RefCell<Vec<Meta>>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,12 +40,26 @@ pub(crate) struct WipFunction {
     pub(crate) invokes: WipRange<InvokeRef>,
     pub(crate) macros: WipRange<MacroInvokeRef>,
     pub(crate) ret: Option<ValueRef>,
+    /// The predicates of the `#[cfg(..)]` attributes on the function
+    pub(crate) cfgs: RefCell<Vec<Meta>>,
+    /// Other attributes on the function, like `#[inline]`
+    pub(crate) attrs: RefCell<Vec<Attribute>>,
+    /// The parameters declared `mut`, by the name of their binding
+    pub(crate) mut_args: RefCell<Vec<Ident>>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Invoke {
     pub(crate) function: Rc<Function>,
     pub(crate) args: Vec<ValueRef>,
+    /// The `Type` of a trait-qualified call `<Type as Trait>::method(..)`
+    pub(crate) qualified_self: Option<Type>,
+    /// The turbofish `::<A, B>` of the call, empty if it has none
+    pub(crate) type_args: Vec<Type>,
+    /// Whether `type_args` were not given by the user, but hold the type
+    /// params that the arguments of the call do not determine, to be
+    /// replaced by their inferred types
+    pub(crate) inferred_type_args: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +94,11 @@ impl<Idx> WipRange<Idx> {
 }
 
 impl<'a> MakeImpl<'a> {
+    /// The self type of the impl
+    pub fn target_type(&self) -> Type {
+        self.wip.ty.clone()
+    }
+
     pub fn make_function<F>(&self, f: F, run: fn(MakeFunction) -> Value)
     where
         F: RuntimeFunction,
@@ -83,6 +110,9 @@ impl<'a> MakeImpl<'a> {
             invokes: WipRange::new(InvokeRef(INVOKES.with_borrow(Vec::len))),
             macros: WipRange::new(MacroInvokeRef(MACROS.with_borrow(Vec::len))),
             ret: None,
+            cfgs: RefCell::new(Vec::new()),
+            attrs: RefCell::new(Vec::new()),
+            mut_args: RefCell::new(Vec::new()),
         };
         let ret = run(MakeFunction { wip: &wip }).index;
         wip.values.end = Some(ValueRef(VALUES.with_borrow(Vec::len)));
@@ -92,9 +122,66 @@ impl<'a> MakeImpl<'a> {
 
         self.wip.functions.borrow_mut().push(wip);
     }
+
+    /// Defines the associated type `name` of the implemented trait, emitted
+    /// as `type #name = #ty;`. The trait must declare it, like `type #name;`
+    /// in `library!`, or the derive fails with
+    /// `ReflectError::UnknownAssocType`.
+    pub fn make_assoc_type<T>(&self, name: &str, ty: T)
+    where
+        T: RuntimeType,
+    {
+        self.wip
+            .assoc_types
+            .borrow_mut()
+            .push((Ident::new(name), ty.SELF()));
+    }
+
+    /// Emits the impl only if the `cfg` predicate holds, e.g.
+    /// `feature = "serde"`. With several predicates all of them must hold.
+    pub fn add_cfg(&self, predicate: &str) {
+        self.wip.cfgs.borrow_mut().push(parse_cfg(predicate));
+    }
 }
 
 impl<'a> MakeFunction<'a> {
+    /// Emits the function only if the `cfg` predicate holds, e.g.
+    /// `feature = "serde"`. With several predicates all of them must hold.
+    pub fn add_cfg(&self, predicate: &str) {
+        self.wip.cfgs.borrow_mut().push(parse_cfg(predicate));
+    }
+
+    /// Emits an outer attribute on the function, e.g. `#[inline]`
+    pub fn add_attr(&self, attr: &str) {
+        let attr = Attribute::parse_outer
+            .parse_str(attr)
+            .ok()
+            .filter(|attrs| attrs.len() == 1)
+            .expect("add_attr: Not an outer attribute")
+            .remove(0);
+        self.wip.attrs.borrow_mut().push(attr);
+    }
+
+    /// Declares the parameter at `index` as `mut`, e.g. `mut __arg0: u32`,
+    /// so that the body uses it in place instead of rebinding it. The index
+    /// is the same as for `arg`, counting the receiver if there is one.
+    pub fn set_arg_mut(&self, index: usize) {
+        use crate::Receiver::*;
+        let receiver = &self.wip.f.sig.receiver;
+        let name = match receiver {
+            NoSelf => Ident::new(format!("__arg{}", index)),
+            SelfByReference { .. } if index == 0 => {
+                panic!("set_arg_mut: A reference receiver can not be mut")
+            }
+            SelfByValue | SelfByWrapper(_) if index == 0 => Ident::new("self"),
+            _ => Ident::new(format!("__arg{}", index - 1)),
+        };
+        let mut mut_args = self.wip.mut_args.borrow_mut();
+        if !mut_args.contains(&name) {
+            mut_args.push(name);
+        }
+    }
+
     pub fn unit(&self) -> Value {
         self.wip.unit()
     }
@@ -103,21 +190,27 @@ impl<'a> MakeFunction<'a> {
         self.wip.string(s)
     }
 
+    /// A struct literal `Self { a, b }`, `Self(a, b)` or `Self` of the struct
+    /// the impl is for, with one value per field in the order the fields are
+    /// declared. A `#[non_exhaustive]` struct can not be built this way,
+    /// which the derive reports as `ReflectError::NonExhaustiveLiteral`.
+    pub fn struct_literal(&self, fields: &[Value]) -> Value {
+        self.wip.struct_literal(fields)
+    }
+
     pub fn arg(&self, mut index: usize) -> Value {
         use crate::Receiver::*;
         let wip = self.wip;
 
-        let node = match match wip.f.sig.receiver {
-            SelfByValue if index == 0 => wip.self_ty.clone(),
-            SelfByReference { is_mut, lifetime } if index == 0 => wip.self_ty.clone().map(|ty| {
-                Type(TypeNode::Reference {
-                    is_mut,
-                    lifetime,
-                    inner: Box::new(ty.0),
-                })
-            }),
+        let receiver = &wip.f.sig.receiver;
+        let node = match match receiver {
             NoSelf => None,
-            SelfByValue | SelfByReference { .. } => {
+            _ if index == 0 => wip
+                .self_ty
+                .clone()
+                .and_then(|ty| receiver.self_type(ty.0))
+                .map(Type),
+            SelfByValue | SelfByReference { .. } | SelfByWrapper(_) => {
                 index -= 1;
                 None
             }
@@ -128,7 +221,10 @@ impl<'a> MakeFunction<'a> {
             },
             None => ValueNode::Binding {
                 name: Ident::new(format!("__arg{}", index)),
-                ty: wip.f.sig.inputs[index].clone(),
+                ty: match &wip.self_ty {
+                    Some(self_ty) => Type(wip.f.sig.inputs[index].0.replace_self(&self_ty.0)),
+                    None => wip.f.sig.inputs[index].clone(),
+                },
             },
         };
         Value {
@@ -155,10 +251,45 @@ impl WipFunction {
             index: VALUES.index_push(node),
         }
     }
+
+    fn struct_literal(&self, fields: &[Value]) -> Value {
+        let ty = match &self.self_ty {
+            Some(ty @ Type(TypeNode::DataStructure(data)))
+                if matches!(data.data, Data::Struct(_)) =>
+            {
+                ty
+            }
+            _ => panic!("MakeFunction::struct_literal: The impl is not for a struct"),
+        };
+        let field_types = ty.data();
+        if field_types.fields().count() != fields.len() {
+            panic!("MakeFunction::struct_literal: Wrong number of fields");
+        }
+        let mut values = fields.iter();
+        let node = ValueNode::DataStructure {
+            ty: ty.clone(),
+            data: field_types.map(|_| values.next().unwrap().index),
+        };
+        Value {
+            index: VALUES.index_push(node),
+        }
+    }
 }
 impl WipImpl {
+    /// Whether the impl is of an `unsafe trait`
+    pub(crate) fn is_unsafe(&self) -> bool {
+        match &self.trait_ty {
+            Some(parent) => parent.unsafety,
+            None => false,
+        }
+    }
+
     pub(crate) fn has_generics(&self) -> bool {
-        if let TypeNode::DataStructure(data) = &self.ty.0 {
+        // The self type of a blanket impl is itself a param
+        if let TypeNode::TypeParam(_) = self.ty.0 {
+            return true;
+        }
+        if let Some(TypeNode::DataStructure(data)) = self.ty.0.wrapped_data_structure() {
             !data.generics.params.is_empty()
                 || if let Some(parent) = &self.trait_ty {
                     !parent.generics.params.is_empty()
@@ -169,4 +300,69 @@ impl WipImpl {
             false
         }
     }
+
+    /// Whether a call in the impl has a turbofish whose types are left for
+    /// the trait inference to fill in
+    pub(crate) fn has_inferred_type_args(&self) -> bool {
+        INVOKES.with_borrow(|invokes| {
+            self.functions.borrow().iter().any(|function| {
+                invokes[function.invokes.start.0..function.invokes.end.unwrap().0]
+                    .iter()
+                    .any(|invoke| invoke.inferred_type_args && !invoke.type_args.is_empty())
+            })
+        })
+    }
+
+    /// Checks the signatures of the functions in the impl, and of every
+    /// function they invoke, for values that can not be sized
+    pub(crate) fn check_sized(&self) -> Result<(), ReflectError> {
+        INVOKES.with_borrow(|invokes| {
+            self.functions.borrow().iter().try_for_each(|function| {
+                function.f.sig.check_sized()?;
+                invokes[function.invokes.start.0..function.invokes.end.unwrap().0]
+                    .iter()
+                    .try_for_each(|invoke| invoke.function.sig.check_sized())
+            })
+        })
+    }
+
+    /// Checks that every associated type defined by the impl is declared by
+    /// the implemented trait
+    pub(crate) fn check_assoc_types(&self) -> Result<(), ReflectError> {
+        let trait_ty = match &self.trait_ty {
+            Some(trait_ty) => trait_ty,
+            None => return Ok(()),
+        };
+        self.assoc_types.borrow().iter().try_for_each(|(name, _)| {
+            if trait_ty.assoc_types.contains(name) {
+                Ok(())
+            } else {
+                Err(ReflectError::UnknownAssocType {
+                    name: name.to_string(),
+                    trait_path: trait_ty.path.clone(),
+                })
+            }
+        })
+    }
+
+    /// Checks that no function of the impl builds a `#[non_exhaustive]`
+    /// struct with a struct literal
+    pub(crate) fn check_literals(&self) -> Result<(), ReflectError> {
+        VALUES.with_borrow(|values| {
+            self.functions.borrow().iter().try_for_each(|function| {
+                values[function.values.start.0..function.values.end.unwrap().0]
+                    .iter()
+                    .try_for_each(|node| match node {
+                        ValueNode::DataStructure { ty, .. } if ty.is_non_exhaustive() => {
+                            Err(ReflectError::NonExhaustiveLiteral { ty: ty.clone() })
+                        }
+                        _ => Ok(()),
+                    })
+            })
+        })
+    }
+}
+
+fn parse_cfg(predicate: &str) -> Meta {
+    syn::parse_str(predicate).expect("add_cfg: Not a cfg predicate")
 }
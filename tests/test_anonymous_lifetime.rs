@@ -0,0 +1,97 @@
+use quote::quote;
+use reflect::runtime::RuntimeType;
+use reflect::*;
+
+library! {
+    use anonymous {
+        type Ref<'a>;
+        type Text;
+
+        trait Borrow {
+            fn borrow(&self) -> Ref<'_>;
+        }
+
+        impl Text {
+            fn as_ref(&self) -> Ref<'_>;
+        }
+    }
+}
+
+#[test]
+fn test_anonymous_lifetime_type() {
+    let mut param_map = SynParamMap::new();
+    let ty = Path::path_from_str("Ref<'_>", &mut param_map).SELF();
+    assert_eq!(ty.display(&param_map), quote!(Ref<'_>).to_string());
+
+    let reference = Type::primitive_str().reference_with_lifetime("'_", &param_map);
+    assert_eq!(reference.display(&param_map), quote!(&'_ str).to_string());
+}
+
+fn derive_borrow(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::anonymous::Borrow, ex.target_type(), |block| {
+        block.make_function(RUNTIME::anonymous::Borrow::borrow, |make_function| {
+            let receiver = make_function.arg(0);
+            match receiver.data() {
+                Data::Struct(Struct::Struct(receiver)) => {
+                    let text = receiver.fields().next().unwrap().get_value();
+                    RUNTIME::anonymous::Text::as_ref.INVOKE(text)
+                }
+                _ => unimplemented!(),
+            }
+        });
+    });
+}
+
+#[test]
+fn test_anonymous_lifetime_in_signature() {
+    let input = quote! {
+        struct Doc {
+            text: Text,
+        }
+    };
+
+    let expected = quote! {
+        impl ::anonymous::Borrow for Doc {
+            fn borrow<'__a1>(&'__a1 self) -> ::anonymous::Ref<'__a1> {
+                let __v0 = self;
+                let __v1 = &__v0.text;
+                let __v2 = ::anonymous::Text::as_ref(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive_borrow);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_anonymous_lifetime_regenerated() {
+    let input = quote! {
+        struct Doc {
+            text: Text,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.set_codegen_options(CodegenOptions {
+            preserve_elided_lifetimes: true,
+            ..CodegenOptions::default()
+        });
+        derive_borrow(ex);
+    }
+
+    let expected = quote! {
+        impl ::anonymous::Borrow for Doc {
+            fn borrow(&self) -> ::anonymous::Ref<'_> {
+                let __v0 = self;
+                let __v1 = &__v0.text;
+                let __v2 = ::anonymous::Text::as_ref(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,231 @@
+use quote::quote;
+use reflect::runtime::RuntimeType;
+use reflect::*;
+
+library! {
+    use buffer {
+        trait ToBytes {
+            fn to_bytes(&self) -> [u8; 4];
+        }
+
+        trait Fill {
+            fn fill(self) -> [u8; SIZE];
+        }
+
+        trait Widen {
+            fn widen(self) -> [u8; SIZE * 2];
+        }
+
+        trait Join {
+            fn join(self);
+        }
+
+        type Bytes;
+
+        impl Bytes {
+            fn copy(&[u8; 4]) -> [u8; 4];
+            fn zeroed() -> [u8; SIZE];
+            fn double([u8; SIZE]) -> [u8; SIZE * 2];
+            fn concat<T>(&[T]);
+        }
+    }
+}
+
+#[test]
+fn test_literal_length() {
+    let input = quote! {
+        struct Buffer {
+            bytes: [u8; 4],
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::buffer::ToBytes, ex.target_type(), |block| {
+            block.make_function(RUNTIME::buffer::ToBytes::to_bytes, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let bytes = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::buffer::Bytes::copy.INVOKE(bytes)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::buffer::ToBytes for Buffer {
+            fn to_bytes<'__a1>(&'__a1 self) -> [u8; 4] {
+                let __v0 = self;
+                let __v1 = &__v0.bytes;
+                let __v2 = ::buffer::Bytes::copy(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_const_length() {
+    let input = quote! {
+        struct Buffer;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::buffer::Fill, ex.target_type(), |block| {
+            block.make_function(RUNTIME::buffer::Fill::fill, |_make_function| {
+                RUNTIME::buffer::Bytes::zeroed.INVOKE()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::buffer::Fill for Buffer {
+            fn fill(self) -> [u8; SIZE] {
+                let __v0 = ::buffer::Bytes::zeroed();
+                __v0
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_computed_length() {
+    let input = quote! {
+        struct Buffer {
+            bytes: [u8; SIZE],
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::buffer::Widen, ex.target_type(), |block| {
+            block.make_function(RUNTIME::buffer::Widen::widen, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let bytes = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::buffer::Bytes::double.INVOKE(bytes)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::buffer::Widen for Buffer {
+            fn widen(self) -> [u8; SIZE * 2] {
+                let __v0 = self;
+                let __v1 = __v0.bytes;
+                let __v2 = ::buffer::Bytes::double(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_length_mismatch() {
+    let mut param_map = SynParamMap::new();
+    let byte = Path::path_from_str("u8", &mut param_map).SELF();
+
+    let four = byte.array("4");
+    assert_eq!(four, byte.array("4"));
+    assert_eq!(four.debug_tree(&param_map), "Array 4\n    Path u8\n");
+    assert!(Type::common_supertype(&[four.clone(), byte.array("SIZE * 2")]).is_err());
+    assert_eq!(
+        Type::common_supertype(&[four.clone(), four.clone()]).unwrap(),
+        four
+    );
+}
+
+#[test]
+fn test_array_literal_as_slice() {
+    let input = quote! {
+        struct Pair<T> {
+            first: T,
+            second: u32,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::buffer::Join, ex.target_type(), |block| {
+            block.make_function(RUNTIME::buffer::Join::join, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let fields: Vec<_> =
+                            receiver.fields().map(|field| field.get_value()).collect();
+                        let parts = Value::array(&fields);
+                        RUNTIME::buffer::Bytes::concat.INVOKE(parts.reference());
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // Both elements are passed as the `T` of `concat`, so the param of
+    // `Pair` has to be `u32`
+    let expected = quote! {
+        impl ::buffer::Join for Pair<u32> {
+            fn join(self) {
+                let __v0 = self;
+                let __v1 = __v0.first;
+                let __v2 = __v0.second;
+                let __v3 = [__v1, __v2];
+                let __v4 = &__v3;
+                let _ = ::buffer::Bytes::concat(__v4);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_const_param_length() {
+    let input = quote! {
+        struct Buf<const N: usize> {
+            data: [u8; N],
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::buffer::Join, ex.target_type(), |block| {
+            block.make_function(RUNTIME::buffer::Join::join, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    // `Generics` has no const params, so an impl for `Buf` could not write
+    // `Buf<N>`, and is refused instead
+    let expected = quote! {
+        compile_error! {
+            "the const generic parameter `N` is not supported"
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_const_param_from_str() {
+    let mut param_map = SynParamMap::new();
+    let error = Generics::from_str("<T, const N: usize>", &mut param_map).unwrap_err();
+    assert!(matches!(error, ReflectError::UnsupportedConstParam { .. }));
+}
@@ -0,0 +1,139 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use std {
+        mod ops {
+            trait Add<Rhs> {
+                type Output;
+                fn add(self, Rhs) -> Self::Output;
+            }
+        }
+    }
+    use measure {
+        trait Measure {
+            type Unit;
+            fn measure(self) -> Self::Unit;
+            fn measure_twice(self) -> Self::Unit;
+        }
+
+        trait Length {
+            fn length(&::std::string::String);
+        }
+    }
+}
+
+#[test]
+fn test_add_output() {
+    let input = quote! {
+        struct Foo(u32);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::std::ops::Add, ex.target_type(), |block| {
+            let mut param_map = SynParamMap::new();
+            block.make_assoc_type("Output", Path::path_from_str("u32", &mut param_map));
+            block.make_function(RUNTIME::std::ops::Add::add, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        receiver.fields().next().unwrap().get_value()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // `Self::Output` is resolved to the defined `u32` to check the body
+    let expected = quote! {
+        impl<__T0> ::std::ops::Add<__T0> for Foo {
+            type Output = u32;
+            fn add(self, __arg0: __T0) -> Self::Output {
+                let __v0 = self;
+                let __v1 = __v0.0;
+                __v1
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_invoked_assoc_output() {
+    let input = quote! {
+        struct Meters<T>(T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::measure::Measure, ex.target_type(), |block| {
+            let mut param_map = SynParamMap::new();
+            block.make_assoc_type(
+                "Unit",
+                Path::path_from_str("::std::string::String", &mut param_map),
+            );
+            block.make_function(RUNTIME::measure::Measure::measure, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        receiver.fields().next().unwrap().get_value()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+            block.make_function(RUNTIME::measure::Measure::measure_twice, |make_function| {
+                let receiver = make_function.arg(0);
+                let first = RUNTIME::measure::Measure::measure.INVOKE(receiver);
+                RUNTIME::measure::Length::length.INVOKE(first.reference());
+                first
+            });
+        });
+    }
+
+    // The invoked `measure` returns the defined `String`, which fixes `T`
+    let expected = quote! {
+        impl ::measure::Measure for Meters<::std::string::String> {
+            type Unit = ::std::string::String;
+            fn measure(self) -> Self::Unit {
+                let __v0 = self;
+                let __v1 = __v0.0;
+                __v1
+            }
+            fn measure_twice(self) -> Self::Unit {
+                let __v2 = self;
+                let __v3 = ::measure::Measure::measure(__v2);
+                let __v4 = &__v3;
+                let _ = ::measure::Length::length(__v4);
+                __v3
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_unknown_assoc_type() {
+    let input = quote! {
+        struct Meters(u32);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::measure::Measure, ex.target_type(), |block| {
+            let mut param_map = SynParamMap::new();
+            block.make_assoc_type("Output", Path::path_from_str("u32", &mut param_map));
+        });
+    }
+
+    let expected = quote! {
+        compile_error! {
+            "type `Output` is not a member of trait `:: measure :: Measure`"
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
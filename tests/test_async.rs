@@ -0,0 +1,100 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use asynchronous {
+        trait Fetch {
+            async fn fetch(self) -> ::std::string::String;
+        }
+
+        trait Task {
+            fn run(self);
+        }
+
+        type Runtime;
+
+        impl Runtime {
+            fn spawn<F: ::core::future::Future>(F);
+        }
+    }
+}
+
+#[test]
+fn test_async_method_impl() {
+    let input = quote! {
+        struct Named {
+            name: ::std::string::String,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::asynchronous::Fetch, ex.target_type(), |block| {
+            block.make_function(RUNTIME::asynchronous::Fetch::fetch, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        receiver.fields().next().unwrap().get_value()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::asynchronous::Fetch for Named {
+            async fn fetch(self) -> ::std::string::String {
+                let __v0 = self;
+                let __v1 = __v0.name;
+                __v1
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+// The call of an async method is an `impl Future` of its output, which can be
+// passed on to a function expecting a future
+#[test]
+fn test_async_invoke() {
+    let input = quote! {
+        struct Job<T> {
+            inner: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::asynchronous::Task, ex.target_type(), |block| {
+            block.make_function(RUNTIME::asynchronous::Task::run, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let inner = receiver.fields().next().unwrap().get_value();
+                        let future = RUNTIME::asynchronous::Fetch::fetch.INVOKE(inner);
+                        RUNTIME::asynchronous::Runtime::spawn.INVOKE(future)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::asynchronous::Task for Job<__T0>
+        where
+            __T0: ::asynchronous::Fetch,
+        {
+            fn run(self) {
+                let __v0 = self;
+                let __v1 = __v0.inner;
+                let __v2 = ::asynchronous::Fetch::fetch(__v1);
+                let _ = ::asynchronous::Runtime::spawn(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,99 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use batch {
+        trait Bound {
+            fn bound(&self);
+        }
+
+        trait First {
+            fn first(&self);
+        }
+
+        trait Second {
+            fn second(&self);
+        }
+    }
+}
+
+fn derive_per_impl_memo(ex: Execution) {
+    ex.make_impl_batch(|batch| {
+        batch.set_per_impl_memo(true);
+        make_batch_impls(batch);
+    });
+}
+
+fn derive_batch(ex: Execution) {
+    ex.make_impl_batch(make_batch_impls);
+}
+
+fn make_batch_impls(batch: ImplBatch) {
+    batch.make_trait_impl(RUNTIME::batch::First, batch.target_type(), |block| {
+        block.make_function(RUNTIME::batch::First::first, |make_function| {
+            let receiver = make_function.arg(0);
+            match receiver.data() {
+                Data::Struct(Struct::Struct(receiver)) => {
+                    for field in receiver.fields() {
+                        RUNTIME::batch::Bound::bound.INVOKE(field.get_value());
+                    }
+                    make_function.unit()
+                }
+                _ => unimplemented!(),
+            }
+        });
+    });
+    batch.make_trait_impl(RUNTIME::batch::Second, batch.target_type(), |block| {
+        block.make_function(RUNTIME::batch::Second::second, |make_function| {
+            make_function.unit()
+        });
+    });
+}
+
+fn expected_batch() -> proc_macro2::TokenStream {
+    // `Second` never uses the bound, but it is inferred together with `First`
+    quote! {
+        impl<__T0> ::batch::First for Wrapper<__T0>
+        where
+            __T0: ::batch::Bound,
+        {
+            fn first<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.value;
+                let _ = ::batch::Bound::bound(__v1);
+            }
+        }
+
+        impl<__T0> ::batch::Second for Wrapper<__T0>
+        where
+            __T0: ::batch::Bound,
+        {
+            fn second<'__a4>(&'__a4 self) {}
+        }
+    }
+}
+
+#[test]
+fn test_impl_batch() {
+    let input = quote! {
+        struct Wrapper<T> {
+            value: T,
+        }
+    };
+
+    let output = reflect::derive(input, derive_batch);
+    assert_eq!(output.to_string(), expected_batch().to_string());
+}
+
+#[test]
+fn test_impl_batch_per_impl_memo() {
+    let input = quote! {
+        struct Wrapper<T> {
+            value: T,
+        }
+    };
+
+    // Only the memo differs, not what is inferred
+    let output = reflect::derive(input, derive_per_impl_memo);
+    assert_eq!(output.to_string(), expected_batch().to_string());
+}
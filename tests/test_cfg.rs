@@ -0,0 +1,81 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use gated {
+        trait Export {
+            fn export(self);
+            fn export_json(self);
+        }
+    }
+}
+
+#[test]
+fn test_cfg_gated_method() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::gated::Export, ex.target_type(), |block| {
+            block.add_cfg("not(test)");
+            block.make_function(RUNTIME::gated::Export::export, |make_function| {
+                make_function.unit()
+            });
+            block.make_function(RUNTIME::gated::Export::export_json, |make_function| {
+                make_function.add_cfg("feature = \"json\"");
+                make_function.add_cfg("unix");
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        #[cfg(not(test))]
+        impl ::gated::Export for Unit {
+            fn export(self) {}
+            #[cfg(feature = "json")]
+            #[cfg(unix)]
+            fn export_json(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_inline_attr() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::gated::Export, ex.target_type(), |block| {
+            block.make_function(RUNTIME::gated::Export::export, |make_function| {
+                make_function.add_attr("#[inline]");
+                make_function.unit()
+            });
+            block.make_function(RUNTIME::gated::Export::export_json, |make_function| {
+                make_function.add_cfg("feature = \"json\"");
+                make_function.add_attr("#[inline(always)]");
+                make_function.add_attr("#[must_use = \"exported\"]");
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::gated::Export for Unit {
+            #[inline]
+            fn export(self) {}
+            #[cfg(feature = "json")]
+            #[inline(always)]
+            #[must_use = "exported"]
+            fn export_json(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,110 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use closures {
+        trait Run {
+            fn run(self);
+        }
+
+        trait Compute {
+            fn compute(self) -> ::std::string::String;
+        }
+
+        type Runner;
+
+        impl Runner {
+            fn apply<F: ::core::ops::Fn(u32) -> u32>(F);
+            fn map<T, U, F: ::core::ops::Fn(T) -> U>(F, T) -> U;
+        }
+    }
+}
+
+// A closure stored in a field is passed on to a function taking an `Fn`,
+// which bounds the type param of the field
+#[test]
+fn test_closure_fn_bound() {
+    let input = quote! {
+        struct Task<F> {
+            callback: F,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::closures::Run, ex.target_type(), |block| {
+            block.make_function(RUNTIME::closures::Run::run, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let callback = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::closures::Runner::apply.INVOKE(callback)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::closures::Run for Task<__T0>
+        where
+            __T0: ::core::ops::Fn(u32) -> u32,
+        {
+            fn run(self) {
+                let __v0 = self;
+                let __v1 = __v0.callback;
+                let _ = ::closures::Runner::apply(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_closure_fn_signature_unified() {
+    let input = quote! {
+        struct Mapper<F> {
+            callback: F,
+            value: u32,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::closures::Compute, ex.target_type(), |block| {
+            block.make_function(RUNTIME::closures::Compute::compute, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let mut fields = receiver.fields();
+                        let callback = fields.next().unwrap().get_value();
+                        let value = fields.next().unwrap().get_value();
+                        RUNTIME::closures::Runner::map.INVOKE(callback, value)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The inputs and the output of the `Fn` bound of `map` are unified with
+    // the value passed in and the output of `compute`
+    let expected = quote! {
+        impl<__T0> ::closures::Compute for Mapper<__T0>
+        where
+            __T0: ::core::ops::Fn(u32) -> ::std::string::String,
+        {
+            fn compute(self) -> ::std::string::String {
+                let __v0 = self;
+                let __v1 = __v0.callback;
+                let __v2 = __v0.value;
+                let __v3 = ::closures::Runner::map(__v1, __v2);
+                __v3
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
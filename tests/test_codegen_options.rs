@@ -0,0 +1,98 @@
+use quote::quote;
+use reflect::runtime::RuntimeType;
+use reflect::*;
+
+library! {
+    use holder {
+        trait Holder {
+            type Item;
+            fn hold(self);
+        }
+    }
+}
+
+fn derive_holder(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::holder::Holder, ex.target_type(), |block| {
+        let mut param_map = SynParamMap::new();
+        let byte = Path::path_from_str("u8", &mut param_map).SELF();
+        block.make_assoc_type("Item", Type::option(&byte));
+        block.make_function(RUNTIME::holder::Holder::hold, |make_function| {
+            make_function.unit()
+        });
+    });
+}
+
+#[test]
+fn test_std_paths_by_default() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    let expected = quote! {
+        impl ::holder::Holder for Unit {
+            type Item = ::std::option::Option<u8>;
+            fn hold(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive_holder);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_core_paths() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.set_codegen_options(CodegenOptions {
+            prefer_core: true,
+            ..CodegenOptions::default()
+        });
+        derive_holder(ex);
+    }
+
+    let expected = quote! {
+        impl ::holder::Holder for Unit {
+            type Item = ::core::option::Option<u8>;
+            fn hold(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_core_wrapper_types() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.set_codegen_options(CodegenOptions {
+            prefer_core: true,
+            ..CodegenOptions::default()
+        });
+        let mut param_map = SynParamMap::new();
+        let byte = Path::path_from_str("u8", &mut param_map).SELF();
+        let mut parsed = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+        assert_eq!(Type::vec(&byte), parsed("::alloc::vec::Vec<u8>"));
+        assert_eq!(
+            Type::result(&byte, &byte),
+            parsed("::core::result::Result<u8, u8>")
+        );
+        assert_eq!(
+            Type::boxed_dyn(&["::core::fmt::Debug"], &mut param_map),
+            Path::path_from_str(
+                "::alloc::boxed::Box<dyn ::core::fmt::Debug>",
+                &mut param_map
+            )
+            .SELF()
+        );
+    }
+
+    reflect::derive(input, derive);
+}
@@ -0,0 +1,36 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use constant {
+        trait Constant {
+            const fn constant();
+        }
+    }
+}
+
+fn derive(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::constant::Constant, ex.target_type(), |block| {
+        block.make_function(RUNTIME::constant::Constant::constant, |make_function| {
+            make_function.unit()
+        });
+    });
+}
+
+#[test]
+fn test_const_fn() {
+    let input = quote! {
+        struct Constant;
+    };
+
+    // A function in a trait impl cannot be const, even if it is declared
+    // that way
+    let expected = quote! {
+        impl ::constant::Constant for Constant {
+            fn constant() {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
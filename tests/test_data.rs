@@ -0,0 +1,129 @@
+use quote::quote;
+use reflect::*;
+
+fn field_names(data: &Data<Type>) -> Vec<String> {
+    data.fields().map(|(_, field)| field.get_name()).collect()
+}
+
+#[test]
+fn test_struct_fields() {
+    let input = quote! {
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().data();
+        assert_eq!(field_names(&data), ["x", "y"]);
+        assert!(data.fields().all(|(variant, _)| variant.is_none()));
+        assert!(data.variants().is_empty());
+        assert_eq!(data.variant_count(), 1);
+    }
+
+    reflect::derive(input, derive);
+}
+
+#[test]
+fn test_tuple_struct_fields() {
+    let input = quote! {
+        struct Pair(i32, ::std::string::String);
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().data();
+        assert_eq!(field_names(&data), ["0", "1"]);
+        assert_eq!(data.variant_count(), 1);
+    }
+
+    reflect::derive(input, derive);
+}
+
+#[test]
+fn test_enum_fields() {
+    let input = quote! {
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Rect { width: f64, height: f64 },
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let data = ex.target_type().data();
+        assert_eq!(field_names(&data), ["0", "width", "height"]);
+        assert_eq!(data.variant_count(), 3);
+
+        let fields: Vec<_> = data
+            .fields()
+            .map(|(variant, field)| (variant.unwrap().get_name(), field.get_name()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (String::from("Circle"), String::from("0")),
+                (String::from("Rect"), String::from("width")),
+                (String::from("Rect"), String::from("height")),
+            ]
+        );
+
+        let variants: Vec<_> = data
+            .variants()
+            .iter()
+            .map(|variant| (variant.get_name(), variant.fields().count()))
+            .collect();
+        assert_eq!(
+            variants,
+            [
+                (String::from("Empty"), 0),
+                (String::from("Circle"), 1),
+                (String::from("Rect"), 2),
+            ]
+        );
+    }
+
+    reflect::derive(input, derive);
+}
+
+#[test]
+fn test_enum_field_params() {
+    let input = quote! {
+        enum Tree<K, V, X> {
+            Leaf,
+            Node(V, ::std::vec::Vec<K>),
+            Pair { left: K, right: V },
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let ty = ex.target_type();
+        let params = ty.type_params();
+        assert_eq!(params.len(), 3);
+
+        // `X` is not used by any field, so it needs no bound
+        let data = ty.data();
+        assert_eq!(
+            data.collect_field_params(),
+            [params[1].clone(), params[0].clone()]
+        );
+    }
+
+    reflect::derive(input, derive);
+}
+
+#[test]
+fn test_concrete_field_params() {
+    let input = quote! {
+        enum Shape {
+            Empty,
+            Circle(f64),
+        }
+    };
+
+    fn derive(ex: Execution) {
+        assert!(ex.target_type().data().collect_field_params().is_empty());
+    }
+
+    reflect::derive(input, derive);
+}
@@ -0,0 +1,132 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use elision {
+        trait Name {
+            fn name(&self) -> &str;
+        }
+
+        trait Label {
+            fn label<'a>(&'a self, &str) -> &'a str;
+        }
+
+        type Text;
+
+        impl Text {
+            fn as_str(&self) -> &str;
+        }
+    }
+}
+
+fn derive_name(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::elision::Name, ex.target_type(), |block| {
+        block.make_function(RUNTIME::elision::Name::name, |make_function| {
+            let receiver = make_function.arg(0);
+            match receiver.data() {
+                Data::Struct(Struct::Struct(receiver)) => {
+                    let name = receiver.fields().next().unwrap().get_value();
+                    RUNTIME::elision::Text::as_str.INVOKE(name)
+                }
+                _ => unimplemented!(),
+            }
+        });
+    });
+}
+
+#[test]
+fn test_elided_getter_explicit_by_default() {
+    let input = quote! {
+        struct Person {
+            name: Text,
+        }
+    };
+
+    let expected = quote! {
+        impl ::elision::Name for Person {
+            fn name<'__a1>(&'__a1 self) -> &'__a1 str {
+                let __v0 = self;
+                let __v1 = &__v0.name;
+                let __v2 = ::elision::Text::as_str(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive_name);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_elided_getter_preserved() {
+    let input = quote! {
+        struct Person {
+            name: Text,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.set_codegen_options(CodegenOptions {
+            preserve_elided_lifetimes: true,
+            ..CodegenOptions::default()
+        });
+        derive_name(ex);
+    }
+
+    let expected = quote! {
+        impl ::elision::Name for Person {
+            fn name(&self) -> &str {
+                let __v0 = self;
+                let __v1 = &__v0.name;
+                let __v2 = ::elision::Text::as_str(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_named_lifetime_kept() {
+    let input = quote! {
+        struct Person {
+            name: Text,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.set_codegen_options(CodegenOptions {
+            preserve_elided_lifetimes: true,
+            ..CodegenOptions::default()
+        });
+        ex.make_trait_impl(RUNTIME::elision::Label, ex.target_type(), |block| {
+            block.make_function(RUNTIME::elision::Label::label, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let name = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::elision::Text::as_str.INVOKE(name)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // Only the lifetime of the second argument was elided
+    let expected = quote! {
+        impl ::elision::Label for Person {
+            fn label<'__a1>(&'__a1 self, __arg0: &str) -> &'__a1 str {
+                let __v0 = self;
+                let __v1 = &__v0.name;
+                let __v2 = ::elision::Text::as_str(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,285 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use pair {
+        trait Pair<T, U> {
+            fn pair((T, U));
+        }
+    }
+}
+
+#[test]
+fn test_tuple_length_mismatch() {
+    let input = quote! {
+        struct Triple<T>(T, T, T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::pair::Pair, ex.target_type(), |block| {
+            block.make_function(RUNTIME::pair::Pair::pair, |make_function| {
+                let tuple = make_function.arg(0);
+                let (t, u) = (tuple.get_index(0), tuple.get_index(1));
+                let triple = Value::tuple(&[t, u, t]);
+                RUNTIME::pair::Pair::pair.INVOKE(triple)
+            });
+        });
+    }
+
+    let expected = quote! {
+        compile_error! {
+            "mismatched types: expected `(_ , _)`, found `(_ , _ , _)`"
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+mod unsized_value {
+    use super::*;
+
+    library! {
+        use objects {
+            trait Shape {}
+
+            type Canvas;
+
+            impl Canvas {
+                fn draw(dyn Shape);
+                fn label(str);
+                fn fill([u8]);
+            }
+
+            trait Paint {
+                fn paint(&self);
+            }
+        }
+    }
+
+    #[test]
+    fn test_trait_object_by_value() {
+        let input = quote! {
+            struct Square;
+        };
+
+        fn derive(ex: Execution) {
+            ex.make_trait_impl(RUNTIME::objects::Paint, ex.target_type(), |block| {
+                block.make_function(RUNTIME::objects::Paint::paint, |make_function| {
+                    let receiver = make_function.arg(0);
+                    RUNTIME::objects::Canvas::draw.INVOKE(receiver);
+                    make_function.unit()
+                });
+            });
+        }
+
+        let expected = quote! {
+            compile_error! {
+                "the trait object `(dyn :: objects :: Shape)` must be behind a pointer such as `&` or `Box`"
+            }
+        };
+
+        let output = reflect::derive(input, derive);
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_str_by_value() {
+        let input = quote! {
+            struct Square;
+        };
+
+        fn derive(ex: Execution) {
+            ex.make_trait_impl(RUNTIME::objects::Paint, ex.target_type(), |block| {
+                block.make_function(RUNTIME::objects::Paint::paint, |make_function| {
+                    let receiver = make_function.arg(0);
+                    RUNTIME::objects::Canvas::label.INVOKE(receiver);
+                    make_function.unit()
+                });
+            });
+        }
+
+        let expected = quote! {
+            compile_error! {
+                "the unsized type `str` must be behind a pointer such as `&` or `Box`"
+            }
+        };
+
+        let output = reflect::derive(input, derive);
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_slice_by_value() {
+        let input = quote! {
+            struct Square;
+        };
+
+        fn derive(ex: Execution) {
+            ex.make_trait_impl(RUNTIME::objects::Paint, ex.target_type(), |block| {
+                block.make_function(RUNTIME::objects::Paint::paint, |make_function| {
+                    let receiver = make_function.arg(0);
+                    RUNTIME::objects::Canvas::fill.INVOKE(receiver);
+                    make_function.unit()
+                });
+            });
+        }
+
+        let expected = quote! {
+            compile_error! {
+                "the unsized type `[u8]` must be behind a pointer such as `&` or `Box`"
+            }
+        };
+
+        let output = reflect::derive(input, derive);
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+}
+
+mod path_mismatch {
+    use super::*;
+
+    library! {
+        use collections {
+            type List<T>;
+            type Set<T>;
+
+            impl Set {
+                fn insert_all<U>(Set<U>);
+            }
+
+            trait Collect {
+                fn collect(self);
+            }
+        }
+    }
+
+    #[test]
+    fn test_different_single_arg_paths() {
+        let input = quote! {
+            struct Items<T> {
+                items: ::collections::List<T>,
+            }
+        };
+
+        fn derive(ex: Execution) {
+            ex.make_trait_impl(RUNTIME::collections::Collect, ex.target_type(), |block| {
+                block.make_function(RUNTIME::collections::Collect::collect, |make_function| {
+                    let receiver = make_function.arg(0);
+                    match receiver.data() {
+                        Data::Struct(Struct::Struct(receiver)) => {
+                            let items = receiver.fields().next().unwrap().get_value();
+                            RUNTIME::collections::Set::insert_all.INVOKE(items);
+                            make_function.unit()
+                        }
+                        _ => unimplemented!(),
+                    }
+                });
+            });
+        }
+
+        // `List<T>` and `Set<U>` both have one argument, but `T` and `U` are
+        // not related since the types are different
+        let expected = quote! {
+            compile_error! {
+                "mismatched types: expected `:: collections :: Set < _ >`, found `:: collections :: List < _ >`"
+            }
+        };
+
+        let output = reflect::derive(input, derive);
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_mismatch_points_at_field() {
+        // Both fields have the same type, so only the value tells them apart
+        let input: proc_macro2::TokenStream = "
+            struct Twice<T> {
+                first: ::collections::List<T>,
+                second: ::collections::List<T>,
+            }
+        "
+        .parse()
+        .unwrap();
+
+        fn derive(ex: Execution) {
+            ex.make_trait_impl(RUNTIME::collections::Collect, ex.target_type(), |block| {
+                block.make_function(RUNTIME::collections::Collect::collect, |make_function| {
+                    let receiver = make_function.arg(0);
+                    match receiver.data() {
+                        Data::Struct(Struct::Struct(receiver)) => {
+                            let second = receiver.fields().nth(1).unwrap().get_value();
+                            RUNTIME::collections::Set::insert_all.INVOKE(second);
+                            make_function.unit()
+                        }
+                        _ => unimplemented!(),
+                    }
+                });
+            });
+        }
+
+        let output = reflect::derive(input, derive);
+        let span = output.into_iter().next().unwrap().span();
+        assert_eq!((span.start().line, span.start().column), (4, 24));
+    }
+}
+
+mod inference_limit {
+    use super::*;
+
+    library! {
+        use nested {
+            trait Show {}
+
+            trait Flatten {
+                fn flatten(self);
+            }
+
+            type Shower;
+
+            impl Shower {
+                fn show<T: Show>(T);
+            }
+        }
+    }
+
+    #[test]
+    fn test_nesting_deeper_than_limit() {
+        let input = quote! {
+            struct Nested<T> {
+                items: Vec<Vec<Vec<Vec<Vec<Vec<T>>>>>>,
+            }
+        };
+
+        fn derive(ex: Execution) {
+            ex.set_inference_limits(InferenceLimits {
+                max_depth: 4,
+                ..InferenceLimits::default()
+            });
+            ex.make_trait_impl(RUNTIME::nested::Flatten, ex.target_type(), |block| {
+                block.make_function(RUNTIME::nested::Flatten::flatten, |make_function| {
+                    let receiver = make_function.arg(0);
+                    match receiver.data() {
+                        Data::Struct(Struct::Struct(receiver)) => {
+                            let items = receiver.fields().next().unwrap().get_value();
+                            RUNTIME::nested::Shower::show.INVOKE(items);
+                            make_function.unit()
+                        }
+                        _ => unimplemented!(),
+                    }
+                });
+            });
+        }
+
+        // Each `Vec` nests one level deeper, so the inference gives up two
+        // levels above `T`
+        let expected = quote! {
+            compile_error! {
+                "reached the type inference limit while inferring `Vec < Vec < _ > >`"
+            }
+        };
+
+        let output = reflect::derive(input, derive);
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+}
@@ -68,3 +68,82 @@ fn test_field_access() {
     let output = reflect::derive(input, derive);
     assert_eq!(output.to_string(), expected.to_string());
 }
+
+fn derive_accessors(ex: Execution) {
+    match ex.target_type().data() {
+        Data::Struct(Struct::Struct(data)) => {
+            assert!(data.fields().all(|field| field.is_named()));
+            assert!(data.fields().all(|field| field.get_index().is_none()));
+        }
+        Data::Struct(Struct::Tuple(data)) => {
+            for (i, field) in data.fields().enumerate() {
+                assert!(!field.is_named());
+                assert_eq!(field.get_index(), Some(i));
+                assert_eq!(field.get_name(), i.to_string());
+            }
+        }
+        _ => unimplemented!(),
+    }
+
+    ex.make_trait_impl(RUNTIME::base::Trait, ex.target_type(), |block| {
+        block.make_function(RUNTIME::base::Trait::trivial, |make_function| {
+            let receiver = make_function.arg(0);
+            match receiver.data() {
+                Data::Struct(receiver) => {
+                    for field in receiver.fields() {
+                        RUNTIME::base::FieldAccessor::access_field.INVOKE(field.get_value());
+                    }
+                }
+                _ => unimplemented!(),
+            }
+            make_function.unit()
+        });
+    });
+}
+
+#[test]
+fn test_named_field_accessors() {
+    let input = quote! {
+        struct Named {
+            first: String,
+            second: String,
+        }
+    };
+
+    let expected = quote! {
+        impl ::base::Trait for Named {
+            fn trivial<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.first;
+                let __v2 = &__v0.second;
+                let _ = ::base::FieldAccessor::access_field(__v1);
+                let _ = ::base::FieldAccessor::access_field(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive_accessors);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_tuple_field_accessors() {
+    let input = quote! {
+        struct Positional(String, String);
+    };
+
+    let expected = quote! {
+        impl ::base::Trait for Positional {
+            fn trivial<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.0;
+                let __v2 = &__v0.1;
+                let _ = ::base::FieldAccessor::access_field(__v1);
+                let _ = ::base::FieldAccessor::access_field(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive_accessors);
+    assert_eq!(output.to_string(), expected.to_string());
+}
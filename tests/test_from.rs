@@ -0,0 +1,102 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use core {
+        mod convert {
+            trait From<T> {
+                fn from(T) -> Self;
+            }
+            trait Into<T> {
+                fn into(self) -> T;
+            }
+        }
+        mod default {
+            trait Default {
+                fn default() -> Self;
+            }
+        }
+    }
+    use units {
+        trait Unit {}
+
+        type Log;
+
+        impl Log {
+            fn log<U: Unit>(U);
+        }
+    }
+}
+
+#[test]
+fn test_from_impl() {
+    let input = quote! {
+        struct Meters<T> {
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::core::convert::From, ex.target_type(), |block| {
+            block.make_function(RUNTIME::core::convert::From::from, |make_function| {
+                let unit = make_function.arg(0);
+                RUNTIME::units::Log::log.INVOKE(unit);
+                RUNTIME::core::default::Default::default.INVOKE()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0, __T1> ::core::convert::From<__T1> for Meters<__T0>
+        where
+            Self: ::core::default::Default,
+            __T1: ::units::Unit,
+        {
+            fn from(__arg0: __T1) -> Self {
+                let __v0 = __arg0;
+                let _ = ::units::Log::log(__v0);
+                let __v2 = ::core::default::Default::default();
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_into_impl() {
+    let input = quote! {
+        struct Meters<T> {
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::core::convert::Into, ex.target_type(), |block| {
+            block.make_function(RUNTIME::core::convert::Into::into, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        receiver.fields().next().unwrap().get_value()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::core::convert::Into<__T0> for Meters<__T0> {
+            fn into(self) -> __T0 {
+                let __v0 = self;
+                let __v1 = __v0.value;
+                __v1
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
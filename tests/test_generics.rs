@@ -1,11 +1,19 @@
 use quote::quote;
+use reflect::runtime::RuntimeType;
 use reflect::*;
+use std::collections::HashMap;
 
 library! {
     use std {
         mod string {
             type String;
         }
+        mod collections {
+            type HashMap<K, V, S>;
+        }
+        mod hash {
+            trait Hash {}
+        }
     }
     use generic {
         type Wrapper<T>;
@@ -17,6 +25,33 @@ library! {
         trait Bound {}
 
         trait TraitArgs<'a, 'b: 'a, T, U: 'static> {}
+
+        type Visitor;
+
+        impl Visitor {
+            fn visit<U>(U) -> Wrapper<U>;
+            fn visit_string(::std::string::String);
+            fn visit_borrowed<'b, U: Borrowed<'b, ::std::string::String>>(&'b U);
+        }
+
+        trait Borrowed<'a, T> {}
+
+        trait VisitAll {
+            fn visit_all(self);
+        }
+
+        trait Iterable {}
+
+        trait Inspect {
+            fn inspect<U: ?::core::marker::Sized>(&self, &U);
+        }
+
+        type Walker;
+
+        impl Walker {
+            fn walk<U>(U) where for<'a> &'a U: Iterable;
+            fn walk_map<K: ::std::hash::Hash, V, S>(&::std::collections::HashMap<K, V, S>);
+        }
     }
 }
 
@@ -117,3 +152,1233 @@ fn test_generics2() {
 
     assert_eq!(&output, &expected.to_string());
 }
+
+#[test]
+fn test_generic_method_invoked_twice() {
+    let input = quote! {
+        struct Pair<T, U> {
+            first: T,
+            second: U,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::generic::Visitor::visit.INVOKE(field.get_value());
+                        }
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0, __T1> ::generic::VisitAll for Pair<__T0, __T1> {
+            fn visit_all(self) {
+                let __v0 = self;
+                let __v1 = __v0.first;
+                let __v2 = __v0.second;
+                let _ = ::generic::Visitor::visit(__v1);
+                let _ = ::generic::Visitor::visit(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_type_display() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["'a", "T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+
+    let ty = Path::path_from_str("Wrapper<&'a T, &'static str>", &mut param_map).SELF();
+    assert_eq!(ty.display(&param_map), "Wrapper < & 'a T , & 'static str >");
+}
+
+#[test]
+fn test_type_display_generated_name() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+
+    // A type that happens to be named like the generated name of `T`
+    let generated = Type::type_param_from_str("T", &mut param_map).to_turbofish_string();
+    let path = format!("{}<T>", generated);
+    let ty = Path::path_from_str(&path, &mut param_map).SELF();
+    assert_eq!(ty.display(&param_map), format!("{} < T >", generated));
+}
+
+#[test]
+fn test_elide_lifetimes() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["'a", "'b", "T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+
+    let inner =
+        Type::type_param_from_str("T", &mut param_map).reference_with_lifetime("'b", &param_map);
+    let number = Path::path_from_str("u32", &mut param_map).SELF();
+    let ty = Type::tuple(&[inner, number]).reference_with_lifetime("'a", &param_map);
+    assert_eq!(ty.display(&param_map), "& 'a (& 'b T , u32)");
+    assert_eq!(ty.elide_lifetimes().display(&param_map), "& (& T , u32)");
+
+    let ty = Path::path_from_str("Wrapper<'a, &'b T>", &mut param_map).SELF();
+    assert_eq!(ty.elide_lifetimes().display(&param_map), "Wrapper < & T >");
+}
+
+#[test]
+fn test_impl_trait_use_bound() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["'a", "'b", "T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+
+    let ty = Type::get_impl_trait(&["::std::fmt::Display", "use<'a, T>"], &mut param_map);
+    assert_eq!(
+        ty.display(&param_map),
+        "(impl :: std :: fmt :: Display + use < 'a , T >)"
+    );
+    assert_eq!(
+        ty.elide_lifetimes().display(&param_map),
+        "(impl :: std :: fmt :: Display + use < T >)"
+    );
+
+    let ty = Type::get_impl_trait(&["::std::fmt::Display"], &mut param_map);
+    assert_eq!(ty.display(&param_map), "(impl :: std :: fmt :: Display)");
+}
+
+#[test]
+fn test_debug_tree() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["'a", "T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+
+    let ty = Path::path_from_str("::std::option::Option<(&'a mut T, [u8])>", &mut param_map).SELF();
+    let expected = "\
+Path ::std::option::Option
+    Tuple
+        Reference 'a mut
+            TypeParam T
+        Slice
+            Path u8
+";
+    assert_eq!(ty.debug_tree(&param_map), expected);
+}
+
+#[test]
+fn test_pretty_diff() {
+    let mut param_map = SynParamMap::new();
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    let left = ty("Wrapper<(u8, u16)>");
+    let right = ty("Wrapper<u8>");
+    left.assert_eq_pretty(&left.clone());
+    assert_eq!(left.pretty_diff(&left.clone()), None);
+
+    let expected = "\
+types are not equal
+  left: Wrapper < (u8 , u16) >
+ right: Wrapper < u8 >
+left tree:
+Path Wrapper
+    Tuple
+        Path u8
+        Path u16
+right tree:
+Path Wrapper
+    Path u8
+";
+    assert_eq!(left.pretty_diff(&right).unwrap(), expected);
+}
+
+#[test]
+fn test_strip_one_reference() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+
+    let param = Type::type_param_from_str("T", &mut param_map);
+    assert_eq!(
+        param.reference().strip_one_reference(),
+        Some((param.clone(), false))
+    );
+    assert_eq!(
+        param.reference_mut().strip_one_reference(),
+        Some((param.clone(), true))
+    );
+    assert_eq!(
+        param.reference().reference_mut().strip_one_reference(),
+        Some((param.reference(), true))
+    );
+    assert_eq!(param.strip_one_reference(), None);
+}
+
+#[test]
+fn test_as_reference_inner() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+
+    let param = Type::type_param_from_str("T", &mut param_map);
+    let reference = param.reference();
+    assert_eq!(reference.as_reference_inner(), Some(&param));
+    assert_eq!(
+        reference.as_reference_inner().cloned(),
+        Some(reference.dereference())
+    );
+    let nested = param.reference_mut().reference();
+    assert_eq!(nested.as_reference_inner(), Some(&param.reference_mut()));
+    assert_eq!(param.as_reference_inner(), None);
+}
+
+#[test]
+fn test_replace_infer() {
+    let mut param_map = SynParamMap::new();
+    let ty = Path::path_from_str("::std::vec::Vec<_>", &mut param_map).SELF();
+    let number = Path::path_from_str("u32", &mut param_map).SELF();
+
+    let expected = Path::path_from_str("::std::vec::Vec<u32>", &mut param_map).SELF();
+    assert_eq!(ty.replace_infer(&number), expected);
+
+    // The bounds of a trait object are walked too
+    let ty = Path::path_from_str(
+        "::std::boxed::Box<dyn ::std::iter::Iterator<Item = _>>",
+        &mut param_map,
+    )
+    .SELF();
+    let expected = Path::path_from_str(
+        "::std::boxed::Box<dyn ::std::iter::Iterator<Item = u32>>",
+        &mut param_map,
+    )
+    .SELF();
+    assert_eq!(ty.replace_infer(&number), expected);
+}
+
+#[test]
+fn test_substitute() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T", "U", "V"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let t = Type::type_param_from_str("T", &mut param_map);
+    let u = Type::type_param_from_str("U", &mut param_map);
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    let mut map = HashMap::new();
+    map.insert(t.clone(), ty("u32"));
+    map.insert(u, ty("::std::string::String"));
+
+    let pair = Type::tuple(&[t, ty("Vec<U>")]);
+    let expected = Type::tuple(&[ty("u32"), ty("Vec<::std::string::String>")]);
+    assert_eq!(pair.substitute(&map), expected);
+
+    // Params without a type are left as they are
+    let ty = ty("Wrapper<V, &U>");
+    assert_eq!(
+        ty.substitute(&map).display(&param_map),
+        "Wrapper < V , & :: std :: string :: String >"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Type::substitute: Not a type param")]
+fn test_substitute_non_param() {
+    let mut param_map = SynParamMap::new();
+    let mut map = HashMap::new();
+    map.insert(
+        Path::path_from_str("u32", &mut param_map).SELF(),
+        Path::path_from_str("u64", &mut param_map).SELF(),
+    );
+    Type::unit().substitute(&map);
+}
+
+#[test]
+fn test_common_supertype() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T", "U"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    let types = [
+        ty("Wrapper<T>"),
+        ty("Wrapper<::std::string::String>"),
+        ty("Wrapper<U>"),
+    ];
+    let common = Type::common_supertype(&types).unwrap();
+    assert_eq!(common, ty("Wrapper<::std::string::String>"));
+
+    let types = [ty("Wrapper<T>"), ty("Other<T>")];
+    assert!(Type::common_supertype(&types).is_err());
+
+    let error = Type::common_supertype(&[]).unwrap_err();
+    assert!(matches!(error, ReflectError::NoTypes));
+}
+
+#[test]
+fn test_unifier_rollback() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T", "U"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let t = Type::type_param_from_str("T", &mut param_map);
+    let u = Type::type_param_from_str("U", &mut param_map);
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    let mut unifier = Unifier::new();
+    unifier.unify(&ty("Wrapper<T>"), &ty("Wrapper<U>")).unwrap();
+    let before = unifier.resolve(&ty("Vec<T>")).unwrap();
+    assert_eq!(unifier.resolve(&ty("Vec<U>")).unwrap(), before);
+
+    // Try out `u32` for the params, and back out again
+    let snapshot = unifier.snapshot();
+    unifier.unify(&u, &ty("u32")).unwrap();
+    assert_eq!(unifier.resolve(&ty("Vec<T>")).unwrap(), ty("Vec<u32>"));
+    unifier.rollback(snapshot);
+    assert_eq!(unifier.resolve(&ty("Vec<T>")).unwrap(), before);
+
+    // A failed unification is undone the same way
+    let snapshot = unifier.snapshot();
+    assert!(unifier.unify(&ty("Wrapper<T>"), &ty("Other<T>")).is_err());
+    unifier.rollback(snapshot);
+    unifier.unify(&t, &ty("::std::string::String")).unwrap();
+    assert_eq!(
+        unifier.resolve(&ty("Wrapper<U>")).unwrap(),
+        ty("Wrapper<::std::string::String>")
+    );
+}
+
+// `T` and `U` are unified only through the types that hold them, so each is
+// made concrete through its own equality set, which holds `u32`
+#[test]
+fn test_inner_params_resolve_through_their_set() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T", "U"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let t = Type::type_param_from_str("T", &mut param_map);
+    let u = Type::type_param_from_str("U", &mut param_map);
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+    let int = ty("u32");
+    let flag = ty("bool");
+
+    let pairs = [
+        (
+            Type::tuple(&[t.clone(), flag.clone()]),
+            Type::tuple(&[u.clone(), flag.clone()]),
+            Type::tuple(&[int.clone(), flag.clone()]),
+        ),
+        (t.reference(), u.reference(), int.reference()),
+        (t.slice(), u.slice(), int.slice()),
+        (ty("Vec<T>"), ty("Vec<U>"), ty("Vec<u32>")),
+    ];
+    for (with_t, with_u, expected) in pairs {
+        let mut unifier = Unifier::new();
+        unifier.unify(&with_t, &with_u).unwrap();
+        unifier.unify(&u, &int).unwrap();
+        assert_eq!(unifier.resolve(&with_t).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_trait_object_binding_drives_param() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    let types = [
+        ty("Pair<T, Box<dyn Iterator<Item = T>>>"),
+        ty("Pair<T, Box<dyn Iterator<Item = u32>>>"),
+    ];
+    let common = Type::common_supertype(&types).unwrap();
+    assert_eq!(common, ty("Pair<u32, Box<dyn Iterator<Item = u32>>>"));
+}
+
+#[test]
+fn test_never_coerces() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::TraitArgs", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    let never = Type::never();
+    let number = ty("u32");
+    let tuple = Type::tuple(&[number.clone(), ty("T")]);
+    for target in [number, ty("Wrapper<T>"), tuple, Type::primitive_str()] {
+        assert_eq!(never.coerce_to(&target), target);
+        assert_eq!(target.coerce_to(&never), target);
+
+        let common = Type::common_supertype(&[never.clone(), target.clone()]).unwrap();
+        assert_eq!(common, target);
+        let common = Type::common_supertype(&[target.clone(), never.clone()]).unwrap();
+        assert_eq!(common, target);
+    }
+
+    // `!` only coerces at the top level, so inside a type it is a type like
+    // any other
+    let common = Type::common_supertype(&[ty("Wrapper<!>"), ty("Wrapper<T>")]).unwrap();
+    assert_eq!(common, ty("Wrapper<!>"));
+    let types = [ty("Wrapper<!>"), ty("Wrapper<u32>")];
+    assert!(Type::common_supertype(&types).is_err());
+    let types = [
+        Type::tuple(std::slice::from_ref(&never)),
+        Type::tuple(&[ty("u32")]),
+    ];
+    assert!(Type::common_supertype(&types).is_err());
+
+    let common = Type::common_supertype(&[never.clone(), never.clone()]).unwrap();
+    assert_eq!(common, never);
+}
+
+#[test]
+fn test_param_resolved_to_concrete_type() {
+    let input = quote! {
+        struct Single<T: Clone> {
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::generic::Visitor::visit_string.INVOKE(field.get_value());
+                        }
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // `T` is always a `String`, so it is not a parameter of the impl, and
+    // the `T: Clone` bound is not needed either
+    let expected = quote! {
+        impl ::generic::VisitAll for Single<::std::string::String> {
+            fn visit_all(self) {
+                let __v0 = self;
+                let __v1 = __v0.value;
+                let _ = ::generic::Visitor::visit_string(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_type_outlives_predicates() {
+    let input = quote! {
+        struct Outlives<'a, T: 'a, U>
+        where
+            U: 'static,
+        {
+            one: &'a T,
+            two: U,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<'__a1, __T0, __T1> ::generic::VisitAll for Outlives<'__a1, __T0, __T1>
+        where
+            __T0: '__a1,
+            __T1: 'static,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_lifetime_outlives_predicates() {
+    let input = quote! {
+        struct Refs<'a, 'b, 'c: 'a>
+        where
+            'a: 'b,
+        {
+            x: &'a u8,
+            y: &'b u8,
+            z: &'c u8,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<'__a1, '__a2, '__a3> ::generic::VisitAll for Refs<'__a1, '__a2, '__a3>
+        where
+            '__a1: '__a2,
+            '__a3: '__a1,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_lifetime_outlives_chain() {
+    let input = quote! {
+        struct Chain<'a, 'b, 'c, 'd>
+        where
+            'a: 'b,
+            'b: 'c,
+            'c: 'd,
+        {
+            a: &'a u8,
+            b: &'b u8,
+            c: &'c u8,
+            d: &'d u8,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<'__a1, '__a2, '__a3, '__a4> ::generic::VisitAll for Chain<'__a1, '__a2, '__a3, '__a4>
+        where
+            '__a1: '__a2,
+            '__a2: '__a3,
+            '__a3: '__a4,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_declared_where_clause_kept() {
+    let input = quote! {
+        struct Keyed<T>
+        where
+            ::generic::Wrapper<T>: ::generic::Bound,
+        {
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    // The bound is not needed by the body, but it is part of the declaration
+    // of `Keyed`, so it is kept
+    let expected = quote! {
+        impl<__T0> ::generic::VisitAll for Keyed<__T0>
+        where
+            ::generic::Wrapper<__T0>: ::generic::Bound,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_projection_bounded_predicate() {
+    let input = quote! {
+        struct Items<T: Iterator>
+        where
+            T::Item: Clone,
+        {
+            iter: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::generic::VisitAll for Items<__T0>
+        where
+            __T0::Item: Clone,
+            __T0: Iterator,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_impl_for_reference() {
+    let input = quote! {
+        struct Keyed<T> {
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let self_ty = ex.target_type().reference();
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, self_ty, |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::generic::VisitAll for &Keyed<__T0> {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_impl_for_box() {
+    let input = quote! {
+        struct Keyed<T> {
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let mut param_map = SynParamMap::new();
+        let boxed = Path::path_from_str("::std::boxed::Box<_>", &mut param_map);
+        let self_ty = boxed.SELF().replace_infer(&ex.target_type());
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, self_ty, |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::generic::VisitAll for ::std::boxed::Box<Keyed<__T0> > {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_trivial_bound_dropped() {
+    let input = quote! {
+        struct Keyed<T>
+        where
+            u32: ::std::marker::Copy + ::generic::Generic<T>,
+        {
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    // `u32: Copy` always holds, so only the bound mentioning `T` is left
+    let expected = quote! {
+        impl<__T0> ::generic::VisitAll for Keyed<__T0>
+        where
+            u32: ::generic::Generic<__T0>,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_lifetime_args_in_bounds() {
+    let input = quote! {
+        struct Holder<'a, T, U> {
+            value: &'a T,
+            other: U,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let value = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::generic::Visitor::visit_borrowed.INVOKE(value);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<'__a1, __T0, __T1> ::generic::VisitAll for Holder<'__a1, __T0, __T1>
+        where
+            __T0: ::generic::Borrowed<'__a1, ::std::string::String>,
+        {
+            fn visit_all(self) {
+                let __v0 = self;
+                let __v1 = __v0.value;
+                let _ = ::generic::Visitor::visit_borrowed(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_structural_hash() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    fn hash(ty: &Type, param_map: &SynParamMap) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        ty.structural_hash(param_map, &mut hasher);
+        hasher.finish()
+    }
+
+    fn wrapper(params: &[&str], ty: &str) -> (Type, SynParamMap) {
+        let mut parent = ParentBuilder::new(ParentKind::Trait);
+        parent.set_generic_params(params);
+        parent.set_path(|param_map: &mut SynParamMap| {
+            Path::path_from_str("::generic::TraitArgs", param_map)
+        });
+        let mut param_map = parent.into_parent().get_param_map().clone();
+        (Path::path_from_str(ty, &mut param_map).SELF(), param_map)
+    }
+
+    let (first, first_map) = wrapper(&["'a", "T"], "Wrapper<&'a T>");
+    let (second, second_map) = wrapper(&["'a", "T"], "Wrapper<&'a T>");
+    assert_ne!(first, second);
+    assert_eq!(hash(&first, &first_map), hash(&second, &second_map));
+
+    let (renamed, renamed_map) = wrapper(&["'a", "U"], "Wrapper<&'a U>");
+    assert_ne!(hash(&first, &first_map), hash(&renamed, &renamed_map));
+
+    // Params without a name are hashed by position
+    let empty = SynParamMap::new();
+    assert_eq!(hash(&first, &empty), hash(&second, &empty));
+
+    // and not by a name that a type could have as well
+    let (param, _) = wrapper(&["T"], "Wrapper<T>");
+    let named = Path::path_from_str("Wrapper<_0>", &mut SynParamMap::new()).SELF();
+    assert_ne!(hash(&param, &empty), hash(&named, &empty));
+}
+
+// The target type is built with whatever params are next in the session,
+// which does not change its hash
+#[test]
+fn test_structural_hash_data_structure() {
+    use std::cell::RefCell;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    thread_local! {
+        static TARGETS: RefCell<Vec<(Type, u64)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn derive(ex: Execution) {
+        let target = ex.target_type();
+        let mut hasher = DefaultHasher::new();
+        target.structural_hash(&SynParamMap::new(), &mut hasher);
+        TARGETS.with_borrow_mut(|targets| targets.push((target, hasher.finish())));
+    }
+
+    let input = quote! {
+        struct Holder<'a, T> {
+            borrowed: &'a T,
+            wrapped: Wrapper<T>,
+        }
+    };
+    reflect::derive(input.clone(), derive);
+
+    // Take a lifetime and a type param so that the second target gets
+    // different ones
+    Generics::from_str("<'b, U>", &mut SynParamMap::new()).unwrap();
+    reflect::derive(input, derive);
+
+    let targets = TARGETS.take();
+    assert_ne!(targets[0].0, targets[1].0);
+    assert_eq!(targets[0].1, targets[1].1);
+}
+
+#[test]
+fn test_generics_from_str() {
+    let mut param_map = SynParamMap::new();
+    let generics = Generics::from_str("<'a, T: Clone + 'a, U>", &mut param_map).unwrap();
+    assert_eq!(generics.display(), "< 'a , T , U > where T : Clone + 'a");
+
+    // The params can be used by types parsed with the same map
+    let ty = Path::path_from_str("Wrapper<&'a T, U>", &mut param_map).SELF();
+    assert_eq!(ty.display(&param_map), "Wrapper < & 'a T , U >");
+
+    let mut param_map = SynParamMap::new();
+    let generics = Generics::from_str(
+        "<'a, 'b: 'a, T> where T: ::generic::Borrowed<'b, T>",
+        &mut param_map,
+    )
+    .unwrap();
+    assert_eq!(
+        generics.display(),
+        "< 'a , 'b , T > where 'b : 'a , T : :: generic :: Borrowed < 'b , T >"
+    );
+
+    let mut param_map = SynParamMap::new();
+    let generics = Generics::from_str("<T> where T: ?Sized", &mut param_map).unwrap();
+    assert_eq!(generics.display(), "< T > where T : ? Sized");
+    assert!(Type::type_param_from_str("T", &mut param_map).is_definitely_unsized(&generics));
+
+    let generics = Generics::from_str("<>", &mut SynParamMap::new()).unwrap();
+    assert!(generics.is_empty());
+
+    let error = Generics::from_str("<T: Clone", &mut SynParamMap::new()).unwrap_err();
+    assert!(matches!(error, ReflectError::InvalidGenerics { .. }));
+}
+
+#[test]
+fn test_no_generics() {
+    assert!(Generics::default().is_empty());
+
+    let mut generics = Generics::default();
+    generics.set_generic_params(&["T"]);
+    assert!(!generics.is_empty());
+
+    let input = quote! {
+        struct Plain {
+            value: ::std::string::String,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::generic::Visitor::visit_string.INVOKE(field.get_value());
+                        }
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::generic::VisitAll for Plain {
+            fn visit_all(self) {
+                let __v0 = self;
+                let __v1 = __v0.value;
+                let _ = ::generic::Visitor::visit_string(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_lifetime_only_generics() {
+    let input = quote! {
+        struct Ref<'a>(&'a str);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::generic::Visitor::visit.INVOKE(field.get_value());
+                        }
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The impl has no type params, but still has to declare the lifetime
+    let expected = quote! {
+        impl<'__a1> ::generic::VisitAll for Ref<'__a1> {
+            fn visit_all(self) {
+                let __v0 = self;
+                let __v1 = __v0.0;
+                let _ = ::generic::Visitor::visit(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_higher_ranked_reference_bound() {
+    let input = quote! {
+        struct Walked<T, U>
+        where
+            for<'a> &'a T: ::generic::Iterable,
+        {
+            declared: T,
+            inferred: U,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let inferred = receiver.fields().nth(1).unwrap().get_value();
+                        RUNTIME::generic::Walker::walk.INVOKE(inferred);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The declared bound is kept and the inferred one comes from the where
+    // clause of `walk`, both with their `for<'a>` binder
+    let expected = quote! {
+        impl<__T0, __T1> ::generic::VisitAll for Walked<__T0, __T1>
+        where
+            for<'__a1> &'__a1 __T0: ::generic::Iterable,
+            for<'__a3> &'__a3 __T1: ::generic::Iterable,
+        {
+            fn visit_all(self) {
+                let __v0 = self;
+                let __v2 = __v0.inferred;
+                let _ = ::generic::Walker::walk(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_defaulted_type_args() {
+    // The field leaves out the defaulted hasher argument of `HashMap`
+    let input = quote! {
+        struct Index<K, V> {
+            map: ::std::collections::HashMap<K, V>,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let map = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::generic::Walker::walk_map.INVOKE(map.reference());
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0, __T1> ::generic::VisitAll for Index<__T0, __T1>
+        where
+            __T0: ::std::hash::Hash,
+        {
+            fn visit_all(self) {
+                let __v0 = self;
+                let __v1 = __v0.map;
+                let __v2 = &__v1;
+                let _ = ::generic::Walker::walk_map(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_type_params() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T", "U", "V"]);
+    parent.set_path(|param_map: &mut SynParamMap| {
+        Path::path_from_str("::generic::Params", param_map)
+    });
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let t = Type::type_param_from_str("T", &mut param_map);
+    let u = Type::type_param_from_str("U", &mut param_map);
+
+    let ty = Path::path_from_str(
+        "::std::result::Result<(U, &T), ::generic::Wrapper<U>>",
+        &mut param_map,
+    )
+    .SELF();
+    assert_eq!(ty.type_params(), [u, t]);
+
+    let concrete = Path::path_from_str("::generic::Wrapper<u32>", &mut param_map).SELF();
+    assert!(concrete.type_params().is_empty());
+}
+
+const MIXED_BOUNDS: &str = "
+    struct Mixed<'a, 'b: 'a, T: ::generic::Bound + 'a, U>
+    where
+        ::generic::Wrapper<U>: ::generic::Bound,
+    {
+        one: &'a T,
+        two: &'b U,
+    }
+";
+
+fn derive_visit_all(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::generic::VisitAll, ex.target_type(), |block| {
+        block.make_function(RUNTIME::generic::VisitAll::visit_all, |make_function| {
+            make_function.unit()
+        });
+    });
+}
+
+#[test]
+fn test_where_clause_style_default() {
+    let input: proc_macro2::TokenStream = MIXED_BOUNDS.parse().unwrap();
+
+    let expected = quote! {
+        impl<'__a1, '__a2, __T0, __T1> ::generic::VisitAll for Mixed<'__a1, '__a2, __T0, __T1>
+        where
+            __T0: ::generic::Bound + '__a1,
+            ::generic::Wrapper<__T1>: ::generic::Bound,
+            '__a2: '__a1,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive_visit_all);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_where_clause_style_inline() {
+    let input: proc_macro2::TokenStream = MIXED_BOUNDS.parse().unwrap();
+
+    fn derive(ex: Execution) {
+        ex.set_codegen_options(CodegenOptions {
+            where_clause_style: WhereClauseStyle::Inline,
+            ..CodegenOptions::default()
+        });
+        derive_visit_all(ex);
+    }
+
+    // Only the predicate on `Wrapper<U>` cannot be written next to a param
+    let expected = quote! {
+        impl<'__a1, '__a2: '__a1, __T0: ::generic::Bound + '__a1, __T1>
+            ::generic::VisitAll for Mixed<'__a1, '__a2, __T0, __T1>
+        where
+            ::generic::Wrapper<__T1>: ::generic::Bound,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_constraint_order() {
+    let input = quote! {
+        struct Ordered<'a, 'b, T, U>
+        where
+            'b: 'a,
+            U: ::generic::Bound,
+            T: 'a,
+            ::generic::Wrapper<T>: ::generic::Bound,
+        {
+            one: &'a T,
+            two: &'b U,
+        }
+    };
+
+    // Type predicates sorted by the params of the bounded type and then by
+    // the bounds, then lifetime predicates, whatever the order they were
+    // declared in
+    let expected = quote! {
+        impl<'__a1, '__a2, __T0, __T1> ::generic::VisitAll for Ordered<'__a1, '__a2, __T0, __T1>
+        where
+            ::generic::Wrapper<__T0>: ::generic::Bound,
+            __T0: '__a1,
+            __T1: ::generic::Bound,
+            '__a2: '__a1,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive_visit_all);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_constraint_order_by_index() {
+    let input = quote! {
+        struct Many<T0, T1, T2, T3, T4, T5, T6, T7, T8, T9, T10>
+        where
+            T10: ::generic::Bound,
+            T2: ::generic::Bound,
+        {
+            first: T2,
+            second: T10,
+        }
+    };
+
+    // `__T2` comes before `__T10`, which it would not if the printed
+    // predicates were compared
+    let expected = quote! {
+        impl<__T0, __T1, __T2, __T3, __T4, __T5, __T6, __T7, __T8, __T9, __T10>
+            ::generic::VisitAll
+            for Many<__T0, __T1, __T2, __T3, __T4, __T5, __T6, __T7, __T8, __T9, __T10>
+        where
+            __T2: ::generic::Bound,
+            __T10: ::generic::Bound,
+        {
+            fn visit_all(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive_visit_all);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_maybe_sized_method_param() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    // The `?Sized` bound stays on the fresh params of the signature
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::generic::Inspect, ex.target_type(), |block| {
+            block.make_function(RUNTIME::generic::Inspect::inspect, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::generic::Inspect for Unit {
+            fn inspect<'__a1, '__a2, __T0>(&'__a1 self, __arg0: &'__a2 __T0)
+            where
+                __T0: ?::core::marker::Sized,
+            {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
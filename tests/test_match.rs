@@ -0,0 +1,67 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use visit {
+        trait Visit {
+            fn visit(&self);
+        }
+    }
+}
+
+#[test]
+fn test_match_generic_enum() {
+    let input = quote! {
+        enum Opt<T> {
+            Some(T),
+            Pair { first: T, second: T },
+            None,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::visit::Visit, ex.target_type(), |block| {
+            block.make_function(RUNTIME::visit::Visit::visit, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Enum(receiver) => receiver.match_variant(|variant| {
+                        for field in variant.fields() {
+                            RUNTIME::visit::Visit::visit.INVOKE(field.get_value());
+                        }
+                        make_function.unit()
+                    }),
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The fields are bound by the patterns, as references since `self` is
+    let expected = quote! {
+        impl<__T0> ::visit::Visit for Opt<__T0>
+        where
+            __T0: ::visit::Visit,
+        {
+            fn visit<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let _ = match __v0 {
+                    Self::Some(__v4) => {
+                        let _ = ::visit::Visit::visit(__v4);
+                        ()
+                    }
+                    Self::Pair { first: __v7, second: __v8 } => {
+                        let _ = ::visit::Visit::visit(__v7);
+                        let _ = ::visit::Visit::visit(__v8);
+                        ()
+                    }
+                    Self::None => {
+                        ()
+                    }
+                };
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
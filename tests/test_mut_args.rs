@@ -0,0 +1,154 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use counting {
+        trait Accumulate {
+            fn accumulate(&self, u32) -> u32;
+        }
+
+        trait Consume {
+            fn consume(self);
+        }
+
+        trait Settle {
+            fn settle(::core::option::Option<u32>) -> ::core::option::Option<u32>;
+        }
+
+        type Counter;
+
+        impl Counter {
+            fn bump(&mut u32);
+        }
+    }
+}
+
+#[test]
+fn test_mut_arg_reassigned() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::counting::Accumulate, ex.target_type(), |block| {
+            block.make_function(RUNTIME::counting::Accumulate::accumulate, |make_function| {
+                make_function.set_arg_mut(1);
+                let count = make_function.arg(1);
+                RUNTIME::counting::Counter::bump.INVOKE(count.reference_mut());
+                count
+            });
+        });
+    }
+
+    // The parameter is changed in place instead of being moved into a
+    // `let mut` binding first
+    let expected = quote! {
+        impl ::counting::Accumulate for Unit {
+            fn accumulate<'__a1>(&'__a1 self, mut __arg0: u32) -> u32 {
+                let __v1 = &mut __arg0;
+                let _ = ::counting::Counter::bump(__v1);
+                __arg0
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_mut_self() {
+    let input = quote! {
+        struct Count(u32);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::counting::Consume, ex.target_type(), |block| {
+            block.make_function(RUNTIME::counting::Consume::consume, |make_function| {
+                make_function.set_arg_mut(0);
+                let receiver = make_function.arg(0);
+                let count = receiver.reference_mut().get_index(0);
+                RUNTIME::counting::Counter::bump.INVOKE(count);
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::counting::Consume for Count {
+            fn consume(mut self) {
+                let __v1 = &mut self;
+                let __v2 = &mut __v1.0;
+                let _ = ::counting::Counter::bump(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_mut_self_field() {
+    let input = quote! {
+        struct Count(u32);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::counting::Consume, ex.target_type(), |block| {
+            block.make_function(RUNTIME::counting::Consume::consume, |make_function| {
+                make_function.set_arg_mut(0);
+                let count = make_function.arg(0).get_index(0);
+                RUNTIME::counting::Counter::bump.INVOKE(count.reference_mut());
+                make_function.unit()
+            });
+        });
+    }
+
+    // The field is read straight off the receiver, which has no `__v0`
+    // binding of its own
+    let expected = quote! {
+        impl ::counting::Consume for Count {
+            fn consume(mut self) {
+                let mut __v1 = self.0;
+                let __v2 = &mut __v1;
+                let _ = ::counting::Counter::bump(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_mut_arg_try_unwrap() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::counting::Settle, ex.target_type(), |block| {
+            block.make_function(RUNTIME::counting::Settle::settle, |make_function| {
+                make_function.set_arg_mut(0);
+                let count = make_function.arg(0).try_unwrap();
+                RUNTIME::counting::Counter::bump.INVOKE(count.reference_mut());
+                make_function.arg(0)
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::counting::Settle for Unit {
+            fn settle(mut __arg0: ::core::option::Option<u32>) -> ::core::option::Option<u32> {
+                let mut __v1 = __arg0?;
+                let __v2 = &mut __v1;
+                let _ = ::counting::Counter::bump(__v2);
+                __arg0
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,170 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use marker {
+        trait Marker {}
+
+        trait Visit {
+            fn visit(&self);
+        }
+
+        trait Make {
+            fn make() -> Self;
+        }
+    }
+}
+
+#[test]
+fn test_non_exhaustive_enum() {
+    let input = quote! {
+        #[non_exhaustive]
+        enum Event {
+            Open,
+            Close,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        assert!(ex.target_type().is_non_exhaustive());
+        ex.make_trait_impl(RUNTIME::marker::Marker, ex.target_type(), |_block| {});
+    }
+
+    let expected = quote! {
+        impl ::marker::Marker for Event {}
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_exhaustive_struct() {
+    let input = quote! {
+        struct Point {
+            x: ::std::string::String,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        assert!(!ex.target_type().is_non_exhaustive());
+        ex.make_trait_impl(RUNTIME::marker::Marker, ex.target_type(), |_block| {});
+    }
+
+    let expected = quote! {
+        impl ::marker::Marker for Point {}
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_match_non_exhaustive_enum() {
+    let input = quote! {
+        #[non_exhaustive]
+        enum Event<T> {
+            Open(T),
+            Close,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::marker::Visit, ex.target_type(), |block| {
+            block.make_function(RUNTIME::marker::Visit::visit, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Enum(receiver) => receiver.match_variant(|variant| {
+                        for field in variant.fields() {
+                            RUNTIME::marker::Visit::visit.INVOKE(field.get_value());
+                        }
+                        make_function.unit()
+                    }),
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::marker::Visit for Event<__T0>
+        where
+            __T0: ::marker::Visit,
+        {
+            fn visit<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let _ = match __v0 {
+                    Self::Open(__v2) => {
+                        let _ = ::marker::Visit::visit(__v2);
+                        ()
+                    }
+                    Self::Close => {
+                        ()
+                    }
+                    #[allow(unreachable_patterns)]
+                    _ => ::core::unreachable!(),
+                };
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+fn derive_make(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::marker::Make, ex.target_type(), |block| {
+        block.make_function(RUNTIME::marker::Make::make, |make_function| {
+            let x = RUNTIME::marker::Make::make.INVOKE();
+            let y = RUNTIME::marker::Make::make.INVOKE();
+            make_function.struct_literal(&[x, y])
+        });
+    });
+}
+
+#[test]
+fn test_struct_literal() {
+    let input = quote! {
+        struct Point<T> {
+            x: T,
+            y: T,
+        }
+    };
+
+    let expected = quote! {
+        impl<__T0> ::marker::Make for Point<__T0>
+        where
+            __T0: ::marker::Make,
+        {
+            fn make() -> Self {
+                let __v0 = ::marker::Make::make();
+                let __v1 = ::marker::Make::make();
+                let __v2 = Self { x: __v0, y: __v1 };
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive_make);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_non_exhaustive_struct_literal() {
+    let input = quote! {
+        #[non_exhaustive]
+        struct Point<T> {
+            x: T,
+            y: T,
+        }
+    };
+
+    let expected = quote! {
+        compile_error! {
+            "cannot create a value of the non-exhaustive struct `Point` with a struct literal"
+        }
+    };
+
+    let output = reflect::derive(input, derive_make);
+    assert_eq!(output.to_string(), expected.to_string());
+}
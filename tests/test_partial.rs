@@ -0,0 +1,124 @@
+use quote::quote;
+use reflect::runtime::RuntimeFunction;
+use reflect::*;
+
+library! {
+    use partial {
+        type Capacity;
+        type Builder;
+
+        impl Capacity {
+            fn default() -> Capacity;
+        }
+
+        impl Builder {
+            fn with_capacity(::std::string::String, Capacity) -> Builder;
+            fn labeled<T: ::std::fmt::Display>(T, Capacity) -> Builder;
+        }
+
+        trait Build {
+            fn build(self) -> Builder;
+        }
+    }
+}
+
+#[test]
+fn test_partial_apply() {
+    let input = quote! {
+        struct Named {
+            name: String,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::partial::Build, ex.target_type(), |block| {
+            block.make_function(RUNTIME::partial::Build::build, |make_function| {
+                let receiver = make_function.arg(0);
+
+                let capacity = RUNTIME::partial::Capacity::default.INVOKE();
+                let with_default_capacity = RUNTIME::partial::Builder::with_capacity
+                    .SELF()
+                    .partial_apply(&[(1, capacity)]);
+
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let name = receiver.fields().next().unwrap().get_value();
+                        with_default_capacity.invoke(&[name])
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::partial::Build for Named {
+            fn build(self) -> ::partial::Builder {
+                let __v0 = self;
+                let __v1 = ::partial::Capacity::default();
+                let __v4 = |__v2| {
+                    let __v3 = ::partial::Builder::with_capacity(__v2, __v1);
+                    __v3
+                };
+                let __v5 = __v0.name;
+                let __v6 = __v4(__v5);
+                __v6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_partial_apply_generic() {
+    let input = quote! {
+        struct Named<T> {
+            name: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::partial::Build, ex.target_type(), |block| {
+            block.make_function(RUNTIME::partial::Build::build, |make_function| {
+                let receiver = make_function.arg(0);
+
+                let capacity = RUNTIME::partial::Capacity::default.INVOKE();
+                let labeled = RUNTIME::partial::Builder::labeled
+                    .SELF()
+                    .partial_apply(&[(1, capacity)]);
+
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let name = receiver.fields().next().unwrap().get_value();
+                        labeled.invoke(&[name])
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::partial::Build for Named<__T0>
+        where
+            __T0: ::std::fmt::Display,
+        {
+            fn build(self) -> ::partial::Builder {
+                let __v0 = self;
+                let __v1 = ::partial::Capacity::default();
+                let __v4 = |__v2| {
+                    let __v3 = ::partial::Builder::labeled(__v2, __v1);
+                    __v3
+                };
+                let __v5 = __v0.name;
+                let __v6 = __v4(__v5);
+                __v6
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,180 @@
+use quote::quote;
+use reflect::runtime::RuntimeType;
+use reflect::*;
+
+#[test]
+fn test_strip_generics() {
+    let mut param_map = SynParamMap::new();
+    let map = Path::path_from_str("::std::collections::HashMap<K, V>", &mut param_map);
+    let bare = Path::path_from_str("::std::collections::HashMap", &mut param_map);
+    assert_eq!(map.strip_generics(), bare);
+    assert_eq!(bare.strip_generics(), bare);
+}
+
+#[test]
+fn test_type_arg_count() {
+    let mut param_map = SynParamMap::new();
+    let mut path = |path: &str| Path::path_from_str(path, &mut param_map);
+
+    let vec = path("Vec<T>");
+    assert_eq!(vec.type_arg_count(), 1);
+    assert!(vec.has_generics());
+
+    let map = path("::std::collections::HashMap<K, V>");
+    assert_eq!(map.type_arg_count(), 2);
+    assert!(map.has_generics());
+
+    let borrowed = path("Cow<'static, str>");
+    assert_eq!(borrowed.type_arg_count(), 1);
+
+    let string = path("::std::string::String");
+    assert_eq!(string.type_arg_count(), 0);
+    assert!(!string.has_generics());
+}
+
+#[test]
+fn test_push_segment() {
+    let mut param_map = SynParamMap::new();
+    let mut path = Path::path_from_str("::std", &mut param_map);
+    path.push_segment("collections");
+    path.push_segment("HashMap");
+
+    let expected = Path::path_from_str("::std::collections::HashMap", &mut param_map);
+    assert_eq!(path, expected);
+}
+
+#[test]
+fn test_join() {
+    let mut param_map = SynParamMap::new();
+    let std = Path::path_from_str("::std", &mut param_map);
+    let relative = Path::path_from_str("collections::HashMap", &mut param_map);
+
+    let expected = Path::path_from_str("::std::collections::HashMap", &mut param_map);
+    assert_eq!(std.join(&relative), expected);
+
+    let global = Path::path_from_str("::core::fmt", &mut param_map);
+    assert_eq!(relative.join(&global), global);
+}
+
+#[test]
+fn test_prepend_and_with_global() {
+    let mut param_map = SynParamMap::new();
+    let mut path = |path: &str| Path::path_from_str(path, &mut param_map);
+    let relative = path("geometry::Point<T>");
+
+    assert_eq!(
+        relative.prepend(&["my_crate"]).with_global(true),
+        path("::my_crate::geometry::Point<T>")
+    );
+    assert_eq!(
+        relative.prepend(&["my_crate", "shapes"]),
+        path("my_crate::shapes::geometry::Point<T>")
+    );
+    assert_eq!(relative.prepend(&[]), relative);
+
+    let global = path("::std::fmt");
+    assert_eq!(global.clone().with_global(false), path("std::fmt"));
+    assert_eq!(global.clone().with_global(true), global);
+}
+
+#[test]
+fn test_map_paths() {
+    let mut param_map = SynParamMap::new();
+    let ty = Path::path_from_str(
+        "crate::Wrapper<crate::Inner, ::std::string::String>",
+        &mut param_map,
+    )
+    .SELF();
+
+    let mapped = ty.map_paths(|path| path.replace_root("crate", "$crate"));
+    let expected = quote!($crate::Wrapper<$crate::Inner, ::std::string::String>);
+    assert_eq!(mapped.display(&param_map), expected.to_string());
+}
+
+#[test]
+fn test_walk_paths_mut() {
+    let mut param_map = SynParamMap::new();
+    let ty = Path::path_from_str(
+        "crate::Wrapper<crate::Inner, &[crate::Item; 4]>",
+        &mut param_map,
+    )
+    .SELF();
+
+    let mapped = ty.map_paths(|path| path.replace_root("crate", "$crate"));
+    let mut walked = ty.clone();
+    let mut visited = 0;
+    walked.walk_paths_mut(|path| {
+        *path = path.replace_root("crate", "$crate");
+        visited += 1;
+    });
+    assert_eq!(walked, mapped);
+    assert_eq!(visited, 3);
+}
+
+#[test]
+fn test_to_turbofish_string() {
+    let mut param_map = SynParamMap::new();
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    let vec = ty("Vec<u32>");
+    assert_eq!(vec.to_turbofish_string(), quote!(Vec::<u32>).to_string());
+
+    // Only the outer path is in expression position
+    let nested = ty("::std::collections::HashMap<String, Vec<Option<u8>>>");
+    let expected = ":: std :: collections :: HashMap :: < String , Vec < Option < u8 > > >";
+    assert_eq!(nested.to_turbofish_string(), expected);
+
+    let plain = ty("String");
+    assert_eq!(plain.to_turbofish_string(), quote!(String).to_string());
+}
+
+#[test]
+fn test_last_segment_mut() {
+    let mut param_map = SynParamMap::new();
+    let mut path = Path::path_from_str("::std::vec::Vec<T>", &mut param_map);
+
+    let segment = path.last_segment_mut().unwrap();
+    assert_eq!(segment.name(), "Vec");
+    let byte = Path::path_from_str("u8", &mut param_map).SELF();
+    segment.set_generic_arguments(vec![GenericArgument::Type(byte)]);
+    assert_eq!(
+        path,
+        Path::path_from_str("::std::vec::Vec<u8>", &mut param_map)
+    );
+
+    path.last_segment_mut()
+        .unwrap()
+        .set_generic_arguments(Vec::new());
+    assert_eq!(path, Path::path_from_str("::std::vec::Vec", &mut param_map));
+    assert!(path
+        .last_segment_mut()
+        .unwrap()
+        .generic_arguments()
+        .is_empty());
+}
+
+#[test]
+fn test_resolve_relative() {
+    let mut param_map = SynParamMap::new();
+    let mut path = |path: &str| Path::path_from_str(path, &mut param_map);
+    let expected = path("::shapes::geometry::Point<T>");
+
+    assert_eq!(
+        path("crate::geometry::Point<T>").resolve_relative("shapes"),
+        expected
+    );
+    assert_eq!(
+        path("self::Point").resolve_relative("shapes"),
+        path("Point")
+    );
+    assert_eq!(
+        path("self::super::Point").resolve_relative("shapes"),
+        path("super::Point")
+    );
+    assert_eq!(
+        path("super::Point").resolve_relative("shapes"),
+        path("super::Point")
+    );
+    assert_eq!(expected.resolve_relative("other"), expected);
+    assert_eq!(path("Point").resolve_relative("shapes"), path("Point"));
+}
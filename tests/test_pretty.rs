@@ -0,0 +1,173 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use pretty {
+        type Visitor;
+
+        impl Visitor {
+            fn visit<T: Visit>(&T);
+        }
+
+        trait Visit {
+            fn visit_all(&self);
+        }
+    }
+}
+
+fn derive_visit_all(ex: Execution) {
+    ex.make_trait_impl(RUNTIME::pretty::Visit, ex.target_type(), |block| {
+        block.make_function(RUNTIME::pretty::Visit::visit_all, |make_function| {
+            let receiver = make_function.arg(0);
+            match receiver.data() {
+                Data::Struct(Struct::Struct(receiver)) => {
+                    for field in receiver.fields() {
+                        RUNTIME::pretty::Visitor::visit.INVOKE(field.get_value());
+                    }
+                }
+                _ => unimplemented!(),
+            }
+            make_function.unit()
+        });
+    });
+}
+
+const VISIT_ALL_PAIR: &str = "\
+impl<__T0, __T1> ::pretty::Visit for Pair<__T0, __T1>
+where
+    __T0: ::pretty::Visit,
+    __T1: ::pretty::Visit,
+{
+    fn visit_all<'__a1>(&'__a1 self) {
+        let __v0 = self;
+        let __v1 = &__v0.first;
+        let __v2 = &__v0.second;
+        let _ = ::pretty::Visitor::visit(__v1);
+        let _ = ::pretty::Visitor::visit(__v2);
+    }
+}
+";
+
+#[test]
+fn test_pretty_string() {
+    let input = quote! {
+        struct Pair<A, B> {
+            first: A,
+            second: B,
+        }
+    };
+
+    let output = reflect::derive_to_pretty_string(input, derive_visit_all).unwrap();
+    assert_eq!(output, VISIT_ALL_PAIR);
+}
+
+#[test]
+fn test_impl_to_pretty_string() {
+    let input = quote! {
+        struct Pair<A, B> {
+            first: A,
+            second: B,
+        }
+    };
+
+    let output = reflect::derive_with_impls(input, derive_visit_all, |impls| {
+        assert_eq!(impls.len(), 1);
+        impls[0].to_pretty_string().unwrap()
+    })
+    .unwrap();
+    assert_eq!(output, VISIT_ALL_PAIR);
+}
+
+#[test]
+fn test_pretty_match() {
+    let tokens = quote! {
+        fn get(self) -> u32 {
+            let __v1 = match self {
+                Self::Some(__v2) => {
+                    let _ = ::pretty::Visitor::visit(__v2);
+                    1
+                }
+                Self::None => 0,
+                #[allow(unreachable_patterns)]
+                _ => ::core::unreachable!(),
+            };
+            __v1
+        }
+    };
+
+    let expected = "\
+fn get(self) -> u32 {
+    let __v1 = match self {
+        Self::Some(__v2) => {
+            let _ = ::pretty::Visitor::visit(__v2);
+            1
+        }
+        Self::None => 0,
+        #[allow(unreachable_patterns)]
+        _ => ::core::unreachable!(),
+    };
+    __v1
+}
+";
+    assert_eq!(reflect::pretty_print(tokens), expected);
+}
+
+#[test]
+fn test_pretty_closure() {
+    let tokens = quote! {
+        fn run(self) {
+            let __v1 = |x: u32| x + 1;
+            let __v2 = move |a, b| a || b;
+            let __v3 = || {
+                let _ = __v1(2);
+            };
+        }
+    };
+
+    let expected = "\
+fn run(self) {
+    let __v1 = |x: u32| x + 1;
+    let __v2 = move |a, b| a || b;
+    let __v3 = || {
+        let _ = __v1(2);
+    };
+}
+";
+    assert_eq!(reflect::pretty_print(tokens), expected);
+}
+
+#[test]
+fn test_pretty_nested_blocks() {
+    let tokens = quote! {
+        fn nested(self) {
+            {
+                let __v1 = 1;
+                {
+                    let __v2 = __v1;
+                }
+            }
+            if true {
+                let _ = 2;
+            } else {
+                let _ = 3;
+            }
+        }
+    };
+
+    let expected = "\
+fn nested(self) {
+    {
+        let __v1 = 1;
+        {
+            let __v2 = __v1;
+        }
+    }
+    if true {
+        let _ = 2;
+    } else {
+        let _ = 3;
+    }
+}
+";
+    assert_eq!(reflect::pretty_print(tokens), expected);
+}
@@ -0,0 +1,170 @@
+use quote::quote;
+use reflect::runtime::RuntimeType;
+use reflect::*;
+
+#[test]
+fn test_is_primitive() {
+    let mut param_map = SynParamMap::new();
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    assert!(ty("u32").is_primitive());
+    assert!(ty("bool").is_primitive());
+    assert!(ty("str").is_primitive());
+    assert!(Type::primitive_str().is_primitive());
+
+    assert!(!ty("::std::string::String").is_primitive());
+    assert!(!ty("u32::Foo").is_primitive());
+    assert!(!Type::primitive_str().reference().is_primitive());
+    assert!(!Type::unit().is_primitive());
+}
+
+#[test]
+fn test_is_str_and_string() {
+    let mut param_map = SynParamMap::new();
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+    let string = ty("::std::string::String");
+
+    assert!(Type::primitive_str().is_str());
+    assert!(Type::primitive_str().reference().is_str());
+    assert!(ty("str").reference().reference_mut().is_str());
+    assert!(!string.is_str());
+    assert!(!ty("::std::str").is_str());
+
+    assert!(string.is_string());
+    assert!(ty("String").is_string());
+    assert!(!string.reference().is_string());
+    assert!(!Type::primitive_str().is_string());
+    assert!(!ty("Wrapper<String>").is_string());
+}
+
+#[test]
+fn test_is_definitely_unsized() {
+    let mut param_map = SynParamMap::new();
+    let generics = Generics::from_str("<T: ?Sized, U>", &mut param_map).unwrap();
+    let maybe_unsized = Type::type_param_from_str("T", &mut param_map);
+    let sized = Type::type_param_from_str("U", &mut param_map);
+    let unsized_ty = |ty: Type| ty.is_definitely_unsized(&generics);
+
+    assert!(unsized_ty(Type::primitive_str()));
+    assert!(unsized_ty(sized.slice()));
+    assert!(unsized_ty(Type::get_trait_object(
+        &["::std::fmt::Debug"],
+        &mut param_map
+    )));
+    assert!(unsized_ty(maybe_unsized.clone()));
+
+    assert!(!unsized_ty(sized));
+    assert!(!unsized_ty(Type::primitive_str().reference()));
+    assert!(!unsized_ty(Type::boxed_dyn(
+        &["::std::fmt::Debug"],
+        &mut param_map
+    )));
+    assert!(!unsized_ty(Type::unit()));
+
+    // Other generics do not relax the bound of `T`
+    assert!(
+        !maybe_unsized.is_definitely_unsized(&Generics::from_str("<T>", &mut param_map).unwrap())
+    );
+}
+
+#[test]
+fn test_is_generic() {
+    let mut parent = ParentBuilder::new(ParentKind::Trait);
+    parent.set_generic_params(&["T"]);
+    parent
+        .set_path(|param_map: &mut SynParamMap| Path::path_from_str("::sizes::Unsized", param_map));
+    let mut param_map = parent.into_parent().get_param_map().clone();
+    let param = Type::type_param_from_str("T", &mut param_map);
+    let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+
+    assert!(param.is_generic());
+    assert!(Type::tuple(&[ty("u8"), param.reference()]).is_generic());
+    assert!(ty("::std::vec::Vec<u8>").is_generic());
+    assert!(ty("::std::boxed::Box<dyn ::std::ops::Fn(u8)>").is_generic());
+
+    assert!(!ty("u8").is_generic());
+    assert!(!ty("::std::string::String").reference().is_generic());
+    assert!(!Type::unit().is_generic());
+}
+
+#[test]
+fn test_is_known_copy() {
+    let input = quote! {
+        struct Handle(u32);
+    };
+
+    fn derive(ex: Execution) {
+        let mut param_map = SynParamMap::new();
+        let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+        let handle = ex.target_type();
+
+        assert!(ty("u32").is_known_copy());
+        assert!(handle.reference().is_known_copy());
+        assert!(Type::tuple(&[ty("bool"), ty("char")]).is_known_copy());
+
+        assert!(!ty("str").is_known_copy());
+        assert!(!ty("::std::string::String").is_known_copy());
+        assert!(!ty("u32").reference_mut().is_known_copy());
+        assert!(!handle.is_known_copy());
+
+        ex.register_copy_type(handle.clone());
+        assert!(handle.is_known_copy());
+    }
+
+    reflect::derive(input, derive);
+}
+
+#[test]
+fn test_may_impl_drop() {
+    let input = quote! {
+        struct Guard {
+            name: ::std::string::String,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let mut param_map = SynParamMap::new();
+        let mut ty = |path: &str| Path::path_from_str(path, &mut param_map).SELF();
+        let guard = ex.target_type();
+        let string = ty("::std::string::String");
+
+        assert!(!ty("u32").may_impl_drop());
+        assert!(!guard.reference().may_impl_drop());
+        assert!(!Type::tuple(&[guard.clone(), string.clone()]).may_impl_drop());
+
+        // Nothing is known about these, so they are assumed to implement `Drop`
+        assert!(guard.may_impl_drop());
+        assert!(string.may_impl_drop());
+        assert!(Type::option(&guard).may_impl_drop());
+
+        ex.register_drop_free_type(guard.clone());
+        assert!(!guard.may_impl_drop());
+        assert!(string.may_impl_drop());
+    }
+
+    reflect::derive(input, derive);
+}
+
+#[test]
+fn test_as_bare_fn() {
+    let mut param_map = SynParamMap::new();
+    // A function pointer is parsed as a generic argument, as there is no
+    // parser for a bare type
+    let mut arg = |path: &str| {
+        let path = Path::path_from_str(path, &mut param_map);
+        path.generic_arguments()[0].as_type().unwrap().clone()
+    };
+    let number = arg("Wrapper<u32>");
+    let boolean = arg("Wrapper<bool>");
+
+    let predicate = arg("Callback<fn(u32) -> bool>");
+    assert_eq!(
+        predicate.as_bare_fn(),
+        Some((vec![&number], Some(&boolean)))
+    );
+
+    let thunk = arg("Callback<fn()>");
+    assert_eq!(thunk.as_bare_fn(), Some((Vec::new(), None)));
+
+    assert_eq!(number.as_bare_fn(), None);
+}
@@ -0,0 +1,120 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use std {
+        mod string {
+            type String;
+        }
+    }
+    use proj {
+        type Wrapper<T>;
+
+        trait Iterable {}
+
+        type Consumer;
+
+        impl Consumer {
+            fn first<U>(Wrapper<U>) -> <Wrapper<U> as Iterable>::Item;
+            fn consume(::std::string::String);
+        }
+
+        trait Consume {
+            fn consume(self);
+        }
+    }
+}
+
+#[test]
+fn test_registered_projection() {
+    let input = quote! {
+        struct Holder<T> {
+            value: ::proj::Wrapper<T>,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.register_projection(
+            &["T"],
+            "<::proj::Wrapper<T> as ::proj::Iterable>::Item",
+            "T",
+        );
+        ex.make_trait_impl(RUNTIME::proj::Consume, ex.target_type(), |block| {
+            block.make_function(RUNTIME::proj::Consume::consume, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let value = receiver.fields().next().unwrap().get_value();
+                        let first = RUNTIME::proj::Consumer::first.INVOKE(value);
+                        RUNTIME::proj::Consumer::consume.INVOKE(first);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The item of `Wrapper<T>` is `T`, which is passed on as a `String`
+    let expected = quote! {
+        impl ::proj::Consume for Holder<::std::string::String> {
+            fn consume(self) {
+                let __v0 = self;
+                let __v1 = __v0.value;
+                let __v2 = ::proj::Consumer::first(__v1);
+                let _ = ::proj::Consumer::consume(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_projection_without_trait() {
+    let input = quote! {
+        struct Holder {
+            value: <::proj::Wrapper<u8>>::Item,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        let ty = match ex.target_type().data() {
+            Data::Struct(Struct::Struct(data)) => data.fields().next().unwrap().get_type(),
+            _ => unimplemented!(),
+        };
+        assert_eq!(
+            ty.display(&SynParamMap::new()),
+            "< :: proj :: Wrapper < u8 > > :: Item"
+        );
+    }
+
+    reflect::derive(input, derive);
+}
+
+#[test]
+fn test_nested_projection() {
+    let input = quote! {
+        struct Holder<T> {
+            value: <T as ::proj::Iterable>::Item::Inner,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::proj::Consume, ex.target_type(), |block| {
+            block.make_function(RUNTIME::proj::Consume::consume, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        compile_error! {
+            "only projections of a single associated type, like `<T as Trait>::Assoc`, are supported"
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
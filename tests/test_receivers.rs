@@ -0,0 +1,73 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use receivers {
+        trait Boxed {
+            fn boxed(self: ::std::boxed::Box<Self>);
+        }
+
+        trait Shared {
+            fn share(self: ::std::rc::Rc<Self>);
+        }
+
+        trait Combine<T> {
+            fn combine(self: ::std::rc::Rc<Self>, ::std::rc::Rc<T>);
+        }
+    }
+}
+
+#[test]
+fn test_box_receiver() {
+    let input = quote! {
+        struct Wrapper<T>(T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::receivers::Boxed, ex.target_type(), |block| {
+            block.make_function(RUNTIME::receivers::Boxed::boxed, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::receivers::Boxed for Wrapper<__T0> {
+            fn boxed(self: ::std::boxed::Box<Self>) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_rc_receiver() {
+    let input = quote! {
+        struct Wrapper<T>(T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::receivers::Combine, ex.target_type(), |block| {
+            block.make_function(RUNTIME::receivers::Combine::combine, |make_function| {
+                let other = make_function.arg(1);
+                RUNTIME::receivers::Shared::share.INVOKE(other)
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0, __T1> ::receivers::Combine<__T1> for Wrapper<__T0>
+        where
+            __T1: ::receivers::Shared,
+        {
+            fn combine(self: ::std::rc::Rc<Self>, __arg0: ::std::rc::Rc<__T1>) {
+                let __v0 = __arg0;
+                let _ = ::receivers::Shared::share(__v0);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,181 @@
+use quote::quote;
+use reflect::runtime::RuntimeType;
+use reflect::*;
+
+library! {
+    use fallible {
+        trait Check<E> {
+            fn check(&self) -> ::core::result::Result<(), E>;
+        }
+
+        trait Lookup {
+            fn lookup(&self) -> ::core::option::Option<()>;
+        }
+
+        type Checker;
+
+        impl Checker {
+            fn check_field<T>(&T) -> ::core::result::Result<(), T>;
+            fn find_field<T>(&T) -> ::core::option::Option<()>;
+        }
+    }
+}
+
+#[test]
+fn test_result_round_trip() {
+    let mut param_map = SynParamMap::new();
+    let ok = Path::path_from_str("::std::string::String", &mut param_map).SELF();
+    let err = Type::unit();
+
+    let result = Type::result(&ok, &err);
+    assert_eq!(result.result_types(), Some((&ok, &err)));
+
+    let parsed = Path::path_from_str(
+        "::core::result::Result<::std::string::String, ()>",
+        &mut param_map,
+    )
+    .SELF();
+    assert_eq!(parsed, result);
+}
+
+#[test]
+fn test_result_types_of_other_types() {
+    let mut param_map = SynParamMap::new();
+    let option = Path::path_from_str("::core::option::Option<u8>", &mut param_map).SELF();
+    assert_eq!(option.result_types(), None);
+
+    let io_result = Path::path_from_str("::std::io::Result<u8>", &mut param_map).SELF();
+    assert_eq!(io_result.result_types(), None);
+
+    assert_eq!(Type::unit().result_types(), None);
+}
+
+#[test]
+fn test_option_type() {
+    let mut param_map = SynParamMap::new();
+    let option = Path::path_from_str("::core::option::Option<u8>", &mut param_map).SELF();
+    let byte = Path::path_from_str("u8", &mut param_map).SELF();
+    assert_eq!(option.option_type(), Some(&byte));
+    assert_eq!(Type::result(&byte, &byte).option_type(), None);
+}
+
+#[test]
+fn test_try_operator() {
+    let input = quote! {
+        struct Checked<A, B> {
+            first: A,
+            second: B,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::fallible::Check, ex.target_type(), |block| {
+            block.make_function(RUNTIME::fallible::Check::check, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let mut fields = receiver.fields();
+                        let first = fields.next().unwrap().get_value();
+                        let second = fields.next().unwrap().get_value();
+                        RUNTIME::fallible::Checker::check_field
+                            .INVOKE(first)
+                            .try_unwrap();
+                        RUNTIME::fallible::Checker::check_field.INVOKE(second)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The error of the first check is converted to the error of the function
+    let expected = quote! {
+        impl<__T0, __T1> ::fallible::Check<__T1> for Checked<__T0, __T1>
+        where
+            __T1: ::core::convert::From<__T0>,
+        {
+            fn check<'__a1>(&'__a1 self) -> ::core::result::Result<(), __T1> {
+                let __v0 = self;
+                let __v1 = &__v0.first;
+                let __v2 = &__v0.second;
+                let __v3 = ::fallible::Checker::check_field(__v1);
+                let _ = __v3?;
+                let __v5 = ::fallible::Checker::check_field(__v2);
+                __v5
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_try_option_in_result_fn() {
+    let input = quote! {
+        struct Checked<A> {
+            first: A,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::fallible::Check, ex.target_type(), |block| {
+            block.make_function(RUNTIME::fallible::Check::check, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let first = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::fallible::Checker::find_field
+                            .INVOKE(first)
+                            .try_unwrap()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        compile_error! {
+            "the `?` operator on `:: core :: option :: Option < () >` can not be used in a function that returns `:: core :: result :: Result < () , _ >`"
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_try_result_in_option_fn() {
+    let input = quote! {
+        struct Checked<A> {
+            first: A,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::fallible::Lookup, ex.target_type(), |block| {
+            block.make_function(RUNTIME::fallible::Lookup::lookup, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let first = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::fallible::Checker::check_field
+                            .INVOKE(first)
+                            .try_unwrap()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        compile_error! {
+            "the `?` operator on `:: core :: result :: Result < () , _ >` can not be used in a function that returns `:: core :: option :: Option < () >`"
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,48 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use error {
+        trait Boxed {
+            type Error;
+            fn boxed(self);
+        }
+    }
+}
+
+// The canonical trait object regression case: several bounds, auto traits
+// and a lifetime bound in one `Box<dyn ...>`, which must come out exactly as
+// it went in
+#[test]
+fn test_boxed_error_roundtrip() {
+    let input = quote! {
+        struct Failure {
+            source: ::std::boxed::Box<dyn ::std::error::Error + Send + Sync + 'static>,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::error::Boxed, ex.target_type(), |block| {
+            // The type comes from the reflected input, not from parsing the
+            // expected string again
+            let source = match block.target_type().data() {
+                Data::Struct(Struct::Struct(data)) => data.fields().next().unwrap().get_type(),
+                _ => unimplemented!(),
+            };
+            block.make_assoc_type("Error", source);
+            block.make_function(RUNTIME::error::Boxed::boxed, |make_function| {
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::error::Boxed for Failure {
+            type Error = ::std::boxed::Box<dyn ::std::error::Error + Send + Sync + 'static>;
+            fn boxed(self) {}
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
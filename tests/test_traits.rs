@@ -1,4 +1,5 @@
 use quote::quote;
+use reflect::runtime::{RuntimeFunction, RuntimeType};
 use reflect::*;
 
 library! {
@@ -16,11 +17,49 @@ library! {
             fn single_impl(&impl Trait);
             fn double_impl(&(impl Trait + AutoTrait));
             fn generic_impl<T>(&(impl Generic<T> + 'static));
+
+            fn boxed_dyn(&::std::boxed::Box<dyn ::std::marker::Send + Generic<Struct>>);
+
+            fn self_generic<T: Generic<T>>(&T);
+
+            fn print(impl ::std::fmt::Display);
+            fn print_all(impl ::std::iter::IntoIterator<Item = impl ::std::fmt::Display>);
+        }
+
+        trait Consume {
+            fn consume(self);
+        }
+
+        trait Create {
+            fn create() -> Self;
+        }
+
+        trait Release {
+            fn release(self);
         }
 
          trait SimpleTrait {
             fn simple(&self);
         }
+
+        trait Step {
+            fn checked_step(&self) -> ::std::option::Option<Self>;
+        }
+
+        trait Peek<T> {
+            fn peek(&self) -> ::std::option::Option<T>;
+        }
+
+        trait Label {
+            fn label(&self) where Self: ::std::convert::AsRef<::std::ffi::OsStr>;
+        }
+
+        trait Greet {
+            fn name(self) -> ::std::string::String;
+            fn greet(self) {
+                println!("Hello, {}!", self.name());
+            }
+        }
     }
 }
 
@@ -63,10 +102,10 @@ fn test_dyn_traits() {
     let expected = quote! {
         impl<__T0, __T1> ::Mod::SimpleTrait for Generic<__T0, __T1>
         where
+            __T0: ::Mod::Trait,
             __T0: ::Mod::Trait + ::Mod::AutoTrait,
-            __T1: ::Mod::Trait + ::Mod::AutoTrait,
             __T1: ::Mod::Trait,
-            __T0: ::Mod::Trait,
+            __T1: ::Mod::Trait + ::Mod::AutoTrait,
         {
             fn simple<'__a1>(&'__a1 self) {
                 let __v0 = self;
@@ -126,10 +165,10 @@ fn test_impl_traits() {
     let expected = quote! {
         impl<__T0, __T1> ::Mod::SimpleTrait for Generic<__T0, __T1>
         where
+            __T0: ::Mod::Trait,
             __T0: ::Mod::Trait + ::Mod::AutoTrait,
-            __T1: ::Mod::Trait + ::Mod::AutoTrait,
             __T1: ::Mod::Trait,
-            __T0: ::Mod::Trait,
+            __T1: ::Mod::Trait + ::Mod::AutoTrait,
         {
             fn simple<'__a1>(&'__a1 self) {
                 let __v0 = self;
@@ -149,3 +188,512 @@ fn test_impl_traits() {
     let actual = reflect::derive(input, derive2);
     assert_eq!(actual.to_string(), expected.to_string());
 }
+
+#[test]
+fn test_compound_trait_object_bounds() {
+    let mut param_map = SynParamMap::new();
+
+    let compound = Type::get_trait_object(&["::std::io::Read + ::std::io::Write"], &mut param_map);
+    let separate = Type::get_trait_object(&["::std::io::Read", "::std::io::Write"], &mut param_map);
+    assert_eq!(compound, separate);
+
+    let compound = Type::get_trait_object(
+        &["::std::iter::Iterator<Item = u32> + Send", "'static"],
+        &mut param_map,
+    );
+    let separate = Type::get_trait_object(
+        &["::std::iter::Iterator<Item = u32>", "Send", "'static"],
+        &mut param_map,
+    );
+    assert_eq!(compound, separate);
+}
+
+#[test]
+fn test_erase_to_trait_object() {
+    let mut param_map = SynParamMap::new();
+    let point = Path::path_from_str("::geometry::Point", &mut param_map).SELF();
+    let erased = point.erase_to_trait_object(&["::std::fmt::Display"], &mut param_map);
+    assert_eq!(
+        *erased.trait_object(),
+        Type::get_trait_object(&["::std::fmt::Display"], &mut param_map)
+    );
+    assert_eq!(
+        erased.trait_object().display(&param_map),
+        quote!((dyn ::std::fmt::Display)).to_string()
+    );
+
+    // A borrowing type is only erased for as long as it borrows
+    Generics::from_str("<'a>", &mut param_map).unwrap();
+    let view = Path::path_from_str("::geometry::View<'a, &'static str>", &mut param_map).SELF();
+    let erased = view.erase_to_trait_object(&["::std::fmt::Display"], &mut param_map);
+    assert_eq!(
+        erased.trait_object().display(&param_map),
+        quote!((dyn ::std::fmt::Display + 'a)).to_string()
+    );
+
+    // The erased type can be recovered, e.g. to downcast to it
+    assert_eq!(*erased.original(), view);
+    assert_eq!(
+        erased.original().display(&param_map),
+        quote!(::geometry::View<'a, &'static str>).to_string()
+    );
+}
+
+#[test]
+fn test_unordered_trait_object_bounds() {
+    let input = quote! {
+        struct Boxed<T> {
+            inner: ::std::boxed::Box<dyn ::Mod::Generic<T> + ::std::marker::Send>,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::SimpleTrait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::SimpleTrait::simple, |f| {
+                let receiver = f.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::Mod::Struct::boxed_dyn.INVOKE(field.get_value());
+                        }
+                        f.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::Mod::SimpleTrait for Boxed<::Mod::Struct> {
+            fn simple<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.inner;
+                let _ = ::Mod::Struct::boxed_dyn(__v1);
+            }
+        }
+    };
+
+    let actual = reflect::derive(input, derive);
+    assert_eq!(actual.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_default_fn_output_is_unit() {
+    let mut param_map = SynParamMap::new();
+
+    let default = Type::get_trait_object(&["::std::ops::Fn(T)"], &mut param_map);
+    let unit = Type::get_trait_object(&["::std::ops::Fn(T) -> ()"], &mut param_map);
+    assert_eq!(default, unit);
+
+    let default = Type::get_impl_trait(&["FnMut()"], &mut param_map);
+    let unit = Type::get_impl_trait(&["FnMut() -> ()"], &mut param_map);
+    assert_eq!(default, unit);
+}
+
+#[test]
+fn test_merged_declared_bounds() {
+    let input = quote! {
+        struct Merged<T: ::Mod::Trait + ::Mod::AutoTrait>
+        where
+            T: ::Mod::AutoTrait + ::Mod::Trait,
+            T: ::Mod::Trait,
+        {
+            inner: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::SimpleTrait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::SimpleTrait::simple, |f| f.unit());
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::Mod::SimpleTrait for Merged<__T0>
+        where
+            __T0: ::Mod::Trait + ::Mod::AutoTrait,
+        {
+            fn simple<'__a1>(&'__a1 self) {}
+        }
+    };
+
+    let actual = reflect::derive(input, derive);
+    assert_eq!(actual.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_boxed_dyn() {
+    let mut param_map = SynParamMap::new();
+
+    let boxed = Type::boxed_dyn(&["::std::error::Error + Send"], &mut param_map);
+    let parsed = Path::path_from_str(
+        "::std::boxed::Box<dyn ::std::error::Error + Send>",
+        &mut param_map,
+    );
+    assert_eq!(boxed, parsed.SELF());
+}
+
+#[test]
+fn test_self_referential_bound() {
+    let input = quote! {
+        struct Recursive<T>
+        where
+            T: ::Mod::Generic<T>,
+        {
+            inner: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::SimpleTrait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::SimpleTrait::simple, |f| {
+                let receiver = f.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::Mod::Struct::self_generic.INVOKE(field.get_value());
+                        }
+                        f.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::Mod::SimpleTrait for Recursive<__T0>
+        where
+            __T0: ::Mod::Generic<__T0>,
+        {
+            fn simple<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.inner;
+                let _ = ::Mod::Struct::self_generic(__v1);
+            }
+        }
+    };
+
+    let actual = reflect::derive(input, derive);
+    assert_eq!(actual.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_impl_trait_argument() {
+    let input = quote! {
+        struct Labeled<T, U> {
+            label: T,
+            value: U,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::Consume, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::Consume::consume, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::Mod::Struct::print.INVOKE(field.get_value());
+                        }
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // Each call gets its own anonymous param, so both fields are bounded
+    let expected = quote! {
+        impl<__T0, __T1> ::Mod::Consume for Labeled<__T0, __T1>
+        where
+            __T0: ::std::fmt::Display,
+            __T1: ::std::fmt::Display,
+        {
+            fn consume(self) {
+                let __v0 = self;
+                let __v1 = __v0.label;
+                let __v2 = __v0.value;
+                let _ = ::Mod::Struct::print(__v1);
+                let _ = ::Mod::Struct::print(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_qualified_trait_call() {
+    let input = quote! {
+        struct Slot<T>(T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::Consume, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::Consume::consume, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        let value = receiver.fields().next().unwrap().get_value();
+                        let ty = value.get_type();
+                        let created = RUNTIME::Mod::Create::create
+                            .SELF()
+                            .invoke_qualified(&ty, &[]);
+                        RUNTIME::Mod::Release::release
+                            .SELF()
+                            .invoke_qualified(&ty, &[created]);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // `create` has no argument of type `Self`, so the bound on `T` only comes
+    // from the trait path of the call
+    let expected = quote! {
+        impl<__T0> ::Mod::Consume for Slot<__T0>
+        where
+            __T0: ::Mod::Create,
+            __T0: ::Mod::Release,
+        {
+            fn consume(self) {
+                let __v2 = <__T0 as ::Mod::Create>::create();
+                let _ = <__T0 as ::Mod::Release>::release(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_self_in_generic_output() {
+    let input = quote! {
+        struct Slot<T>(T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::Peek, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::Peek::peek, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        let value = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::Mod::Step::checked_step.INVOKE(value)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // `checked_step` on a `&T` returns `Option<T>`, which decides the
+    // argument of `Peek`
+    let expected = quote! {
+        impl<__T0> ::Mod::Peek<__T0> for Slot<__T0>
+        where
+            __T0: ::Mod::Step,
+        {
+            fn peek<'__a1>(&'__a1 self) -> ::std::option::Option<__T0> {
+                let __v0 = self;
+                let __v1 = &__v0.0;
+                let __v2 = ::Mod::Step::checked_step(__v1);
+                __v2
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_method_self_bound() {
+    let input = quote! {
+        struct Named<T>(T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::SimpleTrait, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::SimpleTrait::simple, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        let value = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::Mod::Label::label.INVOKE(value);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The `where Self:` bound of `label` becomes a bound on the field type
+    let expected = quote! {
+        impl<__T0> ::Mod::SimpleTrait for Named<__T0>
+        where
+            __T0: ::Mod::Label,
+            __T0: ::std::convert::AsRef<::std::ffi::OsStr>,
+        {
+            fn simple<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.0;
+                let _ = ::Mod::Label::label(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_blanket_impl() {
+    let input = quote! {
+        struct Unused;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_blanket_impl(RUNTIME::Mod::SimpleTrait, &["::Mod::Step"], |block| {
+            block.make_function(RUNTIME::Mod::SimpleTrait::simple, |make_function| {
+                let receiver = make_function.arg(0);
+                RUNTIME::Mod::Step::checked_step.INVOKE(receiver);
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0: ::Mod::Step> ::Mod::SimpleTrait for __T0 {
+            fn simple<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let _ = ::Mod::Step::checked_step(__v0);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_default_method() {
+    assert!(!RUNTIME::Mod::Greet::name.SELF().has_default());
+    assert!(RUNTIME::Mod::Greet::greet.SELF().has_default());
+
+    let input = quote! {
+        struct Person {
+            name: ::std::string::String,
+        }
+    };
+
+    // Only the method without a default body is implemented
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::Greet, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::Greet::name, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        receiver.fields().next().unwrap().get_value()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::Mod::Greet for Person {
+            fn name(self) -> ::std::string::String {
+                let __v0 = self;
+                let __v1 = __v0.name;
+                __v1
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_blanket_impl_inferred_bound() {
+    let input = quote! {
+        struct Unused;
+    };
+
+    // No bounds are declared, so the bound comes from the trait inference
+    fn derive(ex: Execution) {
+        ex.make_blanket_impl(RUNTIME::Mod::SimpleTrait, &[], |block| {
+            block.make_function(RUNTIME::Mod::SimpleTrait::simple, |make_function| {
+                let receiver = make_function.arg(0);
+                RUNTIME::Mod::Step::checked_step.INVOKE(receiver);
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0: ::Mod::Step> ::Mod::SimpleTrait for __T0 {
+            fn simple<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let _ = ::Mod::Step::checked_step(__v0);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_impl_trait_in_binding() {
+    let input = quote! {
+        struct Labels<T> {
+            labels: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::Mod::Consume, ex.target_type(), |block| {
+            block.make_function(RUNTIME::Mod::Consume::consume, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let labels = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::Mod::Struct::print_all.INVOKE(labels);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The inner `impl Display` is the `Item` of the outer param rather than
+    // a param of its own
+    let expected = quote! {
+        impl<__T0> ::Mod::Consume for Labels<__T0>
+        where
+            <__T0 as ::std::iter::IntoIterator>::Item: ::std::fmt::Display,
+            __T0: ::std::iter::IntoIterator,
+        {
+            fn consume(self) {
+                let __v0 = self;
+                let __v1 = __v0.labels;
+                let _ = ::Mod::Struct::print_all(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,175 @@
+use quote::quote;
+use reflect::runtime::{RuntimeFunction, RuntimeType};
+use reflect::*;
+
+library! {
+    use std {
+        mod iter {
+            trait Iterator {
+                fn collect<B>(self) -> B;
+            }
+        }
+        mod string {
+            type String;
+        }
+    }
+    use factory {
+        type Factory;
+        type Wrapper<T>;
+
+        trait Build {
+            fn build(self);
+        }
+
+        impl Factory {
+            fn make<T>() -> T;
+            fn wrap<T>(T) -> Wrapper<T>;
+            fn consume(::std::string::String);
+            fn pair<U>(U, U);
+        }
+    }
+}
+
+#[test]
+fn test_turbofish() {
+    let input = quote! {
+        struct Lines<I>(I);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::std::iter::Iterator, ex.target_type(), |block| {
+            block.make_function(RUNTIME::std::iter::Iterator::collect, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        let iter = receiver.fields().next().unwrap().get_value();
+                        let mut param_map = SynParamMap::new();
+                        let vec = Path::path_from_str("::std::vec::Vec<_>", &mut param_map).SELF();
+                        RUNTIME::std::iter::Iterator::collect
+                            .SELF()
+                            .invoke_turbofish(&[vec], &[iter]);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::std::iter::Iterator for Lines<__T0>
+        where
+            __T0: ::std::iter::Iterator,
+        {
+            fn collect<__T1>(self) -> __T1 {
+                let __v0 = self;
+                let __v1 = __v0.0;
+                let _ = ::std::iter::Iterator::collect::<::std::vec::Vec<_> >(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+// Nothing passed to `make` decides its `T`, so the inferred type is given
+// in a turbofish
+#[test]
+fn test_inferred_turbofish() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::factory::Build, ex.target_type(), |block| {
+            block.make_function(RUNTIME::factory::Build::build, |make_function| {
+                let made = RUNTIME::factory::Factory::make.INVOKE();
+                RUNTIME::factory::Factory::consume.INVOKE(made);
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::factory::Build for Unit {
+            fn build(self) {
+                let __v0 = ::factory::Factory::make::<::std::string::String>();
+                let _ = ::factory::Factory::consume(__v0);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+// The argument of `wrap` decides its `T`, so no turbofish is needed
+#[test]
+fn test_turbofish_not_needed() {
+    let input = quote! {
+        struct Holder<T>(T);
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::factory::Build, ex.target_type(), |block| {
+            block.make_function(RUNTIME::factory::Build::build, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Tuple(receiver)) => {
+                        let value = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::factory::Factory::wrap.INVOKE(value);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::factory::Build for Holder<__T0> {
+            fn build(self) {
+                let __v0 = self;
+                let __v1 = __v0.0;
+                let _ = ::factory::Factory::wrap(__v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+// Both calls to `make` resolve to the `U` of `pair`, which is not a param
+// of the impl, so it is left for Rust to infer
+#[test]
+fn test_turbofish_other_call_param() {
+    let input = quote! {
+        struct Unit;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::factory::Build, ex.target_type(), |block| {
+            block.make_function(RUNTIME::factory::Build::build, |make_function| {
+                let first = RUNTIME::factory::Factory::make.INVOKE();
+                let second = RUNTIME::factory::Factory::make.INVOKE();
+                RUNTIME::factory::Factory::pair.INVOKE(first, second);
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl ::factory::Build for Unit {
+            fn build(self) {
+                let __v0 = ::factory::Factory::make();
+                let __v1 = ::factory::Factory::make();
+                let _ = ::factory::Factory::pair(__v0, __v1);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
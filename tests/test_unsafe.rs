@@ -0,0 +1,78 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use std {
+        mod marker {
+            unsafe trait Send {}
+        }
+    }
+    use raw {
+        unsafe trait Raw {
+            unsafe fn raw(&self);
+        }
+
+        type Pointer;
+
+        impl Pointer {
+            unsafe fn read(&::std::string::String);
+        }
+    }
+}
+
+#[test]
+fn test_unsafe_impl() {
+    let input = quote! {
+        struct Foo;
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::std::marker::Send, ex.target_type(), |_block| {});
+    }
+
+    let expected = quote! {
+        unsafe impl ::std::marker::Send for Foo {}
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_unsafe_fn() {
+    let input = quote! {
+        struct Foo {
+            name: ::std::string::String,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::raw::Raw, ex.target_type(), |block| {
+            block.make_function(RUNTIME::raw::Raw::raw, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let name = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::raw::Pointer::read.INVOKE(name);
+                        make_function.unit()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // Calling an `unsafe fn` needs an `unsafe` block
+    let expected = quote! {
+        unsafe impl ::raw::Raw for Foo {
+            unsafe fn raw<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.name;
+                let _ = unsafe { ::raw::Pointer::read(__v1) };
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
@@ -0,0 +1,134 @@
+use quote::quote;
+use reflect::*;
+
+library! {
+    use verbatim {
+        type Inspector;
+        impl Inspector {
+            fn inspect<T>(T);
+            fn inspect_string(::std::string::String);
+        }
+
+        trait Inspect {
+            fn inspect_all(&self);
+        }
+
+        trait Erase<T> {
+            fn erase(self) -> T;
+        }
+    }
+}
+
+#[test]
+fn test_verbatim_field() {
+    // `dyn*` does not parse as a structured type
+    let input = quote! {
+        struct Erased<T> {
+            erased: dyn* ::core::fmt::Debug,
+            value: T,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::verbatim::Inspect, ex.target_type(), |block| {
+            block.make_function(RUNTIME::verbatim::Inspect::inspect_all, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        for field in receiver.fields() {
+                            RUNTIME::verbatim::Inspector::inspect.INVOKE(field.get_value());
+                        }
+                    }
+                    _ => unimplemented!(),
+                }
+                make_function.unit()
+            });
+        });
+    }
+
+    let expected = quote! {
+        impl<__T0> ::verbatim::Inspect for Erased<__T0> {
+            fn inspect_all<'__a1>(&'__a1 self) {
+                let __v0 = self;
+                let __v1 = &__v0.erased;
+                let __v2 = &__v0.value;
+                let _ = ::verbatim::Inspector::inspect(__v1);
+                let _ = ::verbatim::Inspector::inspect(__v2);
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_verbatim_inferred() {
+    let input = quote! {
+        struct Erased {
+            erased: dyn* ::core::fmt::Debug,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::verbatim::Erase, ex.target_type(), |block| {
+            block.make_function(RUNTIME::verbatim::Erase::erase, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        receiver.fields().next().unwrap().get_value()
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The trait argument is inferred to be the verbatim type
+    let expected = quote! {
+        impl ::verbatim::Erase<dyn* ::core::fmt::Debug> for Erased {
+            fn erase(self) -> dyn* ::core::fmt::Debug {
+                let __v0 = self;
+                let __v1 = __v0.erased;
+                __v1
+            }
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_verbatim_path_mismatch() {
+    let input = quote! {
+        struct Erased {
+            erased: dyn* ::core::fmt::Debug,
+        }
+    };
+
+    fn derive(ex: Execution) {
+        ex.make_trait_impl(RUNTIME::verbatim::Erase, ex.target_type(), |block| {
+            block.make_function(RUNTIME::verbatim::Erase::erase, |make_function| {
+                let receiver = make_function.arg(0);
+                match receiver.data() {
+                    Data::Struct(Struct::Struct(receiver)) => {
+                        let erased = receiver.fields().next().unwrap().get_value();
+                        RUNTIME::verbatim::Inspector::inspect_string.INVOKE(erased)
+                    }
+                    _ => unimplemented!(),
+                }
+            });
+        });
+    }
+
+    // The verbatim type is not a path, so it is not a `String`
+    let expected = quote! {
+        compile_error! {
+            "mismatched types: expected `:: std :: string :: String`, found `dyn * :: core :: fmt :: Debug`"
+        }
+    };
+
+    let output = reflect::derive(input, derive);
+    assert_eq!(output.to_string(), expected.to_string());
+}